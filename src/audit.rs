@@ -0,0 +1,143 @@
+//! Formatting of audit log entries and the `--debug` run summary, with the time source
+//! abstracted so they can be tested
+//!
+//! **TODO:** Nothing writes an audit log yet. This is the groundwork for one, so that when it's
+//! added, its output can be tested exactly rather than by pattern-matching around a timestamp.
+
+use std::ffi::OsString;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::sandbox::format_argv;
+use crate::types::CommandName;
+
+/// A source of the current time
+pub trait Clock {
+    /// The current time
+    fn now(&self) -> SystemTime;
+}
+
+/// The [`Clock`] used outside of tests, which reads the system's wall-clock time
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> SystemTime {
+        SystemTime::now()
+    }
+}
+
+/// Format `time` as an RFC 3339 UTC timestamp with whole seconds (eg. `2023-11-14T22:13:20Z`)
+///
+/// Times before the Unix epoch are clamped to it, since they can only come from a badly set
+/// clock and the log shouldn't refuse to record anything because of one.
+#[must_use]
+pub fn format_timestamp(time: SystemTime) -> String {
+    let secs = time.duration_since(UNIX_EPOCH).map_or(0, |x| x.as_secs());
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Howard Hinnant's `civil_from_days`, restricted to dates after the epoch
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// Format the audit log entry for launching `argv` (the complete Firejail command line) with
+/// `root` as the sandbox root, timestamped using `clock`
+#[must_use]
+pub fn entry(clock: &dyn Clock, argv: &[OsString], root: Option<&Path>) -> String {
+    let root = root.map_or_else(|| "(none)".into(), Path::to_string_lossy);
+    format!("{} root={root} argv={}", format_timestamp(clock.now()), format_argv(argv))
+}
+
+/// Format the line `--debug` prints once the sandboxed `command` has exited with `exit_code`,
+/// measuring the time since `started` using `clock`
+///
+/// A clock which has gone backwards since `started` gives an elapsed time of zero.
+#[must_use]
+pub fn summary(
+    clock: &dyn Clock,
+    started: SystemTime,
+    exit_code: i32,
+    command: &CommandName,
+    root: Option<&Path>,
+) -> String {
+    let elapsed = clock.now().duration_since(started).unwrap_or_default();
+    let root = root.map_or_else(|| "(none)".into(), Path::to_string_lossy);
+    format!(
+        "Finished: exit code {exit_code} after {:.2}s (profile: {command}, root: {root})",
+        elapsed.as_secs_f64()
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    /// A [`Clock`] frozen at a fixed number of seconds after the epoch
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> SystemTime {
+            UNIX_EPOCH + Duration::from_secs(self.0)
+        }
+    }
+
+    /// Assert that an injected clock yields an exact, predictable log line
+    #[test]
+    fn entry_uses_injected_clock() {
+        let argv = ["firejail".into(), "--".into(), "make".into(), "all targets".into()];
+        assert_eq!(
+            entry(&FixedClock(1_700_000_000), &argv, Some(Path::new("/src/project"))),
+            "2023-11-14T22:13:20Z root=/src/project argv=firejail -- make 'all targets'"
+        );
+        assert_eq!(
+            entry(&FixedClock(0), &argv[..3], None),
+            "1970-01-01T00:00:00Z root=(none) argv=firejail -- make"
+        );
+    }
+
+    /// Assert that the summary measures elapsed time with the injected clock
+    #[test]
+    fn summary_uses_injected_clock() {
+        let command = CommandName::try_from("make".to_owned()).unwrap();
+        let started = UNIX_EPOCH + Duration::from_millis(97_250);
+        assert_eq!(
+            summary(&FixedClock(100), started, 2, &command, Some(Path::new("/src/project"))),
+            "Finished: exit code 2 after 2.75s (profile: make, root: /src/project)"
+        );
+        assert_eq!(
+            summary(&FixedClock(90), started, 0, &command, None),
+            "Finished: exit code 0 after 0.00s (profile: make, root: (none))"
+        );
+    }
+
+    /// Assert that the calendar arithmetic handles leap days and the ends of months and years
+    #[test]
+    fn timestamps_across_boundaries() {
+        for (secs, expected) in [
+            (951_782_400, "2000-02-29T00:00:00Z"),
+            (951_868_799, "2000-02-29T23:59:59Z"),
+            (978_307_199, "2000-12-31T23:59:59Z"),
+            (4_107_542_400, "2100-03-01T00:00:00Z"),
+        ] {
+            assert_eq!(format_timestamp(UNIX_EPOCH + Duration::from_secs(secs)), expected);
+        }
+        assert_eq!(format_timestamp(UNIX_EPOCH - Duration::from_secs(1)), "1970-01-01T00:00:00Z");
+    }
+}
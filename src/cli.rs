@@ -1,6 +1,15 @@
 //! Minimal argument parsing, `--help`, and other CLI routines
 
-use std::ffi::OsString;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+use crate::report::ErrorFormat;
+use crate::resolve::{canonical_command_name, raw_subcommand, Overrides};
+use crate::sandbox::{Input, Redirects};
+use crate::types::{CommandName, SubcommandName};
+use crate::{config, explain, log};
 
 /// The action determined to have been requested by [`parse_args`]
 #[derive(Debug, Eq, PartialEq)]
@@ -9,32 +18,170 @@ pub enum Action {
     Exit,
     /// Run the provided command in a sandbox.
     Sandbox(ChildArgs),
+    /// Print what would be done with the provided command instead of running it.
+    Explain(explain::Format, ChildArgs),
     /// Print the configuration file path to stdout and exit
-    PathToConf,
+    ///
+    /// If `canonical` is set, resolve symlinks in it first.
+    PathToConf {
+        /// Whether `--canonical` followed `--conf-path`
+        canonical: bool,
+    },
     /// Write the active configuration file to disk and output the path written to.
-    WriteConf,
-    // TODO: Decide on the best way to present a listing of available profiles
+    ///
+    /// If `merge` is set, add new default keys to an existing file instead.
+    WriteConf {
+        /// Whether `--merge` followed `--write-conf`
+        merge: bool,
+    },
+    /// Print the effective configuration, with defaults filled in, to stdout
+    DumpConf,
+    /// Print the project root that would be used for the given command line (which may be empty)
+    PrintRoot(Vec<OsString>),
+    /// Describe what would be decided for a command line, with the project root taken from the
+    /// optional path rather than searched for
+    TestResolve(Vec<OsString>, Option<PathBuf>),
+    /// Print the named command's profile as a standalone Firejail `.profile` file
+    ExportProfile(OsString),
+    /// Compare the Firejail command lines generated for two named commands' profiles, with the
+    /// project root taken from the optional path
+    DiffProfiles(OsString, OsString, Option<PathBuf>),
+    /// Report which profiles' `root_marked_by` markers appear anywhere under a directory
+    PruneReport(PathBuf),
+    /// Check profile resolution against the recorded cases in a directory
+    Replay(PathBuf),
+    /// Print the effective policy of every configured profile, for auditing
+    ExplainAll,
+    /// List the configured profiles in the given format
+    ListProfiles(explain::Format),
+    /// Print advisory warnings about the configuration file's style, such as network access
+    /// granted without a comment explaining why
+    Lint,
+    /// Report a problem with the command line and exit with a failure status
+    BadUsage(&'static str),
 }
 
 /// Parsed information that is relevant to launching a sandboxed subprocess
+#[allow(clippy::struct_excessive_bools)] // Independent command-line flags, not a state machine
 #[derive(Debug, Default, Eq, PartialEq)]
 pub struct ChildArgs {
     /// If `true`, print diagnostic output for troubleshooting or refining sandbox profiles
     pub debug: bool,
+    /// If `true`, also print each directory examined while looking for the project root
+    /// (`--trace`, which implies `debug`)
+    pub trace: bool,
+    /// If `true`, print the Firejail command line instead of running it
+    ///
+    /// | `debug` | `dry_run` | stdout           | stderr                                    | Runs? |
+    /// |---------|-----------|------------------|-------------------------------------------|-------|
+    /// | no      | no        | (the command's)  | warnings                                  | yes   |
+    /// | no      | yes       | the command line | warnings                                  | no    |
+    /// | yes     | no        | (the command's)  | diagnostics, `Running: ...`               | yes   |
+    /// | yes     | yes       | the command line | diagnostics, `Not running (--dry-run)...` | no    |
+    pub dry_run: bool,
+    /// If `true` (only valid with `dry_run`), also check that the paths the sandbox would expose
+    /// exist and are accessible
+    pub check: bool,
+    /// If `true`, run an interactive shell inside the sandbox `child_argv` would get instead of
+    /// `child_argv` itself
+    pub shell: bool,
+    /// If `true`, suppress `nodo`'s own advisory warnings (but not errors or Firejail's output)
+    pub quiet: bool,
+    /// Per-invocation replacements for decisions normally made by [`Config::resolve`]
+    ///
+    /// [`Config::resolve`]: crate::config::Config::resolve
+    pub overrides: Overrides,
+    /// Where the command's standard streams should go instead of `nodo`'s own
+    pub redirects: Redirects,
+    /// Environment variables given with `--env` to set for the command, on top of (and
+    /// overriding) the profile's `env_set`
+    pub env: BTreeMap<String, String>,
     /// The command-line to be passed to Firejail after the generated sandboxing directives
     pub child_argv: Vec<OsString>,
 }
 
+impl ChildArgs {
+    /// The profile name implied by `argv[0]` (its basename, minus any of `strip_suffixes`), if
+    /// it forms a valid one
+    ///
+    /// This is the same interpretation [`Config::resolve`] uses (given the configuration's
+    /// `strip_suffixes`), so diagnostics can't disagree with it.
+    ///
+    /// [`Config::resolve`]: crate::config::Config::resolve
+    #[must_use]
+    pub fn command(&self, strip_suffixes: &[String]) -> Option<CommandName> {
+        canonical_command_name(self.child_argv.first()?, strip_suffixes)
+    }
+
+    /// The subcommand in `argv[1]` before alias resolution, if there is one
+    ///
+    /// Flag-shaped arguments (those beginning with `-`) aren't subcommands. (A `--` separating
+    /// `nodo`'s own flags from the command has already been removed by [`parse_args`].)
+    pub fn subcommand(&self) -> Option<SubcommandName> {
+        raw_subcommand(self.child_argv.get(1).map(OsString::as_os_str))
+    }
+
+    /// The [`log`] threshold these flags call for, falling back to `from_env` (the level named
+    /// by [`log::LOG_ENV_VAR`], if set) when none of `--debug`, `--trace`, or `--quiet` was given
+    #[must_use]
+    pub fn log_level(&self, from_env: Option<log::Level>) -> log::Level {
+        if self.trace {
+            log::Level::Trace
+        } else if self.debug {
+            log::Level::Debug
+        } else if self.quiet {
+            log::Level::Error
+        } else {
+            from_env.unwrap_or_default()
+        }
+    }
+}
+
 /// Print the `--help` output to stdout
 ///
+/// The default form is a summary of the common flags which fits in 80 columns. `verbose`
+/// (`--help --verbose` or `--help-all`) selects the full reference.
+fn print_help(verbose: bool) {
+    if verbose {
+        print_help_verbose();
+        return;
+    }
+    println!(
+        concat!(
+            "{wrapper_bin} {wrapper_version}\n",
+            "\n",
+            "USAGE:\n",
+            "    {wrapper_bin} [-d|--debug] [--dry-run] [--] <command> [arguments]\n",
+            "    {wrapper_bin} --explain [--json] [--] <command> [arguments]\n",
+            "\n",
+            "COMMON OPTIONS:\n",
+            "    -d, --debug       Print diagnostics and let Firejail report problems\n",
+            "        --dry-run     Print the Firejail command line instead of running it\n",
+            "        --explain     Describe the sandbox for <command> without running it\n",
+            "        --profile <name>\n",
+            "                      Use the profile <name> instead of the one for <command>\n",
+            "        --conf-path   Print where the configuration file is looked for\n",
+            "    -h, --help        Print this summary\n",
+            "    -V, --version     Print the version number\n",
+            "\n",
+            "Run `{wrapper_bin} --help --verbose` for every option and more detail."
+        ),
+        wrapper_bin = env!("CARGO_PKG_NAME"),
+        wrapper_version = env!("CARGO_PKG_VERSION"),
+    );
+}
+
+/// Print the `--help --verbose` output to stdout
+///
 /// # Note to Future Maintainers
 ///
-/// When making any changes to this, check how `help2man --no-info target/debug/nodo | man -l -`
-/// interprets it.
+/// This is the form meant for `help2man`. When making any changes to this, check how
+/// `help2man --no-info --help-option=--help-all target/debug/nodo | man -l -` interprets it.
 ///
 /// Pay particular attention to how its hard word-wrapping detection can remove line-breaks. (This
 /// is why there are two newline characters between each `USAGE` line.)
-fn print_help() {
+#[allow(clippy::too_many_lines)] // A single string literal, which splitting would only obscure
+fn print_help_verbose() {
     println!(
         concat!(
             "{wrapper_bin} {wrapper_version}\n",
@@ -42,9 +189,31 @@ fn print_help() {
             "{wrapper_desc}.\n",
             "\n",
             "USAGE:\n",
-            "    {wrapper_bin} [-d|--debug|--] <command> [subcommand] [arguments]\n",
+            "    {wrapper_bin} [-d|--debug|--trace|-q|--quiet] [--dry-run [--check]] [--shell]\n",
+            "        [--profile <name>] [--root <dir>] [--] <command> [subcommand] [arguments]\n",
+            "\n",
+            "    {wrapper_bin} --explain [--json|--firejail-syntax] [--profile <name>] [--root <dir>]\n",
+            "        [--] <command> [subcommand] [arguments]\n",
+            "\n",
+            "    {wrapper_bin} [-h|-V|--help|--version|--conf-path|--dump-config|--lint]\n",
+            "\n",
+            "    {wrapper_bin} --help --verbose\n",
+            "\n",
+            "    {wrapper_bin} --conf-path [--canonical]\n",
+            "\n",
+            "    {wrapper_bin} --write-conf [--merge]\n",
+            "\n",
+            "    {wrapper_bin} --prune-report <directory>\n",
             "\n",
-            "    {wrapper_bin} [-h|-V|--help|--version|--conf-path|--write-conf]\n",
+            "    {wrapper_bin} --print-root [--] [<command> [subcommand] [arguments]]\n",
+            "\n",
+            "    {wrapper_bin} --test-resolve [--root <dir>] [--] <command> [subcommand] [arguments]\n",
+            "\n",
+            "    {wrapper_bin} --diff-profiles <command> <command> [--root <dir>]\n",
+            "\n",
+            "    {wrapper_bin} --replay <directory>\n",
+            "\n",
+            "    {wrapper_bin} [--error-format <human|json>] [--config <path>] <any of the above>\n",
             "\n",
             "OPTIONS:\n",
             "        --            Treat all following arguments as part of the command to be\n",
@@ -54,26 +223,232 @@ fn print_help() {
             "    -d, --debug       Print information on commands being executed and\n",
             "                      omit --quiet from the Firejail command line so that problems\n",
             "                      with sandboxing policies can be diagnosed.\n",
-            "    -h, --help        Print this help message to standard output\n",
+            "        --trace       Like --debug, but also print each directory examined while\n",
+            "                      looking for the project root and which of the root_marked_by\n",
+            "                      markers it contains.\n",
+            "                      (If none of --debug, --trace, or --quiet is given, the\n",
+            "                      {debug_env} environment variable may be set to 1 or 2 to\n",
+            "                      act as --debug or --trace.)\n",
+            "    -q, --quiet       Suppress {wrapper_bin}'s own warnings. (Unlike --debug, this\n",
+            "                      doesn't change the flags passed to Firejail.)\n",
+            "                      (If none of --debug, --trace, or --quiet is given, the\n",
+            "                      {log_env} environment variable may be set to error, warn,\n",
+            "                      info, debug, or trace to choose which of {wrapper_bin}'s own\n",
+            "                      messages are shown without changing the Firejail flags.)\n",
+            "        --dry-run     Print the Firejail command line that would be run instead\n",
+            "                      of running it. Combined with --debug, the diagnostics\n",
+            "                      still go to standard error and only the command line goes\n",
+            "                      to standard output.\n",
+            "        --check       (With --dry-run) Also warn if the project root or any\n",
+            "                      whitelist entry doesn't exist or can't be read.\n",
+            "        --shell       Run an interactive shell ($SHELL, or /bin/sh if unset)\n",
+            "                      inside exactly the sandbox <command> would get, instead\n",
+            "                      of <command>, to investigate failures.\n",
+            "        --explain     Print the profile, sandbox root, and other decisions that\n",
+            "                      would apply to <command> instead of running it.\n",
+            "        --json        (After --explain) Print the decisions as a single JSON\n",
+            "                      object. Paths which aren't valid UTF-8 are encoded as\n",
+            "                      arrays of bytes rather than strings.\n",
+            "        --firejail-syntax\n",
+            "                      (After --explain) Print the Firejail options that would be\n",
+            "                      used as the directives of a Firejail .profile file, one per\n",
+            "                      line (eg. net none), for review.\n",
+            "        --profile <name>\n",
+            "                      Use the profile named <name> instead of the one matching\n",
+            "                      <command>. (<command> is still what gets run.)\n",
+            "        --env <KEY=VALUE>\n",
+            "                      Set the environment variable KEY to VALUE for <command>,\n",
+            "                      overriding any value from the profile's env_set. May be\n",
+            "                      given more than once.\n",
+            "        --stdin <path|null>\n",
+            "                      Read <command>'s standard input from <path>, or give it\n",
+            "                      none at all with null.\n",
+            "        --stdout <path>, --stderr <path>\n",
+            "                      Write <command>'s standard output or error (along with\n",
+            "                      Firejail's) to <path>, replacing its contents. <path> must\n",
+            "                      be somewhere the sandbox could write to itself: inside the\n",
+            "                      project root (or a writable_subdirs entry) or a whitelist\n",
+            "                      entry, and not blacklisted.\n",
+            "        --root <dir>  Use <dir> as the sandbox root instead of searching for the\n",
+            "                      profile's root_marked_by markers. It must still contain the\n",
+            "                      current directory and not be / or your home directory.\n",
+            "    -h, --help        Print a summary of the common options to standard output.\n",
+            "                      Add --verbose (or use --help-all) for this full reference.\n",
             "    -V, --version     Print the version number to standard output\n",
             "        --conf-path   Print the path where {wrapper_bin} will look for the\n",
             "                      configuration file or write it if --write-conf is used.\n",
+            "                      This is built from $XDG_CONFIG_HOME or $HOME as-is. Add\n",
+            "                      --canonical to resolve any symlinks in it first.\n",
             "        --write-conf  Save the active configuration to a file and report where it \n",
-            "                      was saved via stdout.\n",
+            "                      was saved via stdout. With --merge, add any new default\n",
+            "                      settings to an existing file, preserving its comments.\n",
+            "        --dump-config Print the configuration {wrapper_bin} would use, with all\n",
+            "                      defaults filled in, as TOML.\n",
+            "        --explain-all Print each profile's network access (by default and for\n",
+            "                      which subcommands), denied subcommands, root markers, and\n",
+            "                      Firejail flags, with the root written as {export_root}.\n",
+            "        --list-profiles [--json]\n",
+            "                      List each profile's name and root markers or, with --json,\n",
+            "                      print an array of objects also giving its network default\n",
+            "                      and its network, denied, and projectless subcommands.\n",
+            "        --lint        Warn about profiles which grant network access without a\n",
+            "                      comment (above the setting or at the end of its line)\n",
+            "                      saying why. This is advisory, so the exit status is still 0.\n",
+            "        --print-root  Print the project root that would be used for <command>\n",
+            "                      (or, without one, the nearest directory containing any\n",
+            "                      profile's root_marked_by markers) and exit with status 1 if\n",
+            "                      there is none.\n",
+            "        --prune-report <directory>\n",
+            "                      Search <directory> (up to {prune_depth} levels deep) for each\n",
+            "                      profile's root_marked_by markers and list the profiles with\n",
+            "                      none, as candidates for removal. Nothing is modified.\n",
+            "        --test-resolve [--root <dir>] [--] <command> [subcommand] [arguments]\n",
+            "                      Print the decisions --explain would for the given command\n",
+            "                      line without needing to be in a project. The root is <dir>\n",
+            "                      if given, or else the markers which would be searched for\n",
+            "                      are listed.\n",
+            "        --export-profile <command>\n",
+            "                      Print <command>'s profile as a Firejail .profile file for use\n",
+            "                      without {wrapper_bin}. The project root is found anew on every\n",
+            "                      run, so it's written as {export_root} and must be replaced.\n",
+            "        --diff-profiles <command> <command> [--root <dir>]\n",
+            "                      Compare the Firejail command lines the two commands' profiles\n",
+            "                      would produce, one argument per line, marking those only the\n",
+            "                      first has with - and those only the second has with +. The\n",
+            "                      root is <dir> if given, or else {export_root}. The exit\n",
+            "                      status is 1 if they differ, as with diff(1).\n",
+            "        --replay <directory>\n",
+            "                      Resolve each recorded case (a .toml file giving argv, cwd,\n",
+            "                      files to create, and [expected] --explain --json values) in\n",
+            "                      <directory> in a scratch directory and report any whose\n",
+            "                      results differ. The exit status is 1 if any do. Scratch\n",
+            "                      directories are made in ${temp_env} if set, or else the\n",
+            "                      usual temporary directory.\n",
+            "        --error-format <human|json>\n",
+            "                      (Before any other option) Report fatal errors on standard\n",
+            "                      error as prose (the default) or as a single JSON object with\n",
+            "                      \"kind\" and \"message\" fields and, where relevant, \"path\"\n",
+            "                      and \"profile\" fields. The exit status is the same either way.\n",
+            "        --config <path>\n",
+            "                      (Before any other option except --error-format) Read the\n",
+            "                      configuration from <path> instead of the usual location, or\n",
+            "                      from standard input if <path> is -. The file read is the one\n",
+            "                      the sandbox denies access to. If ${sha256_env} is set,\n",
+            "                      the configuration must have that SHA-256 digest (as printed\n",
+            "                      by sha256sum) or {wrapper_bin} refuses to run.\n",
             "\n",
             "<command> and [subcommand] will be used to look up a sandboxing profile in the\n",
             "configuration file and then <command> [subcommand] [arguments] will be executed as\n",
-            "a subprocess inside a Firejail sandbox.\n",
+            "a subprocess inside a Firejail sandbox. If no profile matches, {wrapper_bin} exits\n",
+            "with status 2 rather than running anything. If the configuration file is invalid,\n",
+            "it exits with status 3.\n",
+            "\n",
+            "Subcommands in a profile's no_root_subcommands (eg. cargo install) skip the search\n",
+            "and use the profile's no_root_base as the sandbox root. If no_root_base isn't set,\n",
+            "the root is your home directory, so ALL of it is exposed to them, read-write.\n",
             "\n",
             "Please report any issues at {repo_url}"
         ),
-        wrapper_bin = env!("CARGO_BIN_NAME"),
+        wrapper_bin = env!("CARGO_PKG_NAME"),
         wrapper_version = env!("CARGO_PKG_VERSION"),
         wrapper_desc = env!("CARGO_PKG_DESCRIPTION"),
+        debug_env = DEBUG_ENV_VAR,
+        log_env = log::LOG_ENV_VAR,
+        temp_env = crate::temp::TEMP_ENV_VAR,
+        sha256_env = crate::config::SHA256_ENV_VAR,
+        prune_depth = crate::prune::MAX_DEPTH,
+        export_root = crate::sandbox::EXPORT_ROOT_PLACEHOLDER,
         repo_url = env!("CARGO_PKG_REPOSITORY"),
     );
 }
 
+/// Remove a leading `--error-format <format>` from `argv`, returning the format it selects
+///
+/// Because it applies to every action, it must come before everything else (including
+/// terminal flags like `--dump-config`) and is stripped before [`parse_args`] sees `argv`.
+///
+/// # Errors
+///
+/// Fails if `--error-format` isn't followed by `human` or `json`.
+pub fn take_error_format(argv: &mut Vec<OsString>) -> Result<ErrorFormat, &'static str> {
+    if argv.get(1).is_none_or(|x| x != "--error-format") {
+        return Ok(ErrorFormat::Human);
+    }
+    let format = match argv.get(2).and_then(|x| x.to_str()) {
+        Some("human") => ErrorFormat::Human,
+        Some("json") => ErrorFormat::Json,
+        _ => return Err("--error-format must be followed by `human` or `json`"),
+    };
+    argv.drain(1..3);
+    Ok(format)
+}
+
+/// Remove a leading `--config <path>` from `argv`, returning where the configuration should be
+/// read from (`-` for standard input)
+///
+/// Like `--error-format`, this applies to every action, so it must come before everything else
+/// (but after `--error-format`, if both are given) and is stripped before [`parse_args`].
+///
+/// # Errors
+///
+/// Fails if `--config` isn't followed by a path.
+pub fn take_config(argv: &mut Vec<OsString>) -> Result<config::Source, &'static str> {
+    if argv.get(1).is_none_or(|x| x != "--config") {
+        return Ok(config::Source::Default);
+    }
+    let source = match argv.get(2) {
+        Some(path) if path == "-" => config::Source::Stdin,
+        Some(path) if !path.is_empty() => config::Source::File(PathBuf::from(path)),
+        _ => return Err("--config must be followed by a path or `-` for standard input"),
+    };
+    argv.drain(1..3);
+    Ok(source)
+}
+
+/// Every argument [`take_error_format`], [`take_config`], or [`parse_args`] will intercept as one
+/// of `nodo`'s own flags when it comes first, so a command by that name can only be run after `--`
+pub const RESERVED_FLAGS: &[&str] = &[
+    "--",
+    "--check",
+    "--conf-path",
+    "--config",
+    "--debug",
+    "--diff-profiles",
+    "--dry-run",
+    "--dump-config",
+    "--env",
+    "--error-format",
+    "--explain",
+    "--explain-all",
+    "--export-profile",
+    "--help",
+    "--help-all",
+    "--lint",
+    "--list-profiles",
+    "--print-root",
+    "--profile",
+    "--prune-report",
+    "--quiet",
+    "--replay",
+    "--root",
+    "--shell",
+    "--stderr",
+    "--stdin",
+    "--stdout",
+    "--test-resolve",
+    "--trace",
+    "--version",
+    "--write-conf",
+    "-V",
+    "-d",
+    "-h",
+    "-q",
+];
+
+/// The environment variable which sets the debug level when neither `--debug`, `--trace`, nor
+/// `--quiet` is given (`0` for none, `1` for `--debug`, `2` for `--trace`)
+pub const DEBUG_ENV_VAR: &str = "NODO_DEBUG";
+
 /// Helper to abstract away the handful of flags we don't just pass through
 ///
 /// We don't use a command-line argument parsing library because:
@@ -81,44 +456,227 @@ fn print_help() {
 /// 1. They tend to just be footguns for this kind of wrapper
 /// 2. It represents another external dependency that may be vulnerable to a supply-chain attack.
 pub fn parse_args(args: impl Iterator<Item = OsString>) -> Action {
+    parse_args_with_env(args, env::var_os(DEBUG_ENV_VAR).as_deref())
+}
+
+/// Implementation of [`parse_args`] with the value of [`DEBUG_ENV_VAR`] passed in so tests don't
+/// depend on the environment they're run in
+fn parse_args_with_env(args: impl Iterator<Item = OsString>, debug_env: Option<&OsStr>) -> Action {
     let mut debug = false;
+    let mut trace = false;
+    let mut dry_run = false;
+    let mut check = false;
+    let mut shell = false;
+    let mut quiet = false;
+    let mut explain = None;
+    let mut overrides = Overrides::default();
+    let mut redirects = Redirects::default();
+    let mut env = BTreeMap::new();
     let mut child_argv: Vec<_> = args.skip(1).collect();
 
-    match child_argv.get(0).map(|x| x.to_string_lossy()).as_deref() {
-        Some("--") => {
-            // Since we only inspect the first argument for this, removing it is enough
-            child_argv.remove(0);
-        },
-        Some("--debug" | "-d") => {
-            debug = true;
-            child_argv.remove(0);
-        },
+    if let Some(action) = parse_terminal_flag(&mut child_argv) {
+        return action;
+    }
+
+    // Consume the leading group of flags which modify how the command is handled, stopping at
+    // `--` or the first argument that isn't one of them so the child's own flags are untouched
+    while let Some(arg) = child_argv.first().map(|x| x.to_string_lossy().into_owned()) {
+        match arg.as_str() {
+            "--" => {
+                child_argv.remove(0);
+                break;
+            },
+            "--debug" | "-d" => debug = true,
+            "--trace" => (debug, trace) = (true, true),
+            "--dry-run" => dry_run = true,
+            "--check" => check = true,
+            "--shell" => shell = true,
+            "--quiet" | "-q" => quiet = true,
+            "--explain" => explain = Some(explain::Format::Human),
+            "--json" if explain.is_some() => explain = Some(explain::Format::Json),
+            "--firejail-syntax" if explain.is_some() => {
+                explain = Some(explain::Format::FirejailSyntax);
+            },
+            "--profile" if child_argv.len() > 1 => overrides.profile = Some(child_argv.remove(1)),
+            "--root" if child_argv.len() > 1 => {
+                overrides.root = Some(PathBuf::from(child_argv.remove(1)));
+            },
+            "--env" if child_argv.len() > 1 => match parse_env_pair(&child_argv.remove(1)) {
+                Ok((key, value)) => {
+                    env.insert(key, value);
+                },
+                Err(message) => return Action::BadUsage(message),
+            },
+            "--stdin" if child_argv.len() > 1 => {
+                let source = child_argv.remove(1);
+                redirects.stdin =
+                    Some(if source == "null" { Input::Null } else { Input::File(source.into()) });
+            },
+            "--stdout" if child_argv.len() > 1 => {
+                redirects.stdout = Some(PathBuf::from(child_argv.remove(1)));
+            },
+            "--stderr" if child_argv.len() > 1 => {
+                redirects.stderr = Some(PathBuf::from(child_argv.remove(1)));
+            },
+            "--profile" | "--root" | "--env" | "--stdin" | "--stdout" | "--stderr" => {
+                // A missing profile name or root is as much a usage error as a missing command
+                child_argv.clear();
+                break;
+            },
+            _ => break,
+        }
+        child_argv.remove(0);
+    }
+
+    // Don't let `--` suppress the "help on 'no command provided'" behaviour
+    if child_argv.is_empty() {
+        print_help(false);
+        return Action::Exit;
+    }
+
+    // Explicit flags always win over the environment
+    if !(debug || quiet) {
+        match debug_env.map(|x| x.to_string_lossy()).as_deref() {
+            None | Some("" | "0") => (),
+            Some("1") => debug = true,
+            Some("2") => (debug, trace) = (true, true),
+            Some(_) => return Action::BadUsage("NODO_DEBUG must be 0, 1, or 2"),
+        }
+    }
+
+    if check && !dry_run {
+        return Action::BadUsage("--check can only be used with --dry-run");
+    }
+
+    if debug && quiet {
+        return Action::BadUsage("--debug (or --trace) and --quiet contradict each other");
+    }
+
+    let parsed = ChildArgs {
+        debug,
+        trace,
+        dry_run,
+        check,
+        shell,
+        quiet,
+        overrides,
+        redirects,
+        env,
+        child_argv,
+    };
+    match explain {
+        Some(format) => Action::Explain(format, parsed),
+        None => Action::Sandbox(parsed),
+    }
+}
+
+/// Split the argument to `--env` into a variable name and value at the first `=`
+fn parse_env_pair(pair: &OsStr) -> Result<(String, String), &'static str> {
+    let pair = pair.to_str().ok_or("--env must be followed by valid UTF-8")?;
+    let (key, value) = pair.split_once('=').ok_or("--env must be followed by KEY=VALUE")?;
+    if !config::is_env_name(key) {
+        return Err("--env names may only contain ASCII letters, digits, and _, and may not \
+                    start with a digit");
+    }
+    if key == crate::sandbox::ACTIVE_ENV_VAR {
+        return Err("--env can't set NODO_ACTIVE, which is how nodo detects that it's already \
+                    inside a sandbox");
+    }
+    Ok((key.to_owned(), value.to_owned()))
+}
+
+/// Handle the options which are only recognized as `argv[1]` and replace running a command
+/// entirely (eg. `--help`), returning `None` if `child_argv` doesn't start with one
+fn parse_terminal_flag(child_argv: &mut Vec<OsString>) -> Option<Action> {
+    let action = match child_argv.first().map(|x| x.to_string_lossy()).as_deref() {
         Some("--conf-path") => {
-            return Action::PathToConf;
+            let canonical = child_argv.get(1).is_some_and(|x| x == "--canonical");
+            Action::PathToConf { canonical }
         },
         None | Some("--help" | "-h") => {
             // No arguments, --help, or -h
-            print_help();
-            return Action::Exit;
+            print_help(child_argv.get(1).is_some_and(|x| x == "--verbose"));
+            Action::Exit
+        },
+        Some("--help-all") => {
+            // A single-argument spelling of `--help --verbose` for help2man
+            print_help(true);
+            Action::Exit
         },
         Some("--version" | "-V") => {
             // Needed by help2man
             println!("{}", env!("CARGO_PKG_VERSION"));
-            return Action::Exit;
+            Action::Exit
         },
         Some("--write-conf") => {
-            return Action::WriteConf;
+            let merge = child_argv.get(1).is_some_and(|x| x == "--merge");
+            Action::WriteConf { merge }
         },
-        _ => (),
-    }
-
-    // Don't let `--` suppress the "help on 'no command provided'" behaviour
-    if child_argv.get(0).is_none() {
-        print_help();
-        return Action::Exit;
-    }
-
-    Action::Sandbox(ChildArgs { debug, child_argv })
+        Some("--dump-config") => Action::DumpConf,
+        Some("--explain-all") => Action::ExplainAll,
+        Some("--lint") => Action::Lint,
+        Some("--list-profiles") => match child_argv.get(1..) {
+            None | Some([]) => Action::ListProfiles(explain::Format::Human),
+            Some([flag]) if flag == "--json" => Action::ListProfiles(explain::Format::Json),
+            Some(_) => Action::BadUsage("--list-profiles takes no arguments except --json"),
+        },
+        Some("--print-root") => {
+            child_argv.remove(0);
+            if child_argv.first().is_some_and(|x| x == "--") {
+                child_argv.remove(0);
+            }
+            Action::PrintRoot(std::mem::take(child_argv))
+        },
+        Some("--test-resolve") => {
+            child_argv.remove(0);
+            let mut root = None;
+            if child_argv.first().is_some_and(|x| x == "--root") {
+                let Some(dir) = child_argv.get(1) else {
+                    return Some(Action::BadUsage("--root must be followed by a directory"));
+                };
+                root = Some(PathBuf::from(dir));
+                child_argv.drain(..2);
+            }
+            if child_argv.first().is_some_and(|x| x == "--") {
+                child_argv.remove(0);
+            }
+            if child_argv.is_empty() {
+                return Some(Action::BadUsage("--test-resolve must be followed by a command"));
+            }
+            Action::TestResolve(std::mem::take(child_argv), root)
+        },
+        Some("--export-profile") => match child_argv.get(1) {
+            Some(command) => Action::ExportProfile(command.clone()),
+            None => Action::BadUsage("--export-profile must be followed by a command name"),
+        },
+        Some("--diff-profiles") => {
+            let root = match child_argv.get(3..) {
+                None | Some([]) => None,
+                Some([flag, root]) if flag == "--root" => Some(PathBuf::from(root)),
+                Some(_) => {
+                    return Some(Action::BadUsage(
+                        "--diff-profiles takes two command names and an optional --root <dir>",
+                    ))
+                },
+            };
+            match (child_argv.get(1), child_argv.get(2)) {
+                (Some(first), Some(second)) => {
+                    Action::DiffProfiles(first.clone(), second.clone(), root)
+                },
+                _ => Action::BadUsage("--diff-profiles must be followed by two command names"),
+            }
+        },
+        Some("--replay") => match child_argv.get(1) {
+            Some(dir) => Action::Replay(PathBuf::from(dir)),
+            None => Action::BadUsage("--replay must be followed by a directory"),
+        },
+        Some("--prune-report") => match child_argv.get(1) {
+            Some(dir) => Action::PruneReport(PathBuf::from(dir)),
+            None => Action::BadUsage("--prune-report must be followed by a directory"),
+        },
+        _ => return None,
+    };
+    Some(action)
 }
 
 #[cfg(test)]
@@ -128,10 +686,10 @@ mod test {
     /// Helper for applying parse_args to test input more concisely
     macro_rules! test_args {
         ($( $arg:expr ),*) => {
-            parse_args([
-                OsString::from(env!("CARGO_BIN_NAME")),
+            parse_args_with_env([
+                OsString::from(env!("CARGO_PKG_NAME")),
                 $( OsString::from($arg) ),*
-            ].into_iter())
+            ].into_iter(), None)
         }
     }
 
@@ -140,7 +698,8 @@ mod test {
         ($debug:expr, $( $arg:expr ),*) => {
             Action::Sandbox(ChildArgs {
                     debug: $debug,
-                    child_argv: vec![$( OsString::from($arg) ),*]
+                    child_argv: vec![$( OsString::from($arg) ),*],
+                    ..ChildArgs::default()
             })
         }
     }
@@ -193,9 +752,468 @@ mod test {
         assert_eq!(test_args!("-h"), Action::Exit);
         assert_eq!(test_args!("-V"), Action::Exit);
         assert_eq!(test_args!("--help"), Action::Exit);
+        assert_eq!(test_args!("--help", "--verbose"), Action::Exit);
+        assert_eq!(test_args!("--help-all"), Action::Exit);
         assert_eq!(test_args!("--version"), Action::Exit);
-        assert_eq!(test_args!("--conf-path"), Action::PathToConf);
-        assert_eq!(test_args!("--write-conf"), Action::WriteConf);
+        assert_eq!(test_args!("--conf-path"), Action::PathToConf { canonical: false });
+        assert_eq!(
+            test_args!("--conf-path", "--canonical"),
+            Action::PathToConf { canonical: true }
+        );
+        assert_eq!(test_args!("--write-conf"), Action::WriteConf { merge: false });
+        assert_eq!(test_args!("--write-conf", "--merge"), Action::WriteConf { merge: true });
+        assert_eq!(test_args!("--dump-config"), Action::DumpConf);
+        assert_eq!(test_args!("--lint"), Action::Lint);
+        assert_eq!(test_args!("--explain-all"), Action::ExplainAll);
+        assert_eq!(test_args!("--list-profiles"), Action::ListProfiles(explain::Format::Human));
+        assert_eq!(
+            test_args!("--list-profiles", "--json"),
+            Action::ListProfiles(explain::Format::Json)
+        );
+        assert!(matches!(test_args!("--list-profiles", "cargo"), Action::BadUsage(_)));
+        assert_eq!(test_args!("--prune-report", "src"), Action::PruneReport("src".into()));
+        assert_eq!(test_args!("--replay", "cases"), Action::Replay("cases".into()));
+        assert!(matches!(test_args!("--replay"), Action::BadUsage(_)));
+        assert_eq!(test_args!("--print-root"), Action::PrintRoot(vec![]));
+        assert_eq!(
+            test_args!("--test-resolve", "cargo", "publish"),
+            Action::TestResolve(vec!["cargo".into(), "publish".into()], None)
+        );
+        assert_eq!(
+            test_args!("--test-resolve", "--root", "/src/x", "--", "make", "a b", "--root"),
+            Action::TestResolve(
+                vec!["make".into(), "a b".into(), "--root".into()],
+                Some("/src/x".into())
+            )
+        );
+        assert_eq!(test_args!("--export-profile", "cargo"), Action::ExportProfile("cargo".into()));
+        assert!(matches!(test_args!("--export-profile"), Action::BadUsage(_)));
+        assert_eq!(
+            test_args!("--diff-profiles", "make", "gmake"),
+            Action::DiffProfiles("make".into(), "gmake".into(), None)
+        );
+        assert_eq!(
+            test_args!("--diff-profiles", "make", "gmake", "--root", "/src/x"),
+            Action::DiffProfiles("make".into(), "gmake".into(), Some("/src/x".into()))
+        );
+        assert!(matches!(test_args!("--diff-profiles", "make"), Action::BadUsage(_)));
+        assert!(matches!(test_args!("--diff-profiles", "a", "b", "c"), Action::BadUsage(_)));
+        assert!(matches!(test_args!("--test-resolve"), Action::BadUsage(_)));
+        assert!(matches!(test_args!("--test-resolve", "--"), Action::BadUsage(_)));
+        assert!(matches!(test_args!("--test-resolve", "--root"), Action::BadUsage(_)));
+        assert!(matches!(test_args!("--test-resolve", "--root", "/src/x"), Action::BadUsage(_)));
+        assert_eq!(
+            test_args!("--print-root", "--", "cargo", "build"),
+            Action::PrintRoot(vec!["cargo".into(), "build".into()])
+        );
+        assert_eq!(
+            test_args!("--prune-report"),
+            Action::BadUsage("--prune-report must be followed by a directory")
+        );
+    }
+
+    /// Assert that `--dry-run` is recognized alone and alongside `--debug`
+    #[test]
+    fn parse_args_dry_run() {
+        let expected = ChildArgs {
+            dry_run: true,
+            child_argv: vec!["make".into(), "all".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--dry-run", "make", "all"), Action::Sandbox(expected));
+
+        let expected = ChildArgs {
+            debug: true,
+            dry_run: true,
+            child_argv: vec!["make".into(), "--dry-run".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("-d", "--dry-run", "make", "--dry-run"), Action::Sandbox(expected));
+
+        // Not recognized after the command
+        assert_eq!(test_args!("make", "--dry-run"), make_expected!(false, "make", "--dry-run"));
+    }
+
+    /// Assert that `--trace` implies `--debug` and is only recognized before the command
+    #[test]
+    fn parse_args_trace() {
+        let expected = ChildArgs {
+            debug: true,
+            trace: true,
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--trace", "make"), Action::Sandbox(expected));
+        assert_eq!(
+            test_args!("--trace", "-q", "make"),
+            Action::BadUsage("--debug (or --trace) and --quiet contradict each other")
+        );
+        assert_eq!(test_args!("make", "--trace"), make_expected!(false, "make", "--trace"));
+    }
+
+    /// Assert that [`ChildArgs::command`] and [`ChildArgs::subcommand`] split the child command
+    /// line the way profile lookup does
+    #[test]
+    fn child_args_command_split() {
+        let Action::Sandbox(args) = test_args!("/usr/bin/cargo", "build", "--release") else {
+            panic!("expected Action::Sandbox");
+        };
+        assert_eq!(args.command(&[]), Some(CommandName::try_from("cargo".to_owned()).unwrap()));
+        assert_eq!(args.subcommand(), Some(SubcommandName::try_from("build".to_owned()).unwrap()));
+
+        // No subcommand at all
+        let Action::Sandbox(args) = test_args!("make") else { panic!("expected Action::Sandbox") };
+        assert_eq!(args.command(&[]), Some(CommandName::try_from("make".to_owned()).unwrap()));
+        assert_eq!(args.subcommand(), None);
+
+        // A flag in argv[1] isn't a subcommand, even after a `--`-escaped command
+        let Action::Sandbox(args) = test_args!("--", "--debug", "--version") else {
+            panic!("expected Action::Sandbox");
+        };
+        assert_eq!(args.command(&[]), Some(CommandName::try_from("--debug".to_owned()).unwrap()));
+        assert_eq!(args.subcommand(), None);
+
+        // An argv[0] with no usable basename
+        let args = ChildArgs { child_argv: vec!["/".into()], ..ChildArgs::default() };
+        assert_eq!(args.command(&[]), None);
+    }
+
+    /// Assert that `--error-format` is only recognized first and is removed from the arguments
+    #[test]
+    fn take_error_format_strips_leading_option() {
+        let command_line = |args: &[&str]| -> Vec<OsString> {
+            [env!("CARGO_PKG_NAME")].iter().chain(args).map(OsString::from).collect()
+        };
+
+        let mut args = command_line(&["--error-format", "json", "--dump-config"]);
+        assert_eq!(take_error_format(&mut args), Ok(ErrorFormat::Json));
+        assert_eq!(args, command_line(&["--dump-config"]));
+
+        let mut args = command_line(&["--error-format", "human", "make"]);
+        assert_eq!(take_error_format(&mut args), Ok(ErrorFormat::Human));
+        assert_eq!(args, command_line(&["make"]));
+
+        // Elsewhere, it belongs to the child command
+        let mut args = command_line(&["make", "--error-format", "json"]);
+        assert_eq!(take_error_format(&mut args), Ok(ErrorFormat::Human));
+        assert_eq!(args, command_line(&["make", "--error-format", "json"]));
+
+        for bad in [&["--error-format"][..], &["--error-format", "xml", "make"]] {
+            assert_eq!(
+                take_error_format(&mut command_line(bad)),
+                Err("--error-format must be followed by `human` or `json`")
+            );
+        }
+    }
+
+    /// Assert that `--config` is only recognized first and is removed from the arguments
+    #[test]
+    fn take_config_strips_leading_option() {
+        let command_line = |args: &[&str]| -> Vec<OsString> {
+            [env!("CARGO_PKG_NAME")].iter().chain(args).map(OsString::from).collect()
+        };
+
+        let mut args = command_line(&["--config", "/tmp/nodo.toml", "make"]);
+        assert_eq!(take_config(&mut args), Ok(config::Source::File("/tmp/nodo.toml".into())));
+        assert_eq!(args, command_line(&["make"]));
+
+        let mut args = command_line(&["--config", "-", "--dump-config"]);
+        assert_eq!(take_config(&mut args), Ok(config::Source::Stdin));
+        assert_eq!(args, command_line(&["--dump-config"]));
+
+        let mut args = command_line(&["make", "--config", "x"]);
+        assert_eq!(take_config(&mut args), Ok(config::Source::Default));
+        assert_eq!(args, command_line(&["make", "--config", "x"]));
+
+        for bad in [&["--config"][..], &["--config", ""]] {
+            assert_eq!(
+                take_config(&mut command_line(bad)),
+                Err("--config must be followed by a path or `-` for standard input")
+            );
+        }
+    }
+
+    /// Assert that [`DEBUG_ENV_VAR`] sets the debug level only when no flag does
+    #[test]
+    fn parse_args_debug_env() {
+        let parse = |env: &str, args: &[&str]| {
+            let argv = [env!("CARGO_PKG_NAME")].iter().chain(args).map(OsString::from);
+            match parse_args_with_env(argv, Some(OsStr::new(env))) {
+                Action::Sandbox(parsed) => Ok((parsed.debug, parsed.trace, parsed.quiet)),
+                other => Err(Box::new(other)),
+            }
+        };
+        assert_eq!(parse("", &["make"]), Ok((false, false, false)));
+        assert_eq!(parse("0", &["make"]), Ok((false, false, false)));
+        assert_eq!(parse("1", &["make"]), Ok((true, false, false)));
+        assert_eq!(parse("2", &["make"]), Ok((true, true, false)));
+
+        // Explicit flags win in either direction
+        assert_eq!(parse("0", &["--debug", "make"]), Ok((true, false, false)));
+        assert_eq!(parse("2", &["-d", "make"]), Ok((true, false, false)));
+        assert_eq!(parse("2", &["--quiet", "make"]), Ok((false, false, true)));
+
+        assert_eq!(
+            parse("yes", &["make"]),
+            Err(Box::new(Action::BadUsage("NODO_DEBUG must be 0, 1, or 2")))
+        );
+    }
+
+    /// Assert that `--check` is recognized alongside `--dry-run` and refused without it
+    #[test]
+    fn parse_args_check() {
+        let expected = ChildArgs {
+            dry_run: true,
+            check: true,
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--dry-run", "--check", "make"), Action::Sandbox(expected));
+        assert_eq!(
+            test_args!("--check", "make"),
+            Action::BadUsage("--check can only be used with --dry-run")
+        );
+    }
+
+    /// Assert that `--shell` is recognized and leaves the command to be resolved untouched
+    #[test]
+    fn parse_args_shell() {
+        let expected = ChildArgs {
+            shell: true,
+            child_argv: vec!["cargo".into(), "build".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--shell", "cargo", "build"), Action::Sandbox(expected));
+    }
+
+    /// Assert that the flags choose the log threshold and the environment only fills in for them
+    #[test]
+    fn child_args_log_level() {
+        let level = |args: ChildArgs, env: Option<log::Level>| args.log_level(env);
+        assert_eq!(level(ChildArgs::default(), None), log::Level::Warn);
+        assert_eq!(level(ChildArgs::default(), Some(log::Level::Info)), log::Level::Info);
+        let quiet = ChildArgs { quiet: true, ..ChildArgs::default() };
+        assert_eq!(level(quiet, Some(log::Level::Trace)), log::Level::Error);
+        let debug = ChildArgs { debug: true, ..ChildArgs::default() };
+        assert_eq!(level(debug, Some(log::Level::Error)), log::Level::Debug);
+        let trace = ChildArgs { debug: true, trace: true, ..ChildArgs::default() };
+        assert_eq!(level(trace, None), log::Level::Trace);
+    }
+
+    /// Assert that `--quiet` is recognized and that combining it with `--debug` is refused
+    #[test]
+    fn parse_args_quiet() {
+        let expected = ChildArgs {
+            quiet: true,
+            child_argv: vec!["make".into(), "-q".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--quiet", "make", "-q"), Action::Sandbox(expected));
+        let expected = ChildArgs {
+            quiet: true,
+            dry_run: true,
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("-q", "--dry-run", "make"), Action::Sandbox(expected));
+
+        let contradiction =
+            Action::BadUsage("--debug (or --trace) and --quiet contradict each other");
+        assert_eq!(test_args!("--quiet", "--debug", "make"), contradiction);
+        assert_eq!(test_args!("-d", "-q", "make"), contradiction);
+
+        // Not recognized after the command
+        assert_eq!(test_args!("make", "--quiet"), make_expected!(false, "make", "--quiet"));
+    }
+
+    /// Assert that `--profile` consumes the following argument as the profile name
+    #[test]
+    fn parse_args_profile_override() {
+        let expected = ChildArgs {
+            overrides: Overrides { profile: Some("make".into()), ..Overrides::default() },
+            child_argv: vec!["cargo".into(), "build".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--profile", "make", "cargo", "build"), Action::Sandbox(expected));
+
+        // The name is taken verbatim, even if it looks like a flag, and combines with others
+        let expected = ChildArgs {
+            debug: true,
+            overrides: Overrides { profile: Some("--debug".into()), ..Overrides::default() },
+            child_argv: vec!["cargo".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--profile", "--debug", "-d", "cargo"), Action::Sandbox(expected));
+
+        // A missing name or command results in help output
+        assert_eq!(test_args!("--profile"), Action::Exit);
+        assert_eq!(test_args!("--profile", "make"), Action::Exit);
+
+        // Not recognized after the command
+        assert_eq!(
+            test_args!("cargo", "--profile", "make"),
+            make_expected!(false, "cargo", "--profile", "make")
+        );
+    }
+
+    /// Assert that `--debug` and `--dry-run` combine in either order without implying each other
+    #[test]
+    fn parse_args_debug_dry_run() {
+        let expected = ChildArgs {
+            debug: true,
+            dry_run: true,
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--debug", "--dry-run", "make"), Action::Sandbox(expected));
+        let expected = ChildArgs {
+            debug: true,
+            dry_run: true,
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(test_args!("--dry-run", "-d", "make"), Action::Sandbox(expected));
+
+        let expected =
+            ChildArgs { dry_run: true, child_argv: vec!["make".into()], ..ChildArgs::default() };
+        assert_eq!(test_args!("--dry-run", "make"), Action::Sandbox(expected));
+    }
+
+    /// Assert that `--root` consumes the following argument as the root directory
+    #[test]
+    fn parse_args_root_override() {
+        let expected = ChildArgs {
+            dry_run: true,
+            overrides: Overrides { root: Some("/src/project".into()), ..Overrides::default() },
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(
+            test_args!("--root", "/src/project", "--dry-run", "make"),
+            Action::Sandbox(expected)
+        );
+
+        // A missing directory or command results in help output
+        assert_eq!(test_args!("--root"), Action::Exit);
+        assert_eq!(test_args!("--root", "/src/project"), Action::Exit);
+
+        // Not recognized after the command
+        assert_eq!(
+            test_args!("make", "--root", "/src/project"),
+            make_expected!(false, "make", "--root", "/src/project")
+        );
+    }
+
+    /// Assert that `--stdin`, `--stdout`, and `--stderr` each consume the following argument,
+    /// with `--stdin null` meaning no input at all
+    #[test]
+    fn parse_args_redirects() {
+        let expected = |redirects: Redirects| {
+            Action::Sandbox(ChildArgs {
+                redirects,
+                child_argv: vec!["make".into()],
+                ..ChildArgs::default()
+            })
+        };
+        assert_eq!(
+            test_args!("--stdin", "null", "--stdout", "out.log", "make"),
+            expected(Redirects {
+                stdin: Some(Input::Null),
+                stdout: Some("out.log".into()),
+                ..Redirects::default()
+            })
+        );
+        assert_eq!(
+            test_args!("--stdin", "./null", "--stderr", "/tmp/err.log", "make"),
+            expected(Redirects {
+                stdin: Some(Input::File("./null".into())),
+                stderr: Some("/tmp/err.log".into()),
+                ..Redirects::default()
+            })
+        );
+
+        // A missing path results in help output, and they aren't recognized after the command
+        assert_eq!(test_args!("--stdout"), Action::Exit);
+        assert_eq!(
+            test_args!("make", "--stdin", "null"),
+            make_expected!(false, "make", "--stdin", "null")
+        );
+    }
+
+    /// Assert that `--env` may be repeated, splits only on the first `=`, and refuses names which
+    /// aren't valid environment variable names
+    #[test]
+    fn parse_args_env() {
+        let expected = ChildArgs {
+            env: [("CFLAGS", "-O2 -DX=1"), ("RUST_LOG", "debug"), ("EMPTY", "")]
+                .into_iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+            child_argv: vec!["make".into()],
+            ..ChildArgs::default()
+        };
+        assert_eq!(
+            test_args!(
+                "--env",
+                "RUST_LOG=info",
+                "--env",
+                "CFLAGS=-O2 -DX=1",
+                "--env",
+                "EMPTY=",
+                "--env",
+                "RUST_LOG=debug",
+                "make"
+            ),
+            Action::Sandbox(expected)
+        );
+
+        for bad in ["RUST_LOG", "=x", "1X=y", "MY-VAR=z", "NODO_ACTIVE=0"] {
+            assert!(matches!(test_args!("--env", bad, "make"), Action::BadUsage(_)), "{bad:?}");
+        }
+        assert_eq!(test_args!("--env"), Action::Exit);
+        assert_eq!(
+            test_args!("make", "--env", "X=1"),
+            make_expected!(false, "make", "--env", "X=1")
+        );
+    }
+
+    /// Assert that `--explain` and `--explain --json` wrap the child arguments as expected
+    #[test]
+    fn parse_args_explain() {
+        let expected =
+            ChildArgs { child_argv: vec!["cargo".into(), "b".into()], ..ChildArgs::default() };
+        assert_eq!(
+            test_args!("--explain", "cargo", "b"),
+            Action::Explain(explain::Format::Human, expected)
+        );
+        let expected =
+            ChildArgs { child_argv: vec!["cargo".into(), "b".into()], ..ChildArgs::default() };
+        assert_eq!(
+            test_args!("--explain", "--json", "cargo", "b"),
+            Action::Explain(explain::Format::Json, expected)
+        );
+        let expected =
+            ChildArgs { child_argv: vec!["cargo".into(), "b".into()], ..ChildArgs::default() };
+        assert_eq!(
+            test_args!("--explain", "--firejail-syntax", "cargo", "b"),
+            Action::Explain(explain::Format::FirejailSyntax, expected)
+        );
+        // ...but only after `--explain`
+        assert_eq!(
+            test_args!("--firejail-syntax", "cargo"),
+            make_expected!(false, "--firejail-syntax", "cargo")
+        );
+
+        // `--` may follow `--explain [--json]` to escape commands named like flags
+        let expected = ChildArgs { child_argv: vec!["--json".into()], ..ChildArgs::default() };
+        assert_eq!(
+            test_args!("--explain", "--", "--json"),
+            Action::Explain(explain::Format::Human, expected)
+        );
+
+        // ...and a missing command still results in help output
+        assert_eq!(test_args!("--explain"), Action::Exit);
+        assert_eq!(test_args!("--explain", "--json", "--"), Action::Exit);
     }
 
     /// Assert that [`parse_args`] will react to flags if and only if they're the first argument
@@ -209,6 +1227,10 @@ mod test {
         assert_eq!(test_args!("foo", "--version"), make_expected!(false, "foo", "--version"));
         assert_eq!(test_args!("foo", "--conf-path"), make_expected!(false, "foo", "--conf-path"));
         assert_eq!(test_args!("foo", "--write-conf"), make_expected!(false, "foo", "--write-conf"));
+        assert_eq!(
+            test_args!("foo", "--dump-config"),
+            make_expected!(false, "foo", "--dump-config")
+        );
 
         // Special flags apply in argv[1] regardless of what follows
         assert_eq!(test_args!("-h", "foo"), Action::Exit);
@@ -223,12 +1245,12 @@ mod test {
         assert_eq!(test_args!("--version", "foo"), Action::Exit);
         assert_eq!(test_args!("--version", "--bar"), Action::Exit);
         assert_eq!(test_args!("--version", "--write-conf"), Action::Exit);
-        assert_eq!(test_args!("--conf-path", "foo"), Action::PathToConf);
-        assert_eq!(test_args!("--conf-path", "--bar"), Action::PathToConf);
-        assert_eq!(test_args!("--conf-path", "--help"), Action::PathToConf);
-        assert_eq!(test_args!("--write-conf", "foo"), Action::WriteConf);
-        assert_eq!(test_args!("--write-conf", "--bar"), Action::WriteConf);
-        assert_eq!(test_args!("--write-conf", "--help"), Action::WriteConf);
+        assert_eq!(test_args!("--conf-path", "foo"), Action::PathToConf { canonical: false });
+        assert_eq!(test_args!("--conf-path", "--bar"), Action::PathToConf { canonical: false });
+        assert_eq!(test_args!("--conf-path", "--help"), Action::PathToConf { canonical: false });
+        assert_eq!(test_args!("--write-conf", "foo"), Action::WriteConf { merge: false });
+        assert_eq!(test_args!("--write-conf", "--bar"), Action::WriteConf { merge: false });
+        assert_eq!(test_args!("--write-conf", "--help"), Action::WriteConf { merge: false });
     }
 
     /// Assert that `--` in the first position allows commands named after flags
@@ -242,6 +1264,26 @@ mod test {
         assert_eq!(test_args!("--", "--write-conf"), make_expected!(false, "--write-conf"));
     }
 
+    /// Assert that every entry in `RESERVED_FLAGS` really is intercepted as the first argument
+    #[test]
+    fn reserved_flags_are_intercepted() {
+        for &flag in RESERVED_FLAGS {
+            let mut argv: Vec<OsString> = ["nodo", flag, "json", "cargo"].map(Into::into).into();
+            if take_error_format(&mut argv).is_ok_and(|x| x == ErrorFormat::Json)
+                || take_config(&mut argv).is_ok_and(|x| x != config::Source::Default)
+            {
+                continue;
+            }
+            let intercepted = match parse_args_with_env(argv.into_iter(), None) {
+                Action::Sandbox(parsed) | Action::Explain(_, parsed) => {
+                    parsed.child_argv[0] != flag
+                },
+                _ => true,
+            };
+            assert!(intercepted, "{flag}");
+        }
+    }
+
     /// Assert that `--` in the first position has no effect on the parsed output when unnecessary
     #[test]
     fn doubledash_is_invisible_in_parsed_output() {
@@ -1,19 +1,515 @@
 //! Configuration file schema and supplementary validation routines
 
+use std::borrow::Cow;
 use std::collections::BTreeMap; // Used to ensure deterministic key ordering in Debug output
-use std::env;
-use std::path::PathBuf;
+use std::error::Error;
+use std::ffi::OsStr;
+use std::fmt::{self, Write};
+use std::io::Read;
+use std::net::IpAddr;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+use std::{env, fs, io};
 
-use serde_derive::Deserialize;
+use serde::de::{self, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde_derive::{Deserialize, Serialize};
 use toml_edit::de::from_str as toml_from_str;
 
-use crate::types::{caps, CommandName, FileName, SubcommandName};
+use crate::types::{
+    caps, Capability, CommandName, FileName, FirejailProfile, Hostname, InterfaceName, RootMarker,
+    SubcommandName, Timeout, Umask,
+};
 
 /// The contents of the default configuration file that is used if nothing else is found
-///
-/// **TODO:** Actually implement support for loading a non-default config file
 pub const DEFAULT_CONFIG: &str = include_str!("defaults.toml");
 
+/// A distribution-provided default configuration to use in place of [`DEFAULT_CONFIG`] when it
+/// exists, as set by building with the `NODO_SYSTEM_DEFAULTS` environment variable (eg.
+/// `/usr/share/nodo/defaults.toml`)
+pub const SYSTEM_DEFAULTS_PATH: Option<&str> = option_env!("NODO_SYSTEM_DEFAULTS");
+
+/// Load the configuration from `path`, falling back to the file at [`SYSTEM_DEFAULTS_PATH`] and
+/// then [`DEFAULT_CONFIG`] if there is no file there (or no path could be determined at all)
+///
+/// Any other failure to read a file is an error. Silently falling back to the defaults when the
+/// user's file exists but is unreadable would leave them with a sandbox they didn't ask for.
+///
+/// # Errors
+///
+/// Fails if a file exists but can't be read, can't be parsed, or (with `strict`) contains
+/// unrecognized keys.
+pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+    load_with_system_defaults(path, SYSTEM_DEFAULTS_PATH.map(Path::new))
+}
+
+/// The text of a configuration file and where it came from (or `None` for [`DEFAULT_CONFIG`])
+pub type Text<P> = (Cow<'static, str>, Option<P>);
+
+/// Implementation of [`load`] with the system defaults path passed in so it can be tested
+/// without rebuilding
+fn load_with_system_defaults(
+    path: Option<&Path>,
+    system_defaults: Option<&Path>,
+) -> Result<Config, Box<dyn Error>> {
+    let (source, origin) = read_with_system_defaults(path, system_defaults)?;
+    check_env_sha256(&source)?;
+    parse_from(&source, origin)
+}
+
+/// Read the text [`load_with_system_defaults`] parses
+fn read_with_system_defaults<'a>(
+    path: Option<&'a Path>,
+    system_defaults: Option<&'a Path>,
+) -> Result<Text<&'a Path>, Box<dyn Error>> {
+    for candidate in [path, system_defaults].into_iter().flatten() {
+        match fs::read_to_string(candidate) {
+            Ok(text) => return Ok((Cow::Owned(text), Some(candidate))),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+            Err(err) => return Err(format!("Could not read {}: {err}", candidate.display()).into()),
+        }
+    }
+    Ok((Cow::Borrowed(DEFAULT_CONFIG), None))
+}
+
+/// Where to read the configuration from, as chosen with `--config`
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub enum Source {
+    /// The file at [`find_path`], or the defaults [`load`] falls back to if there is none
+    #[default]
+    Default,
+    /// A specific file, which must exist
+    File(PathBuf),
+    /// Standard input (`--config -`), which leaves no file for the sandbox to blacklist
+    ///
+    /// It's read to the end, so the sandboxed command will see an empty standard input.
+    Stdin,
+}
+
+/// How standard input is named in errors when the configuration is read from it
+const STDIN_ORIGIN: &str = "(standard input)";
+
+impl Source {
+    /// The path of the file the configuration is read from, if there is one
+    ///
+    /// For [`Source::Default`], this is [`find_path`] even if nothing exists there yet.
+    #[must_use]
+    pub fn path(&self) -> Option<PathBuf> {
+        match *self {
+            Self::Default => find_path().ok(),
+            Self::File(ref path) => Some(path.clone()),
+            Self::Stdin => None,
+        }
+    }
+
+    /// Load the configuration, as [`load`] does for [`Source::Default`]
+    ///
+    /// # Errors
+    ///
+    /// As for [`load`], plus an explicitly chosen file or standard input being unreadable.
+    pub fn load(&self) -> Result<Config, Box<dyn Error>> {
+        let (source, origin) = self.read()?;
+        parse_from(&source, origin.as_deref())
+    }
+
+    /// Read the configuration's text without parsing it, for use with [`parse_from`]
+    ///
+    /// # Errors
+    ///
+    /// As for [`Source::load`], minus the parsing, plus the text not matching [`SHA256_ENV_VAR`]
+    /// if it's set.
+    pub fn read(&self) -> Result<Text<PathBuf>, Box<dyn Error>> {
+        let text = match *self {
+            Self::Default => {
+                let path = find_path().ok();
+                let system_defaults = SYSTEM_DEFAULTS_PATH.map(Path::new);
+                let (source, origin) = read_with_system_defaults(path.as_deref(), system_defaults)?;
+                (source, origin.map(Path::to_owned))
+            },
+            Self::File(ref path) => {
+                let source = fs::read_to_string(path)
+                    .map_err(|err| format!("Could not read {}: {err}", path.display()))?;
+                (Cow::Owned(source), Some(path.clone()))
+            },
+            Self::Stdin => {
+                let mut source = String::new();
+                io::stdin().read_to_string(&mut source)?;
+                (Cow::Owned(source), Some(PathBuf::from(STDIN_ORIGIN)))
+            },
+        };
+        check_env_sha256(&text.0)?;
+        Ok(text)
+    }
+}
+
+/// Parse the contents of a configuration file, honouring its `strict` setting
+///
+/// # Errors
+///
+/// Fails on TOML syntax errors, values of the wrong type or failing their newtype's validation,
+/// and (with `strict`) unrecognized keys.
+pub fn parse(source: &str) -> Result<Config, Box<dyn Error>> {
+    parse_from(source, None)
+}
+
+/// [`parse`], but naming `origin` (or the built-in defaults if `None`) in any syntax or type
+/// error
+///
+/// # Errors
+///
+/// As for [`parse`].
+pub fn parse_from(source: &str, origin: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+    let config: Config =
+        toml_from_str(source).map_err(|err| describe_parse_error(&err, source, origin))?;
+    if config.strict {
+        let unknown = find_unknown_keys(source)?;
+        if !unknown.is_empty() {
+            return Err(format!(
+                "Unrecognized keys in configuration file (set `strict = false` to ignore): {}",
+                unknown.join(", ")
+            )
+            .into());
+        }
+    }
+    Ok(config)
+}
+
+/// The environment variable which, if set, holds the SHA-256 digest (as 64 hexadecimal digits)
+/// the configuration's text must have for `nodo` to agree to run
+///
+/// This is for detecting tampering with a file provisioned by configuration management. It's
+/// kept out of the file itself, since anything able to rewrite the file could rewrite a digest
+/// stored alongside it. The value is what `sha256sum nodo.toml` prints.
+pub const SHA256_ENV_VAR: &str = "NODO_CONFIG_SHA256";
+
+/// Verify the configuration's text against [`SHA256_ENV_VAR`], if it's set
+fn check_env_sha256(source: &str) -> Result<(), String> {
+    match env::var_os(SHA256_ENV_VAR) {
+        Some(expected) => check_sha256(source, &expected.to_string_lossy()),
+        None => Ok(()),
+    }
+}
+
+/// Verify that `source` hashes to `expected`
+fn check_sha256(source: &str, expected: &str) -> Result<(), String> {
+    if expected.len() != 64 || !expected.bytes().all(|x| x.is_ascii_hexdigit()) {
+        return Err(format!("{SHA256_ENV_VAR} must be 64 hexadecimal digits, not {expected:?}"));
+    }
+    let actual = crate::sha256::hex_digest(source.as_bytes());
+    if actual.eq_ignore_ascii_case(expected) {
+        Ok(())
+    } else {
+        Err(format!(
+            "Configuration does not match {SHA256_ENV_VAR} (expected {expected}, found \
+             {actual}). Refusing to run with a configuration which may have been tampered with."
+        ))
+    }
+}
+
+/// Render a TOML syntax or type error in the style of `rustc` diagnostics, with the file name,
+/// line, column, a caret under the offending span, and a hint where there's a common cause
+fn describe_parse_error(err: &toml_edit::de::Error, source: &str, origin: Option<&Path>) -> String {
+    let origin =
+        origin.map_or(Cow::Borrowed("(built-in default configuration)"), |x| x.to_string_lossy());
+    let message = err.message().trim_end();
+    // `fmt::Write` for `String` is infallible, so the results are safe to ignore
+    let mut out = format!("Could not parse configuration file: {message}\n");
+
+    if let Some(span) = err.span() {
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |x| x + 1);
+        let line_end = source[start..].find('\n').map_or(source.len(), |x| start + x);
+        let line = &source[line_start..line_end];
+        let line_no = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let width = source[start..span.end.clamp(start, line_end)].chars().count().max(1);
+        let gutter = " ".repeat(line_no.to_string().len());
+
+        let _ = writeln!(out, "{gutter}--> {origin}:{line_no}:{column}");
+        let _ = writeln!(out, "{gutter} |\n{line_no} | {line}");
+        let _ = writeln!(out, "{gutter} | {}{}", " ".repeat(column - 1), "^".repeat(width));
+    } else {
+        let _ = writeln!(out, " --> {origin}");
+    }
+
+    if message.starts_with("invalid type") {
+        out.push_str(
+            "hint: strings must be \"quoted\", switches like `allow_network` take a bare `true` \
+             or `false`, and lists are written as [\"a\", \"b\"]",
+        );
+    } else if message.starts_with("unknown variant") {
+        out.push_str(
+            "hint: switches like `allow_network` take `true`, `false`, or one of the names above",
+        );
+    }
+    out.trim_end().to_owned()
+}
+
+/// Write [`DEFAULT_CONFIG`] to `path` or, if `merge` is set and a file already exists there, add
+/// any new top-level default keys to it using [`merge_defaults`]
+///
+/// An existing file is never replaced wholesale, and the result must load successfully before
+/// anything is written.
+///
+/// # Errors
+///
+/// Fails if a file exists and `merge` isn't set, if the file can't be read or written, or if the
+/// result doesn't parse and [`Config::validate`].
+pub fn write_default(path: &Path, merge: bool) -> Result<(), Box<dyn Error>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(existing) if merge => merge_defaults(&existing)?,
+        Ok(_) => {
+            return Err(format!(
+                "{} already exists. Use --write-conf --merge to add new default keys to it.",
+                path.display()
+            )
+            .into())
+        },
+        Err(err) if err.kind() == io::ErrorKind::NotFound => DEFAULT_CONFIG.to_owned(),
+        Err(err) => return Err(err.into()),
+    };
+    parse(&contents)?.validate().map_err(|problems| problems.join("\n"))?;
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Add any top-level keys present in [`DEFAULT_CONFIG`] but missing from `existing`, preserving
+/// the comments and formatting of `existing`
+///
+/// Existing values are never overwritten. Profiles are deliberately left alone, since a key
+/// missing from a profile means its safe default and copying the default profile's value (eg.
+/// `allow_network_subcommands`) in would quietly loosen the sandbox. For the same reason,
+/// profiles the user removed aren't resurrected.
+///
+/// # Errors
+///
+/// Fails if either `existing` or [`DEFAULT_CONFIG`] isn't valid TOML.
+pub fn merge_defaults(existing: &str) -> Result<String, toml_edit::TomlError> {
+    let defaults: toml_edit::DocumentMut = DEFAULT_CONFIG.parse()?;
+    let mut document: toml_edit::DocumentMut = existing.parse()?;
+    for (key, item) in defaults.iter() {
+        if key == "profile" || document.contains_key(key) {
+            continue;
+        }
+        if let Some((key, _)) = defaults.get_key_value(key) {
+            document.insert_formatted(key, item.clone());
+        }
+    }
+    Ok(document.to_string())
+}
+
+/// The most `--dns` flags Firejail will accept
+const MAX_DNS_SERVERS: usize = 3;
+
+/// Return `true` if `name` matches `pattern`, where `*` matches any run of characters
+fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut pieces = pattern.split('*');
+    // `split` always yields at least one piece
+    let first = pieces.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let mut pieces: Vec<&str> = pieces.collect();
+    let Some(last) = pieces.pop() else {
+        // No `*` at all, so it's an exact match or nothing
+        return rest.is_empty();
+    };
+    for piece in pieces {
+        match rest.find(piece) {
+            Some(index) => rest = &rest[index + piece.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Return `true` if `name` is a valid POSIX environment variable name
+/// (`[A-Za-z_][A-Za-z0-9_]*`)
+pub(crate) fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// The Firejail options which must not appear in `firejail_base_flags` or a profile's
+/// `base_flags_override` because they would undermine the flags `nodo` generates, each with what
+/// it would do, as worded in the resulting validation error
+///
+/// **This is the one place the denylist is kept.** An entry matches the option with or without
+/// a `=` value, except that `--net=none` is allowed since it can only take access away.
+const DANGEROUS_FLAGS: &[(&str, &str)] = &[
+    ("--allow-debuggers", "allow ptrace, which seccomp would otherwise block"),
+    ("--caps.keep", "keep capabilities (use keep_caps instead)"),
+    ("--ignore", "make Firejail ignore directives, such as seccomp or caps"),
+    ("--interface", "grant network access (use allow_network instead)"),
+    ("--net", "grant network access (use allow_network instead)"),
+    ("--netns", "grant network access (use allow_network instead)"),
+    ("--noblacklist", "un-blacklist paths, such as the configuration file"),
+    ("--noprofile", "drop the profile's 'firejail_profile'"),
+    ("--protocol", "override the socket protocols set by 'allow_abstract_sockets'"),
+    ("--read-write", "make read-only paths, such as 'system_readonly', writable"),
+    ("--whitelist", "re-expose blacklisted paths, such as the configuration file"),
+];
+
+/// Return what `flag` would undermine if it's one of the [`DANGEROUS_FLAGS`]
+fn dangerous_flag(flag: &str) -> Option<&'static str> {
+    let name = flag.split_once('=').map_or(flag, |(name, _)| name);
+    if flag == "--net=none" {
+        return None;
+    }
+    DANGEROUS_FLAGS.iter().find(|&&(x, _)| x == name).map(|&(_, effect)| effect)
+}
+
+/// The keys recognized at the top level of the configuration file
+///
+/// **Keep this in sync with the fields of [`Config`].** Serde's `deny_unknown_fields` can't be
+/// toggled at runtime, so `strict` relies on this list to catch typos.
+const CONFIG_KEYS: &[&str] = &[
+    "allow_nesting",
+    "allowed_commands",
+    "backend_path",
+    "backend_verbose",
+    "blacklist_mode",
+    "fail_closed",
+    "firejail_base_flags",
+    "forbidden_roots",
+    "kill_grace_seconds",
+    "match_by_binary",
+    "max_args",
+    "pre_launch",
+    "profile",
+    "root_blacklist",
+    "strict",
+    "strip_suffixes",
+    "system_readonly",
+];
+
+/// The keys recognized in a `[profile.<command>]` table
+///
+/// **Keep this in sync with the fields of [`CommandProfile`]** for the same reason as
+/// [`CONFIG_KEYS`]. (`command` is only valid in the `[[profile]]` form, but isn't worth
+/// special-casing.)
+const PROFILE_KEYS: &[&str] = &[
+    "allow_abstract_sockets",
+    "allow_network",
+    "allow_network_subcommands",
+    "base_flags_override",
+    "blacklist",
+    "command",
+    "command_matches",
+    "command_path_prefix",
+    "deny_subcommands",
+    "dns",
+    "env_set",
+    "firejail_profile",
+    "hostname",
+    "keep_caps",
+    "map_uid",
+    "network_interface",
+    "no_root_base",
+    "no_root_subcommands",
+    "pre_exec",
+    "private_bin",
+    "project_access",
+    "projectless_subcommands",
+    "root_blacklist",
+    "root_find_outermost",
+    "root_marked_by",
+    "root_marked_by_all",
+    "subcommand_aliases",
+    "success_exit_codes",
+    "timeout",
+    "umask",
+    "unknown_subcommand",
+    "whitelist",
+    "writable_subdirs",
+];
+
+/// Return the name and table of each profile in the `profile` item, in either the
+/// `[profile.<command>]` or `[[profile]]` form (where they're named by index)
+fn profile_tables(item: &toml_edit::Item) -> Vec<(String, &dyn toml_edit::TableLike)> {
+    if let Some(array) = item.as_array_of_tables() {
+        array.iter().enumerate().map(|(idx, x)| (idx.to_string(), x as _)).collect()
+    } else if let Some(table) = item.as_table_like() {
+        table
+            .iter()
+            .filter_map(|(command, x)| Some((command.to_owned(), x.as_table_like()?)))
+            .collect()
+    } else {
+        Vec::new()
+    }
+}
+
+/// Return the dotted paths of any keys in `source` which Serde would silently ignore
+fn find_unknown_keys(source: &str) -> Result<Vec<String>, toml_edit::TomlError> {
+    let document: toml_edit::DocumentMut = source.parse()?;
+    let mut unknown = Vec::new();
+    for (key, item) in document.iter() {
+        if !CONFIG_KEYS.contains(&key) {
+            unknown.push(key.to_owned());
+        } else if key == "profile" {
+            for (command, profile) in profile_tables(item) {
+                unknown.extend(
+                    profile
+                        .iter()
+                        .filter(|&(field, _)| !PROFILE_KEYS.contains(&field))
+                        .map(|(field, _)| format!("profile.{command}.{field}")),
+                );
+            }
+        }
+    }
+    Ok(unknown)
+}
+
+/// Return a warning for each profile in `source` which grants network access (through
+/// `allow_network` or `allow_network_subcommands`) without a comment explaining why
+///
+/// A comment counts if it's on the line(s) directly above the key or at the end of its line
+/// (or, for `allow_network_subcommands`, anywhere inside the array). This is purely advisory,
+/// to nudge authors into documenting the holes they punch in the sandbox.
+///
+/// # Errors
+///
+/// Fails if `source` isn't valid TOML.
+pub fn lint(source: &str) -> Result<Vec<String>, toml_edit::TomlError> {
+    /// Return `true` if `decor` (which can only hold whitespace and comments) holds a comment
+    fn has_comment(decor: Option<&toml_edit::RawString>) -> bool {
+        decor.and_then(toml_edit::RawString::as_str).is_some_and(|x| x.contains('#'))
+    }
+
+    let document: toml_edit::DocumentMut = source.parse()?;
+    let mut warnings = Vec::new();
+    let Some(profiles) = document.get("profile") else { return Ok(warnings) };
+    for (command, profile) in profile_tables(profiles) {
+        for field in ["allow_network", "allow_network_subcommands"] {
+            let Some((key, toml_edit::Item::Value(value))) = profile.get_key_value(field) else {
+                continue;
+            };
+            let grants = match *value {
+                toml_edit::Value::Boolean(ref x) => *x.value(),
+                toml_edit::Value::String(ref x) => x.value() == "all_networks",
+                toml_edit::Value::Array(ref x) => !x.is_empty(),
+                _ => false,
+            };
+            let documented = has_comment(key.leaf_decor().prefix())
+                || has_comment(value.decor().suffix())
+                || value.as_array().is_some_and(|array| {
+                    has_comment(Some(array.trailing()))
+                        || array.iter().any(|x| {
+                            has_comment(x.decor().prefix()) || has_comment(x.decor().suffix())
+                        })
+                });
+            if grants && !documented {
+                warnings.push(format!(
+                    "[profile.{command}] '{field}' grants network access without a comment \
+                     explaining why"
+                ));
+            }
+        }
+    }
+    Ok(warnings)
+}
+
 /// Determine the path to load the configuration from or write it to
 ///
 /// This implements the lookup for user-specific configuration files as defined by the
@@ -24,24 +520,11 @@ pub const DEFAULT_CONFIG: &str = include_str!("defaults.toml");
 /// on a sandboxing configuration file introduces a significant amount of complication
 /// for feeling confident in the design's safety for benefits not yet demonstrated to be
 /// worthwhile.
-pub fn find_path() -> Option<PathBuf> {
-    let config_file_name = format!("{}.toml", env!("CARGO_PKG_NAME"));
-
-    // First, check if $XDG_CONFIG_HOME contains a compliant path that meets our needs.
-    //
-    // That is, it must be non-empty, containing an absolute path to a directory which exists.
-    // We're relying on `PathBuf::is_absolute()` to reject empty strings.
-    if let Some(var_str) = env::var_os("XDG_CONFIG_HOME") {
-        let mut xdg_path = PathBuf::from(var_str);
-        if xdg_path.is_absolute() && xdg_path.is_dir() {
-            xdg_path.push(config_file_name);
-            return Some(xdg_path);
-        }
-    }
-
-    // Otherwise, fall back to $HOME/.config but double-check that it exists too
-    // (Better to error than to 'try to make it work' in a security tool)
-    //
+///
+/// # Errors
+///
+/// See [`PathError`].
+pub fn find_path() -> Result<PathBuf, PathError> {
     // `env::home_dir` is deprecated for having unexpected behaviour on Windows.
     // However, this is for Linux (it depends on cgroups via Firejail) and the algorithm listed
     // under "Unix" is perfectly acceptable.
@@ -58,20 +541,90 @@ pub fn find_path() -> Option<PathBuf> {
     // replacement (`env::var_os("HOME")`) would be a strict downgrade, given that the Rust 1.0
     // stability promise ensures `env::home_dir()` will stay around.
     #[allow(deprecated)]
-    if let Some(mut path) = env::home_dir() {
-        path.push(".config");
-        if path.is_absolute() && path.is_dir() {
-            path.push(config_file_name);
-            return Some(path);
+    let home = env::home_dir();
+    find_path_from(env::var_os("XDG_CONFIG_HOME").as_deref(), home.as_deref())
+}
+
+/// Implementation of [`find_path`] with the environment passed in so cases like an
+/// undeterminable home directory (which `env::home_dir` falls back to `getpwuid_r` for) can be
+/// tested in-process
+fn find_path_from(
+    xdg_config_home: Option<&OsStr>,
+    home: Option<&Path>,
+) -> Result<PathBuf, PathError> {
+    let config_file_name = format!("{}.toml", env!("CARGO_PKG_NAME"));
+
+    // First, check if $XDG_CONFIG_HOME contains a compliant path that meets our needs.
+    //
+    // That is, it must be non-empty, containing an absolute path to a directory which exists.
+    // We're relying on `PathBuf::is_absolute()` to reject empty strings.
+    if let Some(var_str) = xdg_config_home {
+        let mut xdg_path = PathBuf::from(var_str);
+        if xdg_path.is_absolute() && xdg_path.is_dir() {
+            xdg_path.push(config_file_name);
+            return Ok(xdg_path);
         }
     }
 
+    // Otherwise, fall back to $HOME/.config but double-check that it exists too
+    // (Better to error than to 'try to make it work' in a security tool)
+    let mut path = home.ok_or(PathError::NoHome)?.join(".config");
+    if path.is_absolute() && path.is_dir() {
+        path.push(config_file_name);
+        return Ok(path);
+    }
+
     // If we reach here, we couldn't find an acceptable path
-    None
+    Err(PathError::NoConfigDir)
+}
+
+/// Resolve every symlink in a configuration file path (as returned by [`find_path`]) for
+/// `--conf-path --canonical`
+///
+/// [`find_path`] deliberately returns the path as built from `$XDG_CONFIG_HOME` or `$HOME`, since
+/// that's what the user will recognize. This is for when they need to know which file that
+/// actually is. The file needn't exist yet, so if it doesn't, only its directory is resolved.
+///
+/// # Errors
+///
+/// Returns any error encountered while resolving the directory, or [`io::ErrorKind::InvalidInput`]
+/// if `path` has no file name.
+pub fn canonicalize_path(path: &Path) -> io::Result<PathBuf> {
+    if let Ok(resolved) = path.canonicalize() {
+        return Ok(resolved);
+    }
+    let name = path.file_name().ok_or(io::ErrorKind::InvalidInput)?;
+    let dir = path.parent().filter(|x| !x.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    Ok(dir.canonicalize()?.join(name))
+}
+
+/// The reasons [`find_path`] may fail to determine where the configuration file belongs
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PathError {
+    /// `$XDG_CONFIG_HOME` isn't usable and the home directory couldn't be determined at all
+    NoHome,
+    /// Neither `$XDG_CONFIG_HOME` nor `$HOME/.config` is an absolute path to a directory
+    NoConfigDir,
+}
+
+impl Error for PathError {}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NoHome => f.write_str(
+                "$XDG_CONFIG_HOME is not an absolute directory path and the home directory \
+                 could not be determined.",
+            ),
+            Self::NoConfigDir => f.write_str(
+                "Neither $XDG_CONFIG_HOME nor $HOME/.config are absolute directory paths.",
+            ),
+        }
+    }
 }
 
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
 /// The schema for a single command's sandboxing profile, with "single command" defined as the
 /// value of `argv[0]` as seen by the subprocess run inside the sandbox.
 ///
@@ -88,9 +641,116 @@ pub struct CommandProfile {
     ///
     /// **NOTE:** It is recommended to leave this set to `false` and selectively override it using
     /// `allow_network_subcommands` if the command has subcommands.
-    #[serde(default)]
+    ///
+    /// Like `root_find_outermost`, this may also be spelled out as the name of the variant (eg.
+    /// `"all_networks"` instead of `true`).
+    #[serde(default, serialize_with = "caps::Network::serialize_as_bool")]
     allow_network: caps::Network,
 
+    /// If `true`, keep the sandboxed program in the host's network namespace when it isn't
+    /// allowed network access, restricting it to Unix domain sockets (`--protocol=unix`) instead
+    /// of giving it a namespace of its own (`--net=none`).
+    ///
+    /// Abstract Unix domain sockets have no presence on the filesystem, so only a separate network
+    /// namespace stops the program from reaching ones (eg. X11 or D-Bus) listening outside the
+    /// sandbox. That's why this defaults to `false`.
+    ///
+    /// **NOTE:** This only matters when `--net=none` would otherwise be used. Whenever
+    /// `allow_network` (or `allow_network_subcommands`) grants unrestricted network access, the
+    /// program shares the host's network namespace and, with it, every abstract socket, while
+    /// `network_interface` always gives it a namespace of its own.
+    ///
+    /// Like `allow_network`, this may also be spelled out as the name of the variant (eg.
+    /// `"shared"` instead of `true`).
+    #[serde(default, serialize_with = "caps::AbstractSockets::serialize_as_bool")]
+    allow_abstract_sockets: caps::AbstractSockets,
+
+    /// If set, a list of flags to use for this command instead of the top-level
+    /// `firejail_base_flags`. (This replaces the list entirely rather than adding to it.)
+    ///
+    /// Like `firejail_base_flags`, it may not contain flags which grant network access, since
+    /// that's what `allow_network` and `allow_network_subcommands` are for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    base_flags_override: Option<Vec<String>>,
+
+    /// Patterns for other command names this profile should also apply to, where `*` matches
+    /// any run of characters (eg. `["cargo-*"]` to claim Cargo plugins like `cargo-nextest`)
+    ///
+    /// These are only a fallback. A profile whose name exactly matches the command always wins,
+    /// and a command matched by patterns from more than one profile is refused as ambiguous
+    /// rather than having one picked for it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    command_matches: Vec<String>,
+
+    /// If set, the profile only applies when `argv[0]`, looked up via `$PATH`, is inside this
+    /// directory (eg. `"/usr/bin"`), so a `make` profile meant for the distro's `make` isn't
+    /// applied to a `./make` script in the project.
+    ///
+    /// This is a lexical comparison. A symlink inside the directory counts as inside it, but a
+    /// path containing `..` never does.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    command_path_prefix: Option<PathBuf>,
+
+    /// If set, a Firejail `.profile` (eg. one of the curated ones Firejail ships) to apply via
+    /// `--profile=` as the base which everything else `nodo` generates is layered on top of.
+    ///
+    /// This may be a bare name, which Firejail looks up in its usual profile directories, or an
+    /// absolute path. It is passed before `firejail_base_flags` and the flags derived from this
+    /// profile, so those add to (and, where Firejail allows, override) what it sets.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    firejail_profile: Option<FirejailProfile>,
+
+    /// If set, the host name the command should see (via Firejail's `--hostname=`), for builds
+    /// which embed it and need to be reproducible. It must be a valid RFC 1123 host name.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    hostname: Option<Hostname>,
+
+    /// Linux capabilities (eg. `["CAP_NET_BIND_SERVICE"]` for a development server on port 80) to
+    /// keep rather than dropping them all.
+    ///
+    /// **This widens the sandbox.** When it isn't empty, `--caps.drop=all` is removed from the
+    /// base flags and `--caps.keep=` with these is passed instead, which still drops every other
+    /// capability.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keep_caps: Vec<Capability>,
+
+    /// If not empty, the names of the only programs (eg. `["cargo", "rustc", "cc", "ld"]`) the
+    /// command may run, via Firejail's `--private-bin=`, which replaces the system's binary
+    /// directories with ones containing just these.
+    ///
+    /// **This can break builds** which shell out to tools you didn't anticipate (eg. `git`
+    /// or a linker), so the command itself and everything it runs must be listed. `sh` is also
+    /// needed if `umask` or `pre_exec` is set, and `unshare` if `map_uid` is, since they're
+    /// applied using those.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    private_bin: Vec<FileName>,
+
+    /// If set, a network interface to give the sandbox access to whenever it isn't allowed
+    /// unrestricted network communication (ie. Firejail's `--net=<interface>` instead of
+    /// `--net=none`).
+    ///
+    /// `"lo"` means loopback only, which is what `--net=none` already provides.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    network_interface: Option<InterfaceName>,
+
+    /// Whether the sandboxed command may modify the project root. (See [`ProjectAccess`].)
+    #[serde(default)]
+    project_access: ProjectAccess,
+
+    /// Names of subdirectories of the project root (eg. `target`) to make writable again when
+    /// `project_access` is `"read_only"`
+    ///
+    /// These are single names joined onto the root, not paths, so they can't reach outside it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    writable_subdirs: Vec<FileName>,
+
+    /// A list of up to three IP addresses of DNS servers to force the sandbox to use whenever it
+    /// has network access, so name lookups can't leak to the system resolver.
+    ///
+    /// It is an error to set this on a profile which can never have network access.
+    #[serde(default)]
+    dns: Vec<IpAddr>,
+
     /// A list of subcommands which should be allowed unrestricted network access.
     ///
     /// This is useful for commands which must query package repositories or fetch dependencies.
@@ -111,9 +771,46 @@ pub struct CommandProfile {
     #[serde(default)]
     projectless_subcommands: Vec<SubcommandName>,
 
+    /// A list of subcommands (eg. `cargo install`) which operate on the user's account as a whole
+    /// rather than on any project, and so should be sandboxed with `no_root_base` as the root
+    /// without searching for `root_marked_by` markers at all.
+    ///
+    /// Unlike `projectless_subcommands`, which confine the command to the directory it was run
+    /// in, this grants access to the whole base regardless of where it was run from. If the
+    /// working directory isn't inside the base, the command is run from the base instead.
+    #[serde(default)]
+    no_root_subcommands: Vec<SubcommandName>,
+
+    /// The absolute path to use as the sandbox root for `no_root_subcommands`, or the home
+    /// directory if unset. It must be an existing directory.
+    ///
+    /// Unlike a discovered root or `--root`, this isn't refused for being too broad, so leaving
+    /// it unset exposes **all of your home directory** to those subcommands, read-write. Narrowing
+    /// it (eg. to `~/.cargo`, written out in full) is strongly recommended.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    no_root_base: Option<PathBuf>,
+
+    /// What to do with a subcommand that isn't listed in `allow_network_subcommands`,
+    /// `deny_subcommands`, `projectless_subcommands`, `no_root_subcommands`, or as
+    /// a `subcommand_aliases` target. (See
+    /// [`UnknownSubcommand`].)
+    #[serde(default)]
+    unknown_subcommand: UnknownSubcommand,
+
     /// If any of the file/directory names in this list are present, choose the directory they
     /// appear in to be the root of the sandbox.
-    root_marked_by: Vec<FileName>,
+    ///
+    /// An entry may also be a table like `{ name = "pyproject.toml", contains = "[tool.poetry]" }`
+    /// to only count a file if it contains the given text. (Only the first megabyte is read.)
+    root_marked_by: Vec<RootMarker>,
+
+    /// A group of file/directory names (or tables, as for `root_marked_by`) which only mark the
+    /// root of the sandbox when they all appear in the same directory (eg. `["Cargo.toml",
+    /// ".git"]`), checked alongside `root_marked_by`.
+    ///
+    /// If this is set, `root_marked_by` may be left empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    root_marked_by_all: Vec<RootMarker>,
 
     /// If `false`, treat the nearest ancestor containing one of the `root_marked_by` files or
     /// directories as the sandbox root.
@@ -121,84 +818,1131 @@ pub struct CommandProfile {
     /// If `true`, walk all the way up to the filesystem root and then take the last match
     /// encountered to be the sandbox root. (This is useful for systems like Cargo Workspaces which
     /// appear as child projects within a parent project.)
-    #[serde(default)]
+    #[serde(default, serialize_with = "caps::ProjectRoot::serialize_as_bool")]
     root_find_outermost: caps::ProjectRoot,
 
     /// A list of subcommand names which should be treated as aliases for other subcommand names
     /// when looking up what sandboxing profile to apply.
+    ///
+    /// Several aliases may share a target, and a target may itself be an alias (eg. `b = "bld"`
+    /// and `bld = "build"`), in which case it's followed to the end of the chain.
     #[serde(default)]
     subcommand_aliases: BTreeMap<SubcommandName, SubcommandName>,
-}
 
-/// The schema for the configuration file as a whole
-#[derive(Debug, Deserialize)]
-pub struct Config {
-    /// A list of flags to pass to Firejail before the flags determined by the profile but after
-    /// the hard-coded flags generated to do things like blacklisting the sandboxing
-    /// configuration file.
+    /// If set, a wall-clock limit in `HH:MM:SS` form after which Firejail will kill the sandbox.
     ///
-    /// This field must be specified. If you *really* mean to specify a sandbox that's as full of
-    /// holes as Swiss cheese, explicitly use an empty list.
-    firejail_base_flags: Vec<String>,
+    /// This is useful for keeping a hung build from tying up a CI runner indefinitely.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timeout: Option<Timeout>,
 
-    /// A default list of root-relative paths to be denied access to.
+    /// If set, the umask (as 3 or 4 octal digits, eg. `"0077"`) to run the command with, so build
+    /// outputs don't end up readable by other users of a shared machine.
     ///
-    /// (The idea being to provide an analogue to `chattr +a foo.log` so `git diff` can be used to
-    /// reveal attempts by malware inside the sandbox to sneak malicious code into a commit.)
+    /// Firejail has no option for this, so the command is started via `/bin/sh -c 'umask ...'`
+    /// inside the sandbox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    umask: Option<Umask>,
+
+    /// If present, a command line (eg. `["rustc", "--version"]`) to run inside the sandbox,
+    /// before the real command, which must succeed for the real command to be run.
+    ///
+    /// Unlike the top-level `pre_launch`, this runs with exactly the command's restrictions, so
+    /// it's suited to checks like verifying a toolchain. It's run via `/bin/sh -c` inside the
+    /// sandbox and, if it fails, its exit status is used as the command's.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pre_exec: Option<Vec<String>>,
+
+    /// If set, the uid (from 1 to 65534) the command should see itself running as, for builds
+    /// which must not run as your own uid even inside the sandbox.
+    ///
+    /// Firejail has no option for this, so the command is started via
+    /// `unshare --user --map-user=<uid>` inside the sandbox. That needs util-linux 2.38 or newer
+    /// and unprivileged user namespaces enabled in the kernel (and not disabled for the sandbox
+    /// with Firejail's `--restrict-namespaces`). Only the mapping changes. Files the command
+    /// creates are still owned by you on the host.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    map_uid: Option<u32>,
+
+    /// A list of additional paths outside the project root to make accessible in the sandbox.
+    ///
+    /// A leading `~` and `$VAR` or `${VAR}` references are expanded when the sandbox is launched.
+    /// Referencing an undefined variable is an error and each path must be absolute after
+    /// expansion.
     #[serde(default)]
+    whitelist: Vec<String>,
+
+    /// A list of paths to deny the sandboxed command access to, expanded the same way as
+    /// `whitelist`.
+    ///
+    /// Since Firejail's behaviour is undefined when the two overlap, the sandbox won't be launched
+    /// if an expanded `whitelist` entry is the same as, inside, or an ancestor of an expanded
+    /// `blacklist` entry (or, with the `hide` `blacklist_mode`, a `root_blacklist` entry).
+    #[serde(default)]
+    blacklist: Vec<String>,
+
+    /// Environment variables to set for the sandboxed command (eg. `CARGO_HOME`), passed to
+    /// Firejail as `--env=KEY=VALUE`
+    ///
+    /// Keys must be valid POSIX environment variable names and values may not contain null bytes.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    env_set: BTreeMap<String, String>,
+
+    /// Nonzero exit codes which should be reported as success (eg. `[1]` for `grep`, where it
+    /// means "no match"), for wrapping tools in CI scripts that treat any nonzero status as
+    /// failure
+    ///
+    /// This only changes the exit status of `nodo` itself. The command still sees and reports
+    /// whatever it normally would.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    success_exit_codes: Vec<i32>,
+
+    /// Root-relative names to protect for this command in addition to the top-level
+    /// `root_blacklist` (eg. `.env` for `npm`), protected according to `blacklist_mode`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     root_blacklist: Vec<FileName>,
+}
 
-    /// A list of mappings from command names (`argv[0]`) to the sandboxing profiles to be applied
-    #[serde(rename = "profile")]
-    profiles: BTreeMap<CommandName, CommandProfile>,
+/// The [`CommandProfile`] field responsible for the network access granted to a command
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NetworkSource {
+    /// The profile-wide `allow_network` default
+    AllowNetwork,
+    /// An `allow_network_subcommands` entry overriding the default for the subcommand
+    AllowNetworkSubcommands,
 }
 
-impl Config {
-    /// Perform validation beyond what Serde is maintainably capable of
+impl CommandProfile {
+    /// Resolve `subcommand` through `subcommand_aliases` (transitively) to the name the other
+    /// fields refer to
+    #[must_use]
+    pub fn canonical_subcommand(&self, subcommand: SubcommandName) -> SubcommandName {
+        self.alias_chain(&subcommand).last().map_or(subcommand, |&x| x.clone())
+    }
+
+    /// Return the targets `subcommand` passes through on the way to its canonical name, ending
+    /// with that name (or empty if it isn't an alias)
     ///
-    /// (Implemented manually rather than accepting [validator](https://github.com/Keats/validator)
-    /// as another point of trust in a tool meant to enforce security.)
-    pub fn validate(&self) -> Result<(), &'static str> {
-        if self.profiles.is_empty() {
-            return Err("Configuration file must contain at least one profile");
-        }
-        for profile in self.profiles.values() {
-            if profile.root_marked_by.is_empty() {
-                return Err("'root_marked_by' must contain at least one file/folder name");
+    /// A cycle (which [`Self::problems`] reports) ends the chain when it would repeat a name.
+    fn alias_chain(&self, subcommand: &SubcommandName) -> Vec<&SubcommandName> {
+        let mut chain: Vec<&SubcommandName> = Vec::new();
+        let mut current = subcommand;
+        while let Some(target) = self.subcommand_aliases.get(current) {
+            if target == subcommand || chain.contains(&target) {
+                break;
             }
+            chain.push(target);
+            current = target;
         }
-        Ok(())
+        chain
     }
-}
 
-#[cfg(test)]
-mod test {
-    use super::*;
-    use std::convert::TryFrom;
+    /// Return every `subcommand_aliases` entry which resolves to `subcommand`, directly or
+    /// through other aliases
+    #[must_use]
+    pub fn aliases_of(&self, subcommand: &SubcommandName) -> Vec<&SubcommandName> {
+        self.subcommand_aliases
+            .keys()
+            .filter(|x| self.alias_chain(x).last() == Some(&subcommand))
+            .collect()
+    }
 
-    /// Assert that a failure to specify at least one profile or a failure to include
-    /// a `root_marked_by` field in the profile will be caught at TOML parsing time
-    /// and that `.validate()` will reject empty `Vec`s.
-    #[test]
-    fn profiles_required() {
-        toml_from_str::<Config>("").unwrap_err();
-        toml_from_str::<Config>("firejail_base_flags=[]\nprofile = {}")
-            .unwrap()
-            .validate()
-            .unwrap_err();
-        toml_from_str::<Config>("firejail_base_flags=[]\n[profile.make]").unwrap_err();
-        toml_from_str::<Config>("firejail_base_flags=[]\n[profile.make]\nroot_marked_by = []")
-            .unwrap()
-            .validate()
-            .unwrap_err();
-        toml_from_str::<Config>("firejail_base_flags=[]\n[profile.make]\nroot_marked_by = [\"\"]")
-            .unwrap_err();
-        toml_from_str::<Config>(
-            "firejail_base_flags=[]\n[profile.make]\nroot_marked_by=[\"Makefile\"]",
-        )
-        .unwrap()
-        .validate()
-        .unwrap();
+    /// Return `true` if `subcommand` must be run outside the sandbox
+    #[must_use]
+    pub fn is_denied(&self, subcommand: Option<&SubcommandName>) -> bool {
+        subcommand.is_some_and(|x| self.deny_subcommands.contains(x))
+    }
+
+    /// Return `true` if `subcommand` is mentioned by one of the profile's subcommand lists (after
+    /// alias resolution, so alias targets count too) and isn't subject to `unknown_subcommand`
+    ///
+    /// No subcommand at all (eg. `cargo --version`) counts as known.
+    #[must_use]
+    pub fn is_known_subcommand(&self, subcommand: Option<&SubcommandName>) -> bool {
+        subcommand.is_none_or(|x| {
+            self.allow_network_subcommands.contains(x)
+                || self.deny_subcommands.contains(x)
+                || self.projectless_subcommands.contains(x)
+                || self.no_root_subcommands.contains(x)
+                || self.subcommand_aliases.values().any(|target| target == x)
+        })
+    }
+
+    /// The subcommands which are granted network access regardless of `allow_network`
+    #[must_use]
+    pub fn allow_network_subcommands(&self) -> &[SubcommandName] {
+        &self.allow_network_subcommands
+    }
+
+    /// The subcommands which must be run outside the sandbox
+    #[must_use]
+    pub fn deny_subcommands(&self) -> &[SubcommandName] {
+        &self.deny_subcommands
+    }
+
+    /// The subcommands which use the current working directory as the sandbox root
+    #[must_use]
+    pub fn projectless_subcommands(&self) -> &[SubcommandName] {
+        &self.projectless_subcommands
+    }
+
+    /// The profile-wide network access, before `allow_network_subcommands` is applied
+    #[must_use]
+    pub fn allow_network(&self) -> caps::Network {
+        self.allow_network
+    }
+
+    /// The policy for subcommands [`Self::is_known_subcommand`] doesn't recognize
+    #[must_use]
+    pub fn unknown_subcommand(&self) -> UnknownSubcommand {
+        self.unknown_subcommand
+    }
+
+    /// Return `true` if `subcommand` should use the current working directory as the sandbox root
+    #[must_use]
+    pub fn is_projectless(&self, subcommand: Option<&SubcommandName>) -> bool {
+        subcommand.is_some_and(|x| self.projectless_subcommands.contains(x))
+    }
+
+    /// Return `true` if `subcommand` should use [`Self::no_root_base`] as the sandbox root
+    #[must_use]
+    pub fn is_no_root(&self, subcommand: Option<&SubcommandName>) -> bool {
+        subcommand.is_some_and(|x| self.no_root_subcommands.contains(x))
+    }
+
+    /// The sandbox root for `no_root_subcommands`, if it isn't to be the home directory
+    #[must_use]
+    pub fn no_root_base(&self) -> Option<&Path> {
+        self.no_root_base.as_deref()
+    }
+
+    /// Determine the network access to grant, taking `allow_network_subcommands` into account,
+    /// and which of the two fields the decision came from
+    #[must_use]
+    pub fn network_for(
+        &self,
+        subcommand: Option<&SubcommandName>,
+    ) -> (caps::Network, NetworkSource) {
+        match subcommand {
+            Some(x) if self.allow_network_subcommands.contains(x) => {
+                (caps::Network::AllNetworks, NetworkSource::AllowNetworkSubcommands)
+            },
+            _ => (self.allow_network, NetworkSource::AllowNetwork),
+        }
+    }
+
+    /// The DNS servers to use when the sandbox has network access
+    #[must_use]
+    pub fn dns(&self) -> &[IpAddr] {
+        &self.dns
+    }
+
+    /// Return `true` if any invocation of this profile could be granted network access
+    fn can_have_network(&self) -> bool {
+        self.allow_network == caps::Network::AllNetworks
+            || !self.allow_network_subcommands.is_empty()
+            || self.network_interface.as_ref().is_some_and(|x| !x.is_loopback())
+    }
+
+    /// The flags to use in place of the top-level `firejail_base_flags`, if any
+    #[must_use]
+    pub fn base_flags_override(&self) -> Option<&[String]> {
+        self.base_flags_override.as_deref()
+    }
+
+    /// The Firejail `.profile` to use as a base, if any
+    #[must_use]
+    pub fn firejail_profile(&self) -> Option<&FirejailProfile> {
+        self.firejail_profile.as_ref()
+    }
+
+    /// The interface to scope restricted network access to, if any
+    #[must_use]
+    pub fn network_interface(&self) -> Option<&InterfaceName> {
+        self.network_interface.as_ref()
+    }
+
+    /// Whether the sandboxed command may modify the project root
+    #[must_use]
+    pub fn project_access(&self) -> ProjectAccess {
+        self.project_access
+    }
+
+    /// The subdirectories of the project root to make writable in read-only mode
+    #[must_use]
+    pub fn writable_subdirs(&self) -> &[FileName] {
+        &self.writable_subdirs
+    }
+
+    /// The file/directory names which mark a directory as the project root
+    #[must_use]
+    pub fn root_marked_by(&self) -> &[RootMarker] {
+        &self.root_marked_by
+    }
+
+    /// Whether abstract Unix domain sockets outside the sandbox stay reachable without network
+    /// access
+    #[must_use]
+    pub fn allow_abstract_sockets(&self) -> caps::AbstractSockets {
+        self.allow_abstract_sockets
+    }
+
+    /// The markers which only identify the project root when all are present together
+    #[must_use]
+    pub fn root_marked_by_all(&self) -> &[RootMarker] {
+        &self.root_marked_by_all
+    }
+
+    /// The policy for choosing between multiple `root_marked_by` matches
+    #[must_use]
+    pub fn root_find_outermost(&self) -> caps::ProjectRoot {
+        self.root_find_outermost
+    }
+
+    /// The host name the sandbox should report, if any
+    #[must_use]
+    pub fn hostname(&self) -> Option<&Hostname> {
+        self.hostname.as_ref()
+    }
+
+    /// The capabilities to keep instead of dropping them all
+    #[must_use]
+    pub fn keep_caps(&self) -> &[Capability] {
+        &self.keep_caps
+    }
+
+    /// The wall-clock limit after which the sandbox should be killed, if any
+    #[must_use]
+    pub fn timeout(&self) -> Option<&Timeout> {
+        self.timeout.as_ref()
+    }
+
+    /// The directory `argv[0]` must resolve to a program inside for this profile to apply, if any
+    #[must_use]
+    pub fn command_path_prefix(&self) -> Option<&Path> {
+        self.command_path_prefix.as_deref()
+    }
+
+    /// The umask to run the command with, if any
+    #[must_use]
+    pub fn umask(&self) -> Option<&Umask> {
+        self.umask.as_ref()
+    }
+
+    /// The only programs the command may run, if restricted
+    #[must_use]
+    pub fn private_bin(&self) -> &[FileName] {
+        &self.private_bin
+    }
+
+    /// The check to run inside the sandbox before the command, if any
+    #[must_use]
+    pub fn pre_exec(&self) -> Option<&[String]> {
+        self.pre_exec.as_deref()
+    }
+
+    /// The uid the command should see itself running as, if it should be remapped
+    #[must_use]
+    pub fn map_uid(&self) -> Option<u32> {
+        self.map_uid
+    }
+
+    /// The unexpanded paths outside the project root to make accessible in the sandbox
+    #[must_use]
+    pub fn whitelist(&self) -> &[String] {
+        &self.whitelist
+    }
+
+    /// The unexpanded paths to deny the sandboxed command access to
+    #[must_use]
+    pub fn blacklist(&self) -> &[String] {
+        &self.blacklist
+    }
+
+    /// Map the exit code of the sandboxed command to the one `nodo` should exit with, applying
+    /// `success_exit_codes`
+    #[must_use]
+    pub fn remap_exit_code(&self, code: i32) -> i32 {
+        if self.success_exit_codes.contains(&code) {
+            0
+        } else {
+            code
+        }
+    }
+
+    /// The environment variables to set for the sandboxed command
+    #[must_use]
+    pub fn env_set(&self) -> &BTreeMap<String, String> {
+        &self.env_set
+    }
+
+    /// Perform the per-profile part of [`Config::validate`], returning problems without saying
+    /// which profile they're in
+    #[allow(clippy::too_many_lines)] // A flat list of independent checks
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for flag in self.base_flags_override.iter().flatten() {
+            if let Some(effect) = dangerous_flag(flag) {
+                problems.push(format!("'base_flags_override' must not {effect}: {flag}"));
+            }
+        }
+        for pattern in &self.command_matches {
+            if pattern.is_empty() || pattern.contains('/') || pattern.chars().all(|c| c == '*') {
+                problems.push(format!(
+                    "'command_matches' entries must be non-empty, may not contain '/', and must \
+                     contain more than '*': {pattern:?}"
+                ));
+            }
+        }
+        for name in &self.private_bin {
+            if name.is_dot_entry() || name.has_comma() {
+                problems.push(format!(
+                    "'private_bin' entries must be plain program names, not \"{name}\""
+                ));
+            }
+        }
+        // The wrappers `build_command` puts around the command need these to be runnable too
+        for (program, needed_for, needed) in [
+            ("sh", "'umask' or 'pre_exec'", self.umask.is_some() || self.pre_exec.is_some()),
+            ("unshare", "'map_uid'", self.map_uid.is_some()),
+        ] {
+            let listed =
+                FileName::try_from(program.to_owned()).is_ok_and(|x| self.private_bin.contains(&x));
+            if needed && !self.private_bin.is_empty() && !listed {
+                problems.push(format!(
+                    "'private_bin' must include \"{program}\" when {needed_for} is set, since \
+                     it's used to apply them"
+                ));
+            }
+        }
+        if self.pre_exec.as_ref().is_some_and(|x| x.first().is_none_or(String::is_empty)) {
+            problems.push("'pre_exec' must start with a non-empty command name".to_owned());
+        }
+        if let Some(ref base) = self.no_root_base {
+            if !base.is_absolute() || base.components().any(|x| x == Component::ParentDir) {
+                problems.push(format!(
+                    "'no_root_base' must be an absolute path without '..' components: {}",
+                    base.display()
+                ));
+            }
+        }
+        for subcommand in
+            self.no_root_subcommands.iter().filter(|x| self.projectless_subcommands.contains(x))
+        {
+            problems.push(format!(
+                "\"{subcommand}\" can't be in both 'no_root_subcommands' and \
+                 'projectless_subcommands'"
+            ));
+        }
+        if let Some(ref prefix) = self.command_path_prefix {
+            if !prefix.is_absolute() || prefix.components().any(|x| x == Component::ParentDir) {
+                problems.push(format!(
+                    "'command_path_prefix' must be an absolute path without '..' components: {}",
+                    prefix.display()
+                ));
+            }
+        }
+        // `is_bad_name` can't catch these since they contain no separators, but they exist in
+        // every directory and so would make whatever directory the walk starts in the root
+        for (key, markers) in [
+            ("root_marked_by", &self.root_marked_by),
+            ("root_marked_by_all", &self.root_marked_by_all),
+        ] {
+            for marker in markers.iter().map(RootMarker::name).filter(|x| x.is_dot_entry()) {
+                problems.push(format!(
+                    "'{key}' must not contain \"{marker}\", which is present in every directory \
+                     and can't mark a project root"
+                ));
+            }
+        }
+        for entry in self.root_blacklist.iter().filter(|x| x.is_dot_entry()) {
+            problems.push(format!(
+                "'root_blacklist' must name something inside the project root, not \"{entry}\""
+            ));
+        }
+        for subdir in self.writable_subdirs.iter().filter(|x| x.is_dot_entry()) {
+            problems.push(format!(
+                "'writable_subdirs' must name a subdirectory of the project root, not \"{subdir}\""
+            ));
+        }
+        if !self.writable_subdirs.is_empty() && self.project_access == ProjectAccess::ReadWrite {
+            problems.push(
+                "sets 'writable_subdirs', which has no effect unless 'project_access' is \
+                 \"read_only\""
+                    .to_owned(),
+            );
+        }
+        if self.map_uid.is_some_and(|uid| !(1..=65534).contains(&uid)) {
+            problems.push(format!(
+                "'map_uid' must be from 1 to 65534 (0 would pose as root): {}",
+                self.map_uid.unwrap_or_default()
+            ));
+        }
+        for code in &self.success_exit_codes {
+            if !(1..=255).contains(code) {
+                problems
+                    .push(format!("'success_exit_codes' entries must be from 1 to 255: {code}"));
+            }
+        }
+        if self.unknown_subcommand == UnknownSubcommand::Passthrough {
+            problems.push(
+                "sets 'unknown_subcommand' to \"passthrough\", which would run unrecognized \
+                 subcommands unsandboxed and is not supported (use \"deny\" or \"sandbox\")"
+                    .to_owned(),
+            );
+        }
+        for (key, value) in &self.env_set {
+            if !is_env_name(key) {
+                problems.push(format!(
+                    "'env_set' key {key:?} is not a valid environment variable name"
+                ));
+            } else if key == crate::sandbox::ACTIVE_ENV_VAR {
+                problems.push(format!(
+                    "'env_set' must not set {key}, which {} uses to detect that it's already \
+                     inside a sandbox",
+                    env!("CARGO_PKG_NAME")
+                ));
+            }
+            if value.contains('\0') {
+                problems.push(format!("'env_set' value for {key:?} contains a null byte"));
+            }
+        }
+        for (alias, target) in &self.subcommand_aliases {
+            let canonical = self.alias_chain(alias).last().copied();
+            if canonical.is_none_or(|x| self.subcommand_aliases.contains_key(x)) {
+                problems.push(format!(
+                    "'subcommand_aliases' entry \"{alias}\" = \"{target}\" leads into a cycle \
+                     and never reaches a subcommand which isn't an alias"
+                ));
+            }
+            for (key, list) in [
+                ("allow_network_subcommands", &self.allow_network_subcommands),
+                ("deny_subcommands", &self.deny_subcommands),
+                ("projectless_subcommands", &self.projectless_subcommands),
+                ("no_root_subcommands", &self.no_root_subcommands),
+            ] {
+                if list.contains(alias) {
+                    problems.push(format!(
+                        "'{key}' entry \"{alias}\" will never apply because 'subcommand_aliases' \
+                         resolves it to \"{}\" first",
+                        self.canonical_subcommand(alias.clone())
+                    ));
+                }
+            }
+        }
+        if self.root_marked_by.is_empty() && self.root_marked_by_all.is_empty() {
+            problems.push(
+                "'root_marked_by' must contain at least one file/folder name unless \
+                 'root_marked_by_all' is set"
+                    .to_owned(),
+            );
+        }
+        if self.dns.len() > MAX_DNS_SERVERS {
+            problems.push(format!("'dns' may list at most {MAX_DNS_SERVERS} servers"));
+        }
+        if !self.dns.is_empty() && !self.can_have_network() {
+            problems.push("sets 'dns' but can never have network access".to_owned());
+        }
+        problems
+    }
+}
+
+/// The Firejail flag used to protect `root_blacklist` entries
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlacklistMode {
+    /// Hide the entries entirely with `--blacklist`.
+    ///
+    /// The sandboxed command can neither read nor modify them, which also keeps things like
+    /// credentials in `.git/config` out of reach, but tools which need to read them (eg. build
+    /// scripts that call `git describe`) will fail.
+    #[default]
+    Hide,
+    /// Leave the entries visible but unmodifiable with `--read-only`.
+    ///
+    /// This is the closer analogue to `chattr +a` for auditing with `git diff`, since tools can
+    /// still read version-control metadata, but anything secret in them is exposed to the
+    /// sandboxed command.
+    ReadOnly,
+}
+
+/// How much access the sandboxed command is given to the project root
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectAccess {
+    /// Allow the command to modify anything in the project root not covered by `root_blacklist`.
+    #[default]
+    ReadWrite,
+    /// Mount the project root with `--read-only`, except for the profile's `writable_subdirs`,
+    /// which are re-granted write access with `--read-write`.
+    ///
+    /// Useful for commands like linters which should only ever need to write to a build
+    /// directory, if anywhere.
+    ReadOnly,
+}
+
+/// The policy for subcommands a profile doesn't mention
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownSubcommand {
+    /// Refuse to run them, for commands where an unrecognized subcommand is suspicious (eg. one
+    /// provided by a plugin the project pulled in).
+    Deny,
+    /// Run them in the sandbox with the profile's defaults.
+    #[default]
+    Sandbox,
+    /// Run them without a sandbox.
+    ///
+    /// This is only recognized so it can be rejected with an explanation. Silently running
+    /// anything unsandboxed defeats the point of `nodo`.
+    Passthrough,
+}
+
+/// The schema for the configuration file as a whole
+#[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[allow(clippy::struct_excessive_bools)] // Independent on/off settings, as spelled in the file
+pub struct Config {
+    /// A list of flags to pass to Firejail before the flags determined by the profile but after
+    /// the hard-coded flags generated to do things like blacklisting the sandboxing
+    /// configuration file.
+    ///
+    /// This field must be specified. If you *really* mean to specify a sandbox that's as full of
+    /// holes as Swiss cheese, explicitly use an empty list.
+    ///
+    /// Options which would undermine the generated flags (eg. `--noblacklist`) are rejected. (See
+    /// [`DANGEROUS_FLAGS`].)
+    firejail_base_flags: Vec<String>,
+
+    /// How `root_blacklist` entries are protected. (See [`BlacklistMode`].)
+    #[serde(default)]
+    blacklist_mode: BlacklistMode,
+
+    /// A default list of root-relative paths to be denied access to.
+    ///
+    /// (The idea being to provide an analogue to `chattr +a foo.log` so `git diff` can be used to
+    /// reveal attempts by malware inside the sandbox to sneak malicious code into a commit.)
+    #[serde(default)]
+    root_blacklist: Vec<FileName>,
+
+    /// A list of mappings from command names (`argv[0]`) to the sandboxing profiles to be applied
+    ///
+    /// The canonical form is a table of `[profile.<command>]` tables, but an array of
+    /// `[[profile]]` tables, each with a `command` key, is also accepted for the convenience of
+    /// tools which generate configuration files.
+    ///
+    /// Profile names are matched against the basename of `argv[0]` case-sensitively, and
+    /// [`Config::validate`] rejects names which differ only by case.
+    #[serde(rename = "profile", deserialize_with = "deserialize_profiles")]
+    profiles: BTreeMap<CommandName, CommandProfile>,
+
+    /// If `true`, reject the configuration file if it contains keys which would otherwise be
+    /// silently ignored (eg. a typo like `root_marked_buy`).
+    ///
+    /// This defaults to `false` so existing configuration files keep loading, but the default
+    /// configuration file written by `--write-conf` turns it on.
+    #[serde(default)]
+    strict: bool,
+
+    /// If present, the only commands (`argv[0]` basenames) `nodo` will agree to run, even if
+    /// profiles exist for others.
+    ///
+    /// This is meant for locked-down environments where an administrator wants `nodo` to only
+    /// ever wrap a known set of tools. If absent, any command with a profile may be run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    allowed_commands: Option<Vec<CommandName>>,
+
+    /// If `true`, never pass `--quiet` to Firejail, even if it's in `firejail_base_flags`, so its
+    /// own warnings are always shown.
+    ///
+    /// This only ever removes `--quiet`. `--debug` removes it too, so the two combine as "either
+    /// one is enough" and neither can put `--quiet` back.
+    #[serde(default)]
+    backend_verbose: bool,
+
+    /// If set, the absolute path of the Firejail executable to run, for when it isn't on `$PATH`
+    /// or a specific build is needed. By default, `firejail` is looked up via `$PATH`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    backend_path: Option<PathBuf>,
+
+    /// If `true`, a `nodo` invoked from inside a `nodo` sandbox (eg. by a `Makefile` that calls
+    /// `nodo cargo build`) runs its command directly, inside the existing sandbox, rather than
+    /// refusing to run.
+    ///
+    /// Nesting Firejail sandboxes doesn't work reliably, so a second sandbox is never created.
+    #[serde(default)]
+    allow_nesting: bool,
+
+    /// Absolute paths of directories which are too broad to ever be used as a discovered
+    /// project root, in addition to `/` and your home directory, which are always refused.
+    ///
+    /// (eg. A stray `Makefile` in `/home` or a `.git` in a directory holding all your projects
+    /// shouldn't turn into a sandbox that exposes everything below it.)
+    #[serde(default)]
+    forbidden_roots: Vec<PathBuf>,
+
+    /// Absolute paths of system directories to explicitly mount read-only in every sandbox, so
+    /// the host exposure a build gets doesn't depend on Firejail's defaults
+    ///
+    /// Defaults to `["/usr", "/etc", "/bin"]`. Setting it replaces that list rather than adding
+    /// to it.
+    #[serde(default = "default_system_readonly")]
+    system_readonly: Vec<PathBuf>,
+
+    /// Executable suffixes (eg. `".exe"`) to remove from the basename of `argv[0]` before looking
+    /// up its profile, so tools invoked through Wine or cross-compilation wrappers as `tool.exe`
+    /// get the `tool` profile
+    ///
+    /// Each must be a `.` followed by at least one character, with no path separators or
+    /// whitespace.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    strip_suffixes: Vec<String>,
+
+    /// If present, the most arguments (including the command itself) `nodo` will agree to
+    /// launch, as a defensive measure against pathologically long command lines
+    ///
+    /// If absent, the length of the command line is only limited by the OS.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_args: Option<usize>,
+
+    /// If present, a command line (eg. `["kinit", "-R"]`) to run on the host, outside any
+    /// sandbox, before each launch, which must succeed for the launch to go ahead
+    ///
+    /// **This runs with all of your privileges** every time `nodo` launches a sandbox, so only set
+    /// it if you trust both the command and everyone who can write to this file. It isn't run
+    /// for `--dry-run` or when `nodo` is nested inside its own sandbox.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pre_launch: Option<Vec<String>>,
+
+    /// If `true`, a command with no profile by name (or `command_matches`) is looked up via
+    /// `$PATH` and matched to the profile whose name is found to be the same file, so the many
+    /// names of a multi-call binary (eg. `ls` and `cat` linked to `busybox`) can share the profile
+    /// for the binary (eg. `[profile.busybox]`)
+    ///
+    /// Files are compared by device and inode after following symlinks. This defaults to `false`,
+    /// so only names are matched unless it's asked for.
+    #[serde(default)]
+    match_by_binary: bool,
+
+    /// How many seconds a sandbox gets to exit after `nodo` passes on a `SIGINT` or `SIGTERM`
+    /// before it's killed with `SIGKILL`, so a child that ignores them can't leave `nodo` hanging
+    ///
+    /// Defaults to 5. Zero means `SIGKILL` follows immediately.
+    #[serde(default = "default_kill_grace_seconds")]
+    kill_grace_seconds: u32,
+
+    /// If `true`, ask Firejail which options it supports (via `--help`) before launching and
+    /// refuse to launch if any of the ones the sandbox needs are missing, rather than trusting
+    /// that an older or differently built Firejail will enforce them.
+    ///
+    /// If `false`, just warn. This defaults to `true`, since a sandbox silently missing (eg.)
+    /// `--seccomp` is weaker than the one the configuration asked for.
+    #[serde(default = "default_fail_closed")]
+    fail_closed: bool,
+}
+
+/// The default for [`Config::kill_grace_seconds`]
+fn default_kill_grace_seconds() -> u32 {
+    5
+}
+
+/// The default for [`Config::fail_closed`]
+fn default_fail_closed() -> bool {
+    true
+}
+
+/// The default for [`Config::system_readonly`]
+fn default_system_readonly() -> Vec<PathBuf> {
+    ["/usr", "/etc", "/bin"].into_iter().map(PathBuf::from).collect()
+}
+
+/// A single entry in the `[[profile]]` form of [`Config::profiles`]
+#[derive(Deserialize)]
+struct NamedProfile {
+    /// The command name which would be the key in the `[profile.<command>]` form
+    command: CommandName,
+    /// Everything else
+    #[serde(flatten)]
+    profile: CommandProfile,
+}
+
+/// Deserialize [`Config::profiles`] from either of its accepted forms, normalizing to the map
+///
+/// (Implemented as a `Visitor` rather than `#[serde(untagged)]` so that errors inside a profile
+/// still get reported instead of a generic "did not match any variant".)
+fn deserialize_profiles<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<CommandName, CommandProfile>, D::Error> {
+    /// `Visitor` that dispatches on whether the `profile` key holds a table or an array
+    struct ProfilesVisitor;
+
+    impl<'de> Visitor<'de> for ProfilesVisitor {
+        type Value = BTreeMap<CommandName, CommandProfile>;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("a table of profiles or an array of profiles with `command` keys")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            serde::Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut profiles = BTreeMap::new();
+            while let Some(NamedProfile { command, profile }) = seq.next_element()? {
+                if profiles.contains_key(&command) {
+                    return Err(de::Error::custom(format!("duplicate profile for {command}")));
+                }
+                profiles.insert(command, profile);
+            }
+            Ok(profiles)
+        }
+    }
+
+    deserializer.deserialize_any(ProfilesVisitor)
+}
+
+impl Config {
+    /// Look up the sandboxing profile for the given command name
+    #[must_use]
+    pub fn profile(&self, command: &CommandName) -> Option<&CommandProfile> {
+        self.profiles.get(command)
+    }
+
+    /// Every configured profile, in name order
+    pub fn profiles(&self) -> impl Iterator<Item = (&CommandName, &CommandProfile)> {
+        self.profiles.iter()
+    }
+
+    /// Look up the sandboxing profile for `command`, falling back to `command_matches` patterns
+    /// if no profile has that exact name
+    ///
+    /// Returns the name of the profile used along with the profile itself.
+    ///
+    /// # Errors
+    ///
+    /// [`Error::NoProfile`](crate::error::Error::NoProfile) if nothing matches or
+    /// [`Error::AmbiguousProfile`](crate::error::Error::AmbiguousProfile) if several
+    /// `command_matches` do.
+    pub fn match_profile(
+        &self,
+        command: &CommandName,
+    ) -> Result<(&CommandName, &CommandProfile), crate::error::Error> {
+        if let Some(exact) = self.profiles.get_key_value(command) {
+            return Ok(exact);
+        }
+        let name = command.to_string();
+        let mut matches = self.profiles.iter().filter(|(_, profile)| {
+            profile.command_matches.iter().any(|pattern| glob_matches(pattern, &name))
+        });
+        match (matches.next(), matches.next()) {
+            (None, _) => Err(crate::error::Error::NoProfile(command.clone())),
+            (Some(only), None) => Ok(only),
+            (Some(first), Some(second)) => {
+                let mut candidates = vec![first.0.clone(), second.0.clone()];
+                candidates.extend(matches.map(|(name, _)| name.clone()));
+                Err(crate::error::Error::AmbiguousProfile(command.clone(), candidates))
+            },
+        }
+    }
+
+    /// The flags passed to Firejail between the hard-coded flags and the profile-derived ones
+    #[must_use]
+    pub fn firejail_base_flags(&self) -> &[String] {
+        &self.firejail_base_flags
+    }
+
+    /// The root-relative names to deny the sandboxed command access to
+    #[must_use]
+    pub fn root_blacklist(&self) -> &[FileName] {
+        &self.root_blacklist
+    }
+
+    /// The directories, beyond `/` and the home directory, which may never be a project root
+    #[must_use]
+    pub fn forbidden_roots(&self) -> &[PathBuf] {
+        &self.forbidden_roots
+    }
+
+    /// The suffixes to strip from the basename of `argv[0]` before looking up its profile
+    #[must_use]
+    pub fn strip_suffixes(&self) -> &[String] {
+        &self.strip_suffixes
+    }
+
+    /// The system directories to mount read-only in every sandbox
+    #[must_use]
+    pub fn system_readonly(&self) -> &[PathBuf] {
+        &self.system_readonly
+    }
+
+    /// The root-relative names to protect when running `profile`: the top-level `root_blacklist`
+    /// followed by any of the profile's own not already listed
+    #[must_use]
+    pub fn root_blacklist_for<'a>(&'a self, profile: &'a CommandProfile) -> Vec<&'a FileName> {
+        let mut entries: Vec<&FileName> = self.root_blacklist.iter().collect();
+        for entry in &profile.root_blacklist {
+            if !entries.contains(&entry) {
+                entries.push(entry);
+            }
+        }
+        entries
+    }
+
+    /// How `root_blacklist` entries should be protected
+    #[must_use]
+    pub fn blacklist_mode(&self) -> BlacklistMode {
+        self.blacklist_mode
+    }
+
+    /// The Firejail executable to run, if it shouldn't be looked up via `$PATH`
+    #[must_use]
+    pub fn backend_path(&self) -> Option<&Path> {
+        self.backend_path.as_deref()
+    }
+
+    /// Whether `--quiet` should be omitted from the Firejail command line even without `--debug`
+    #[must_use]
+    pub fn backend_verbose(&self) -> bool {
+        self.backend_verbose
+    }
+
+    /// Whether invocations from inside a `nodo` sandbox should run directly instead of failing
+    #[must_use]
+    pub fn allow_nesting(&self) -> bool {
+        self.allow_nesting
+    }
+
+    /// The only commands which may be run, or `None` if unrestricted
+    #[must_use]
+    pub fn allowed_commands(&self) -> Option<&[CommandName]> {
+        self.allowed_commands.as_deref()
+    }
+
+    /// The most arguments a command line may have, or `None` if unlimited
+    #[must_use]
+    pub fn max_args(&self) -> Option<usize> {
+        self.max_args
+    }
+
+    /// How long a sandbox asked to exit gets before it's killed (see [`crate::sandbox::launch`])
+    #[must_use]
+    pub fn kill_grace(&self) -> Duration {
+        Duration::from_secs(self.kill_grace_seconds.into())
+    }
+
+    /// Whether to refuse to launch when Firejail doesn't support an option the sandbox needs
+    #[must_use]
+    pub fn fail_closed(&self) -> bool {
+        self.fail_closed
+    }
+
+    /// Whether profiles may be matched by the file `argv[0]` resolves to
+    #[must_use]
+    pub fn match_by_binary(&self) -> bool {
+        self.match_by_binary
+    }
+
+    /// The command line to run on the host before each launch, if any
+    #[must_use]
+    pub fn pre_launch(&self) -> Option<&[String]> {
+        self.pre_launch.as_deref()
+    }
+
+    /// Re-serialize the effective configuration, with all defaults filled in, as canonical TOML
+    ///
+    /// Comments and the `[[profile]]` form aren't preserved, so this is for answering "what is
+    /// `nodo` actually using?" rather than for rewriting the user's file.
+    ///
+    /// # Errors
+    ///
+    /// Only fails if the configuration contains something TOML can't represent.
+    pub fn to_toml(&self) -> Result<String, toml_edit::ser::Error> {
+        toml_edit::ser::to_string_pretty(self)
+    }
+
+    /// Return advisory problems which don't make the configuration unusable, such as profiles
+    /// which can only be reached in an unusual way
+    #[must_use]
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        for (command, profile) in &self.profiles {
+            if crate::cli::RESERVED_FLAGS.contains(&command.to_string().as_str()) {
+                warnings.push(format!(
+                    "[profile.{command}] can only be used as `{} -- {command}` because \
+                     {command} is also one of {0}'s own flags",
+                    env!("CARGO_PKG_NAME")
+                ));
+            }
+            if profile.allow_network == caps::Network::AllNetworks
+                && !profile.allow_network_subcommands.is_empty()
+            {
+                warnings.push(format!(
+                    "[profile.{command}] 'allow_network_subcommands' has no effect because \
+                     'allow_network' already grants every subcommand network access (did you \
+                     mean to leave 'allow_network' false?)"
+                ));
+            }
+        }
+        warnings
+    }
+
+    /// Perform validation beyond what Serde is maintainably capable of, returning every problem
+    /// found rather than stopping at the first
+    ///
+    /// (Implemented manually rather than accepting [validator](https://github.com/Keats/validator)
+    /// as another point of trust in a tool meant to enforce security.)
+    ///
+    /// # Errors
+    ///
+    /// Returns a human-readable description of each problem found.
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        if self.profiles.is_empty() {
+            problems.push("Configuration file must contain at least one profile".to_owned());
+        }
+        for (key, paths) in
+            [("forbidden_roots", &self.forbidden_roots), ("system_readonly", &self.system_readonly)]
+        {
+            for path in paths.iter().filter(|x| !x.is_absolute()) {
+                problems
+                    .push(format!("'{key}' entries must be absolute paths: {}", path.display()));
+            }
+        }
+        for entry in self.root_blacklist.iter().filter(|x| x.is_dot_entry()) {
+            problems.push(format!(
+                "'root_blacklist' must name something inside the project root, not \"{entry}\""
+            ));
+        }
+        if let Some(ref path) = self.backend_path {
+            let executable = fs::metadata(path)
+                .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0);
+            if !path.is_absolute() || !executable {
+                problems.push(format!(
+                    "'backend_path' must be the absolute path of an executable file: {}",
+                    path.display()
+                ));
+            }
+        }
+        if self.pre_launch.as_ref().is_some_and(|x| x.first().is_none_or(String::is_empty)) {
+            problems.push("'pre_launch' must start with a non-empty command name".to_owned());
+        }
+        for suffix in &self.strip_suffixes {
+            let rest = suffix.strip_prefix('.').unwrap_or_default();
+            if rest.is_empty()
+                || rest.contains(|x: char| x == '/' || x.is_whitespace() || x == '\0')
+            {
+                problems.push(format!(
+                    "'strip_suffixes' entries must be a '.' followed by a name, like \".exe\": \
+                     {suffix:?}"
+                ));
+            }
+        }
+        for flag in &self.firejail_base_flags {
+            if let Some(effect) = dangerous_flag(flag) {
+                problems.push(format!("'firejail_base_flags' must not {effect}: {flag}"));
+            }
+        }
+        for (command, profile) in &self.profiles {
+            problems
+                .extend(profile.problems().into_iter().map(|x| format!("[profile.{command}] {x}")));
+        }
+
+        // Profiles are matched case-sensitively, so names which differ only by case are almost
+        // certainly a mistake and would collide on case-insensitive filesystems.
+        let mut seen: BTreeMap<String, &CommandName> = BTreeMap::new();
+        for command in self.profiles.keys() {
+            if let Some(other) = seen.insert(command.to_string().to_lowercase(), command) {
+                problems.push(format!(
+                    "Profile names must not differ only by case: {other}, {command}"
+                ));
+            }
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::test_dir;
+    use std::convert::TryFrom;
+
+    /// Assert that `find_path` distinguishes an unknown home directory from an unusable one
+    ///
+    /// (This is the case the subprocess-based tests in `tests/config_find_path.rs` can't reach,
+    /// since `env::home_dir` consults `getpwuid_r` when `$HOME` is unset.)
+    #[test]
+    fn find_path_without_home() {
+        let guard = test_dir("find_path_without_home");
+        let base = guard.path().to_owned();
+        let file_name = format!("{}.toml", env!("CARGO_PKG_NAME"));
+
+        assert_eq!(find_path_from(None, None), Err(PathError::NoHome));
+        assert_eq!(find_path_from(Some(OsStr::new("relative")), None), Err(PathError::NoHome));
+        assert_eq!(find_path_from(Some(base.as_os_str()), None), Ok(base.join(&file_name)));
+        assert_eq!(find_path_from(None, Some(&base)), Err(PathError::NoConfigDir));
+        assert_eq!(find_path_from(None, Some(Path::new(""))), Err(PathError::NoConfigDir));
+
+        fs::create_dir(base.join(".config")).unwrap();
+        assert_eq!(find_path_from(None, Some(&base)), Ok(base.join(".config").join(&file_name)));
+    }
+
+    /// Assert that a system-wide defaults file is preferred over the embedded defaults but not
+    /// over the user's own file
+    #[test]
+    fn system_defaults_fallback() {
+        let guard = test_dir("system_defaults_fallback");
+        let base = guard.path().to_owned();
+        let (user, system) = (base.join("user.toml"), base.join("system.toml"));
+        let has_profile = |config: &Config, name: &str| {
+            config.profile(&CommandName::try_from(name.to_owned()).unwrap()).is_some()
+        };
+        fs::write(&system, "firejail_base_flags = []\n[profile.distro]\nroot_marked_by = [\"x\"]")
+            .unwrap();
+
+        let config = load_with_system_defaults(Some(&user), Some(&system)).unwrap();
+        assert!(has_profile(&config, "distro"));
+        assert!(!has_profile(&config, "cargo"));
+
+        let config = load_with_system_defaults(Some(&user), Some(&base.join("missing"))).unwrap();
+        assert!(has_profile(&config, "cargo"));
+        assert!(!has_profile(&config, "distro"));
+
+        fs::write(&user, "firejail_base_flags = []\n[profile.mine]\nroot_marked_by = [\"x\"]")
+            .unwrap();
+        let config = load_with_system_defaults(Some(&user), Some(&system)).unwrap();
+        assert!(has_profile(&config, "mine"));
+        assert!(!has_profile(&config, "distro"));
+    }
+
+    /// Assert that a failure to specify at least one profile or a failure to include
+    /// a `root_marked_by` field in the profile will be caught at TOML parsing time
+    /// and that `.validate()` will reject empty `Vec`s.
+    #[test]
+    fn profiles_required() {
+        toml_from_str::<Config>("").unwrap_err();
+        toml_from_str::<Config>("firejail_base_flags=[]\nprofile = {}")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        toml_from_str::<Config>("firejail_base_flags=[]\n[profile.make]").unwrap_err();
+        toml_from_str::<Config>("firejail_base_flags=[]\n[profile.make]\nroot_marked_by = []")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        toml_from_str::<Config>("firejail_base_flags=[]\n[profile.make]\nroot_marked_by = [\"\"]")
+            .unwrap_err();
+        toml_from_str::<Config>(
+            "firejail_base_flags=[]\n[profile.make]\nroot_marked_by=[\"Makefile\"]",
+        )
+        .unwrap()
+        .validate()
+        .unwrap();
     }
 
     /// Assert that the field defaults for a profile are the most secure options
@@ -207,10 +1951,83 @@ mod test {
         let profile: CommandProfile = toml_from_str("root_marked_by=[\"foo\"]").unwrap();
 
         assert_eq!(profile.allow_network, caps::Network::ChildProcsOnly);
+        assert_eq!(profile.allow_abstract_sockets, caps::AbstractSockets::Isolated);
         assert!(profile.allow_network_subcommands.is_empty());
         assert!(profile.projectless_subcommands.is_empty());
+        assert!(profile.no_root_subcommands.is_empty());
+        assert!(profile.no_root_base.is_none());
+        assert!(profile.pre_exec.is_none());
+        assert!(profile.private_bin.is_empty());
         assert!(profile.subcommand_aliases.is_empty());
         assert_eq!(profile.root_find_outermost, caps::ProjectRoot::Innermost);
+        assert_eq!(profile.timeout, None);
+        assert_eq!(profile.hostname, None);
+        assert!(profile.keep_caps.is_empty());
+        assert_eq!(profile.umask, None);
+        assert_eq!(profile.map_uid, None);
+        assert_eq!(profile.network_interface, None);
+        assert!(profile.dns.is_empty());
+        assert_eq!(profile.firejail_profile, None);
+        assert_eq!(profile.base_flags_override, None);
+        assert_eq!(profile.project_access, ProjectAccess::ReadWrite);
+        assert_eq!(profile.unknown_subcommand, UnknownSubcommand::Sandbox);
+        assert!(profile.success_exit_codes.is_empty());
+        assert!(profile.root_blacklist.is_empty());
+        assert!(profile.root_marked_by_all.is_empty());
+        assert!(profile.command_path_prefix.is_none());
+        assert!(profile.writable_subdirs.is_empty());
+        assert!(profile.env_set.is_empty());
+        assert!(profile.whitelist.is_empty());
+        assert!(profile.blacklist.is_empty());
+    }
+
+    /// Assert that a malformed `timeout` is rejected when the config is parsed rather than when
+    /// Firejail is invoked
+    #[test]
+    fn timeout_validated_at_parse_time() {
+        let profile: CommandProfile =
+            toml_from_str("root_marked_by=[\"foo\"]\ntimeout=\"01:30:00\"").unwrap();
+        assert_eq!(profile.timeout, Some(Timeout::try_from("01:30:00".to_owned()).unwrap()));
+
+        let err = toml_from_str::<CommandProfile>("root_marked_by=[\"foo\"]\ntimeout=\"90:00\"")
+            .unwrap_err();
+        assert!(err.to_string().contains("timeout must be in HH:MM:SS form"));
+    }
+
+    /// Assert that serializing the default configuration and parsing the result yields the same
+    /// `Config`, so `--dump-config` and `--write-conf --merge` can't silently change a setting
+    /// (eg. by a capability enum serializing in a form it can't be deserialized from)
+    #[test]
+    fn default_config_round_trips() {
+        let non_defaults = "firejail_base_flags = []\n\
+                            [profile.tool]\n\
+                            allow_network = true\n\
+                            root_find_outermost = \"outermost\"\n\
+                            root_marked_by = [\"Makefile\"]\n\
+                            subcommand_aliases = { b = \"build\" }\n\
+                            umask = \"0077\"";
+        for source in [DEFAULT_CONFIG, non_defaults] {
+            let config = parse(source).unwrap();
+            let dumped = config.to_toml().unwrap();
+            assert_eq!(parse(&dumped).unwrap(), config, "{dumped}");
+        }
+    }
+
+    /// Assert that `umask` accepts 3 or 4 octal digits and rejects anything else at parse time
+    #[test]
+    fn umask_validated_at_parse_time() {
+        let profile: CommandProfile =
+            toml_from_str("root_marked_by=[\"foo\"]\numask=\"0077\"").unwrap();
+        assert_eq!(profile.umask, Some(Umask::try_from("0077".to_owned()).unwrap()));
+        toml_from_str::<CommandProfile>("root_marked_by=[\"foo\"]\numask=\"027\"").unwrap();
+
+        for bad in ["0999", "1777", "77", "00777", "u=rwx", ""] {
+            let err = toml_from_str::<CommandProfile>(&format!(
+                "root_marked_by=[\"foo\"]\numask=\"{bad}\""
+            ))
+            .unwrap_err();
+            assert!(err.to_string().contains("umask must be"), "{bad}: {err}");
+        }
     }
 
     /// Assert that profile fields not directly related to security have unsurprising
@@ -226,7 +2043,779 @@ mod test {
         assert!(profile.deny_subcommands.is_empty());
 
         // Just to be thorough
-        assert_eq!(profile.root_marked_by, [FileName::try_from("foo".to_owned()).unwrap()]);
+        assert_eq!(
+            profile.root_marked_by,
+            [RootMarker::Name(FileName::try_from("foo".to_owned()).unwrap())]
+        );
+    }
+
+    /// Assert that `strict = true` rejects misspelled keys which are otherwise silently ignored
+    #[test]
+    fn strict_rejects_unknown_keys() {
+        let typo = "firejail_base_flags=[]\n[profile.make]\nroot_marked_by=[\"Makefile\"]\n\
+                    root_marked_buy=[\"GNUmakefile\"]";
+
+        // Lenient, both by default and explicitly
+        parse(typo).unwrap();
+        parse(&format!("strict = false\n{typo}")).unwrap();
+
+        // Strict, at both levels, including inline tables
+        let err = parse(&format!("strict = true\n{typo}")).unwrap_err();
+        assert!(err.to_string().ends_with(": profile.make.root_marked_buy"), "{err}");
+        let err = parse("strict = true\nfirejail_base_flag=[]\nfirejail_base_flags=[]\nprofile={}")
+            .unwrap_err();
+        assert!(err.to_string().ends_with(": firejail_base_flag"), "{err}");
+        let err = parse(
+            "strict = true\nfirejail_base_flags=[]\n\
+             profile = { make = { root_marked_by=[\"Makefile\"], allow_netwrk=true } }",
+        )
+        .unwrap_err();
+        assert!(err.to_string().ends_with(": profile.make.allow_netwrk"), "{err}");
+
+        // The default config must pass its own strictness check
+        assert!(parse(DEFAULT_CONFIG).unwrap().strict);
+    }
+
+    /// Assert that `dns` entries must be IP addresses and only make sense with network access
+    #[test]
+    fn dns_validation() {
+        let with_dns = |dns: &str, extra: &str| {
+            parse(&format!(
+                "firejail_base_flags=[]\n[profile.cargo]\nroot_marked_by=[\"Cargo.toml\"]\n\
+                 dns = {dns}\n{extra}"
+            ))
+        };
+
+        let config = with_dns("[\"9.9.9.9\", \"2620:fe::fe\"]", "allow_network = true").unwrap();
+        config.validate().unwrap();
+        let profile = config.profile(&CommandName::try_from("cargo".to_owned()).unwrap()).unwrap();
+        assert_eq!(profile.dns().len(), 2);
+        with_dns("[\"9.9.9.9\"]", "allow_network_subcommands = [\"fetch\"]")
+            .unwrap()
+            .validate()
+            .unwrap();
+
+        let err = with_dns("[\"dns.quad9.net\"]", "allow_network = true").unwrap_err();
+        assert!(err.to_string().contains("invalid IP address syntax"), "{err}");
+
+        assert_eq!(
+            with_dns("[\"9.9.9.9\"]", "").unwrap().validate(),
+            Err(vec!["[profile.cargo] sets 'dns' but can never have network access".to_owned()])
+        );
+        assert_eq!(
+            with_dns("[\"9.9.9.9\"]", "network_interface = \"lo\"").unwrap().validate(),
+            Err(vec!["[profile.cargo] sets 'dns' but can never have network access".to_owned()])
+        );
+        assert_eq!(
+            with_dns(
+                "[\"1.1.1.1\", \"1.0.0.1\", \"9.9.9.9\", \"8.8.8.8\"]",
+                "allow_network = true"
+            )
+            .unwrap()
+            .validate(),
+            Err(vec!["[profile.cargo] 'dns' may list at most 3 servers".to_owned()])
+        );
+    }
+
+    /// Assert that `writable_subdirs` must stay inside the root and be paired with read-only mode
+    #[test]
+    fn writable_subdirs_validated() {
+        let config = |access: &str, subdir: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\n\
+                 root_marked_by = [\"Cargo.toml\"]\n\
+                 project_access = {access:?}\n\
+                 writable_subdirs = [{subdir:?}]"
+            ))
+        };
+        config("read_only", "target").unwrap().validate().unwrap();
+        assert_eq!(
+            config("read_only", "..").unwrap().validate(),
+            Err(vec![
+                "[profile.cargo] 'writable_subdirs' must name a subdirectory of the project root, \
+                 not \"..\""
+                    .to_owned()
+            ])
+        );
+        assert_eq!(
+            config("read_write", "target").unwrap().validate(),
+            Err(vec!["[profile.cargo] sets 'writable_subdirs', which has no effect unless \
+                 'project_access' is \"read_only\""
+                .to_owned()])
+        );
+        // Separators are already rejected when parsing
+        assert!(config("read_only", "target/debug").is_err());
+    }
+
+    /// Assert that `env_set` keys must be environment variable names and values can't contain
+    /// null bytes
+    #[test]
+    fn env_set_validated() {
+        let config = |entry: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\n\
+                 root_marked_by = [\"Cargo.toml\"]\n\
+                 env_set = {{ {entry} }}"
+            ))
+            .unwrap()
+        };
+        config("CARGO_HOME = \"/opt/cargo\", _x1 = \"\"").validate().unwrap();
+
+        let problem = |message: &str| Err(vec![format!("[profile.cargo] 'env_set' {message}")]);
+        assert_eq!(
+            config("\"1X\" = \"a\"").validate(),
+            problem("key \"1X\" is not a valid environment variable name")
+        );
+        assert_eq!(
+            config("\"A=B\" = \"a\"").validate(),
+            problem("key \"A=B\" is not a valid environment variable name")
+        );
+        assert_eq!(
+            config("\"\" = \"a\"").validate(),
+            problem("key \"\" is not a valid environment variable name")
+        );
+        assert_eq!(
+            config("X = \"a\\u0000b\"").validate(),
+            problem("value for \"X\" contains a null byte")
+        );
+        assert_eq!(
+            config("NODO_ACTIVE = \"\"").validate(),
+            problem(
+                "must not set NODO_ACTIVE, which nodo uses to detect that it's already inside a \
+                 sandbox"
+            )
+        );
+    }
+
+    /// Assert that the digest check accepts exactly the text it was computed from (in either
+    /// case) and refuses any change to it, including to lines which only look like a digest
+    /// setting, or a malformed digest
+    #[test]
+    fn sha256_checked() {
+        let body = "firejail_base_flags = []\n[profile.make]\nroot_marked_by = [\"Makefile\"]\n";
+        let digest = crate::sha256::hex_digest(body.as_bytes());
+
+        check_sha256(body, &digest).unwrap();
+        check_sha256(body, &digest.to_uppercase()).unwrap();
+        for tampered in [
+            body.replace("Makefile", "Rakefile"),
+            format!("{body}expected_sha256 = \"{digest}\"\n"),
+            body.trim_end().to_owned(),
+        ] {
+            let err = check_sha256(&tampered, &digest).unwrap_err();
+            assert!(err.contains("does not match NODO_CONFIG_SHA256"), "{err}");
+        }
+
+        let err = check_sha256(body, "abc").unwrap_err();
+        assert!(err.contains("64 hexadecimal digits"), "{err}");
+    }
+
+    /// Assert that the table form of `root_marked_by` entries is parsed, and refused if its name is
+    /// invalid, its substring is empty, or it has unrecognized keys
+    #[test]
+    fn content_markers_parsed() {
+        let profile: CommandProfile = toml_from_str(
+            "root_marked_by = [\"Makefile\", { name = \"pyproject.toml\", contains = \"[tool.poetry]\" }]",
+        )
+        .unwrap();
+        assert_eq!(
+            profile.root_marked_by[1],
+            RootMarker::Content {
+                name: FileName::try_from("pyproject.toml".to_owned()).unwrap(),
+                contains: "[tool.poetry]".to_owned()
+            }
+        );
+
+        for bad in [
+            "{ name = \"a/b\", contains = \"x\" }",
+            "{ name = \"a\", contains = \"\" }",
+            "{ name = \"a\", contains = \"x\", contain = \"y\" }",
+            "{ name = \"a\" }",
+        ] {
+            toml_from_str::<CommandProfile>(&format!("root_marked_by = [{bad}]")).unwrap_err();
+        }
+    }
+
+    /// Assert that `pre_launch` must name a command if it's present
+    #[test]
+    fn pre_launch_validated() {
+        let config_with = |hook: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\npre_launch = {hook}\n\
+                 [profile.make]\nroot_marked_by = [\"Makefile\"]"
+            ))
+            .unwrap()
+        };
+        config_with("[\"kinit\", \"-R\"]").validate().unwrap();
+        for bad in ["[]", "[\"\"]", "[\"\", \"-R\"]"] {
+            let problems = config_with(bad).validate().unwrap_err();
+            assert_eq!(
+                problems,
+                ["'pre_launch' must start with a non-empty command name"],
+                "{bad}"
+            );
+        }
+    }
+
+    /// Assert that `strip_suffixes` entries must look like file extensions
+    #[test]
+    fn strip_suffixes_validated() {
+        let config_with = |suffixes: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\nstrip_suffixes = {suffixes}\n\
+                 [profile.make]\nroot_marked_by = [\"Makefile\"]"
+            ))
+            .unwrap()
+        };
+        config_with("[\".exe\", \".cmd\", \".bat\"]").validate().unwrap();
+        for bad in ["", ".", "exe", "./x", ". exe"] {
+            let problems = config_with(&format!("[{bad:?}]")).validate().unwrap_err();
+            assert_eq!(problems.len(), 1, "{bad:?}");
+            assert!(problems[0].starts_with("'strip_suffixes' entries must be"), "{bad:?}");
+        }
+    }
+
+    /// Assert that `map_uid` refuses root and values outside the usual uid range
+    #[test]
+    fn map_uid_validated() {
+        let config_with = |uid: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.make]\nroot_marked_by = [\"Makefile\"]\nmap_uid = {uid}"
+            ))
+        };
+        config_with("1000").unwrap().validate().unwrap();
+        config_with("65534").unwrap().validate().unwrap();
+        for bad in ["0", "65535", "4294967295"] {
+            let problems = config_with(bad).unwrap().validate().unwrap_err();
+            assert_eq!(problems.len(), 1, "{bad}");
+            assert!(problems[0].starts_with("[profile.make] 'map_uid' must be"), "{bad}");
+        }
+        assert!(config_with("-1").is_err());
+    }
+
+    /// Assert that aliases of aliases resolve to the end of the chain, that cycles and list
+    /// entries shadowed by an alias are rejected, and that the fan-in includes indirect aliases
+    #[test]
+    fn subcommand_aliases_transitive() {
+        let config_with = |aliases: &str, extra: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                 subcommand_aliases = {{ {aliases} }}\n{extra}"
+            ))
+            .unwrap()
+        };
+        let cargo = CommandName::try_from("cargo".to_owned()).unwrap();
+        let name = |x: &str| SubcommandName::try_from(x.to_owned()).unwrap();
+
+        let config = config_with("b = \"bld\", bld = \"build\", c = \"check\"", "");
+        config.validate().unwrap();
+        let profile = config.profile(&cargo).unwrap();
+        assert_eq!(profile.canonical_subcommand(name("b")), name("build"));
+        assert_eq!(profile.canonical_subcommand(name("bld")), name("build"));
+        assert_eq!(profile.canonical_subcommand(name("build")), name("build"));
+        assert_eq!(profile.aliases_of(&name("build")), [&name("b"), &name("bld")]);
+        assert_eq!(profile.aliases_of(&name("check")), [&name("c")]);
+        assert!(profile.aliases_of(&name("test")).is_empty());
+
+        for cycle in ["a = \"a\"", "a = \"b\", b = \"a\"", "a = \"b\", b = \"c\", c = \"b\""] {
+            let problems = config_with(cycle, "").validate().unwrap_err();
+            assert!(problems.iter().all(|x| x.contains("leads into a cycle")), "{cycle}");
+        }
+
+        let problems =
+            config_with("b = \"build\"", "deny_subcommands = [\"b\"]").validate().unwrap_err();
+        assert_eq!(
+            problems,
+            ["[profile.cargo] 'deny_subcommands' entry \"b\" will never apply because \
+              'subcommand_aliases' resolves it to \"build\" first"]
+        );
+    }
+
+    /// Assert that `no_root_base` must be absolute and a subcommand can't be in both
+    /// `no_root_subcommands` and `projectless_subcommands`
+    #[test]
+    fn no_root_validation() {
+        let config_with = |extra: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                 no_root_subcommands = [\"install\"]\n{extra}"
+            ))
+            .unwrap()
+        };
+        config_with("").validate().unwrap();
+        config_with("no_root_base = \"/opt/cargo\"").validate().unwrap();
+        for bad in ["no_root_base = \"cargo\"", "no_root_base = \"/opt/../home\""] {
+            let problems = config_with(bad).validate().unwrap_err();
+            assert!(problems[0].contains("'no_root_base' must be an absolute path"), "{bad}");
+        }
+        let problems =
+            config_with("projectless_subcommands = [\"install\"]").validate().unwrap_err();
+        assert_eq!(
+            problems,
+            ["[profile.cargo] \"install\" can't be in both 'no_root_subcommands' and \
+              'projectless_subcommands'"]
+        );
+    }
+
+    /// Assert that `private_bin` entries must be plain names usable in a comma-separated list
+    /// and must include what the profile's command-line wrappers run
+    #[test]
+    fn private_bin_validation() {
+        let config_with = |private_bin: &str, extra: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                 private_bin = {private_bin}\n{extra}"
+            ))
+        };
+        config_with("[\"cargo\", \"rustc\"]", "").unwrap().validate().unwrap();
+        config_with("[]", "umask = \"0077\"").unwrap().validate().unwrap();
+        config_with("[\"cargo\", \"sh\"]", "umask = \"0077\"").unwrap().validate().unwrap();
+
+        // Separators and whitespace are refused when parsing, like any other `FileName`
+        for bad in ["\"/usr/bin/cargo\"", "\"cargo build\"", "\"\""] {
+            assert!(config_with(&format!("[{bad}]"), "").is_err(), "{bad}");
+        }
+        for bad in ["\"..\"", "\"cargo,rustc\""] {
+            let problems = config_with(&format!("[{bad}]"), "").unwrap().validate().unwrap_err();
+            assert_eq!(
+                problems,
+                [format!(
+                    "[profile.cargo] 'private_bin' entries must be plain program names, not {bad}"
+                )]
+            );
+        }
+
+        let problems = config_with("[\"cargo\"]", "pre_exec = [\"true\"]\nmap_uid = 1500")
+            .unwrap()
+            .validate()
+            .unwrap_err();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("must include \"sh\" when 'umask' or 'pre_exec' is set"));
+        assert!(problems[1].contains("must include \"unshare\" when 'map_uid' is set"));
+    }
+
+    /// Assert that `success_exit_codes` only remaps the listed codes and rejects impossible ones
+    #[test]
+    fn success_exit_codes_remapped() {
+        let config_with = |codes: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.grep]\nroot_marked_by = [\"Makefile\"]\nsuccess_exit_codes = {codes}"
+            ))
+            .unwrap()
+        };
+        let config = config_with("[1]");
+        config.validate().unwrap();
+        let profile = config.profile(&CommandName::try_from("grep".to_owned()).unwrap()).unwrap();
+        assert_eq!(profile.remap_exit_code(0), 0);
+        assert_eq!(profile.remap_exit_code(1), 0);
+        assert_eq!(profile.remap_exit_code(2), 2);
+
+        for bad in ["0", "-1", "256"] {
+            let problems = config_with(&format!("[{bad}]")).validate().unwrap_err();
+            assert_eq!(problems.len(), 1, "{bad}");
+            assert!(problems[0].contains("'success_exit_codes' entries must be"), "{bad}");
+        }
+    }
+
+    /// Assert that `unknown_subcommand = "passthrough"` is parsed but rejected by validation
+    #[test]
+    fn unknown_subcommand_passthrough_rejected() {
+        let config_with = |policy: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.make]\nroot_marked_by = [\"Makefile\"]\nunknown_subcommand = {policy:?}"
+            ))
+            .unwrap()
+        };
+        config_with("deny").validate().unwrap();
+        config_with("sandbox").validate().unwrap();
+        let problems = config_with("passthrough").validate().unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("[profile.make] sets 'unknown_subcommand'"));
+        assert!(parse("[profile.make]\nunknown_subcommand = \"run\"").is_err());
+    }
+
+    /// Assert that `allow_network_subcommands` is warned about only when `allow_network` makes it
+    /// redundant, and that this doesn't make the configuration invalid
+    #[test]
+    fn redundant_network_subcommands_warned() {
+        let config = |fields: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n[profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                 {fields}"
+            ))
+            .unwrap()
+        };
+        let redundant = config("allow_network = true\nallow_network_subcommands = [\"build\"]");
+        redundant.validate().unwrap();
+        let warnings = redundant.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with(
+            "[profile.cargo] 'allow_network_subcommands' has no effect because 'allow_network'"
+        ));
+
+        for fields in [
+            "allow_network = true",
+            "allow_network_subcommands = [\"build\"]",
+            "allow_network = false\nallow_network_subcommands = [\"build\"]",
+        ] {
+            assert!(config(fields).warnings().is_empty(), "{fields}");
+        }
+    }
+
+    /// Assert that a profile named after one of `nodo`'s own flags is warned about
+    #[test]
+    fn profile_named_after_flag_warned() {
+        let config = parse(
+            "firejail_base_flags = []\n\
+             [profile.\"--debug\"]\nroot_marked_by = [\"Makefile\"]\n\
+             [profile.\"--json\"]\nroot_marked_by = [\"Makefile\"]\n\
+             [profile.make]\nroot_marked_by = [\"Makefile\"]",
+        )
+        .unwrap();
+        config.validate().unwrap();
+        let warnings = config.warnings();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("[profile.--debug] can only be used as `nodo -- --debug`"));
+    }
+
+    /// Assert that `root_blacklist` can't name the root itself or its parent at either level
+    #[test]
+    fn dot_root_blacklist_rejected() {
+        let config = |global: &str, profile: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\nroot_blacklist = [{global}]\n\
+                 [profile.npm]\nroot_marked_by = [\"package.json\"]\nroot_blacklist = [{profile}]"
+            ))
+            .unwrap()
+            .validate()
+        };
+        config("\".git\"", "\".env\"").unwrap();
+        for dot in ["\".\"", "\"..\""] {
+            assert_eq!(config(dot, "").unwrap_err().len(), 1, "{dot}");
+            let problems = config("", dot).unwrap_err();
+            assert_eq!(problems.len(), 1, "{dot}");
+            assert!(problems[0].starts_with("[profile.npm] 'root_blacklist' must"), "{dot}");
+        }
+    }
+
+    /// Assert that `root_marked_by_all` can stand in for `root_marked_by` but is held to the
+    /// same rules about `.` and `..`
+    #[test]
+    fn root_marked_by_all_validated() {
+        let config = |markers: &str| {
+            parse(&format!("firejail_base_flags = []\n[profile.cargo]\n{markers}"))
+                .unwrap()
+                .validate()
+        };
+        config("root_marked_by = []\nroot_marked_by_all = [\"Cargo.toml\", \".git\"]").unwrap();
+        assert_eq!(config("root_marked_by = []").unwrap_err().len(), 1);
+        let problems = config("root_marked_by = []\nroot_marked_by_all = [\"Cargo.toml\", \"..\"]")
+            .unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("[profile.cargo] 'root_marked_by_all' must not contain"));
+    }
+
+    /// Assert that `lint` only flags network access granted without an adjacent comment
+    #[test]
+    fn lint_wants_network_comments() {
+        let profile = |body: &str| {
+            lint(&format!("firejail_base_flags = []\n\n[profile.tool]\n{body}\n")).unwrap()
+        };
+        let undocumented = |field: &str| {
+            vec![format!(
+                "[profile.tool] '{field}' grants network access without a comment explaining why"
+            )]
+        };
+
+        assert_eq!(profile("allow_network = true"), undocumented("allow_network"));
+        assert_eq!(profile("allow_network = \"all_networks\""), undocumented("allow_network"));
+        assert_eq!(
+            profile("allow_network_subcommands = [\"fetch\"]"),
+            undocumented("allow_network_subcommands")
+        );
+
+        for documented in [
+            "# Downloads dependencies\nallow_network = true",
+            "allow_network = true  # Downloads dependencies",
+            "allow_network_subcommands = [\"fetch\"]  # Downloads dependencies",
+            "allow_network_subcommands = [\n    \"fetch\",  # Downloads dependencies\n]",
+            "allow_network = false",
+            "allow_network_subcommands = []",
+        ] {
+            assert_eq!(profile(documented), Vec::<String>::new(), "{documented}");
+        }
+
+        // A string containing `#` isn't a comment
+        assert_eq!(
+            profile("allow_network_subcommands = [\"#fetch\"]"),
+            undocumented("allow_network_subcommands")
+        );
+
+        // ...and the defaults should set a good example
+        assert_eq!(lint(DEFAULT_CONFIG).unwrap(), Vec::<String>::new());
+    }
+
+    /// Assert that `.` and `..` are rejected as `root_marked_by` entries
+    #[test]
+    fn dot_markers_rejected() {
+        let config = |marker: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.make]\n\
+                 root_marked_by = [\"Makefile\", {marker:?}]"
+            ))
+            .unwrap()
+        };
+        config(".git").validate().unwrap();
+        config("...").validate().unwrap();
+        for bad in [".", ".."] {
+            assert_eq!(
+                config(bad).validate(),
+                Err(vec![format!(
+                    "[profile.make] 'root_marked_by' must not contain {bad:?}, which is present \
+                     in every directory and can't mark a project root"
+                )])
+            );
+        }
+    }
+
+    /// Assert that `*` in `command_matches` patterns matches any run of characters and nothing
+    /// else is special
+    #[test]
+    fn glob_matches_wildcards() {
+        assert!(glob_matches("cargo-*", "cargo-nextest"));
+        assert!(glob_matches("cargo-*", "cargo-"));
+        assert!(!glob_matches("cargo-*", "cargo"));
+        assert!(!glob_matches("cargo-*", "xcargo-nextest"));
+        assert!(glob_matches("*-config", "pkg-config"));
+        assert!(glob_matches("py*-*", "python3-pip"));
+        assert!(!glob_matches("py*-*", "python3"));
+        assert!(glob_matches("a*a", "aa"));
+        assert!(!glob_matches("a*a", "a"));
+        assert!(glob_matches("make", "make"));
+        assert!(!glob_matches("make", "cmake"));
+        assert!(!glob_matches("m?ke", "make"));
+    }
+
+    /// Assert that `command_matches` patterns which could never be intended are rejected
+    #[test]
+    fn command_matches_validated() {
+        let config = |pattern: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\n\
+                 root_marked_by = [\"Cargo.toml\"]\n\
+                 command_matches = [{pattern:?}]"
+            ))
+            .unwrap()
+        };
+        config("cargo-*").validate().unwrap();
+        for bad in ["", "**", "/usr/bin/cargo-*"] {
+            assert_eq!(
+                config(bad).validate(),
+                Err(vec![format!(
+                    "[profile.cargo] 'command_matches' entries must be non-empty, may not \
+                     contain '/', and must contain more than '*': {bad:?}"
+                )]),
+                "{bad:?}"
+            );
+        }
+    }
+
+    /// Assert that `command_path_prefix` must be an absolute path which can't climb out of itself
+    #[test]
+    fn command_path_prefix_validated() {
+        let config = |prefix: &str| {
+            parse(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.make]\n\
+                 root_marked_by = [\"Makefile\"]\n\
+                 command_path_prefix = {prefix:?}"
+            ))
+            .unwrap()
+        };
+        config("/usr/bin").validate().unwrap();
+        for bad in ["usr/bin", "", "/usr/bin/../../home"] {
+            assert_eq!(
+                config(bad).validate(),
+                Err(vec![format!(
+                    "[profile.make] 'command_path_prefix' must be an absolute path without '..' \
+                     components: {bad}"
+                )]),
+                "{bad:?}"
+            );
+        }
+    }
+
+    /// Assert that neither the global base flags nor a profile's override can grant network
+    /// access contrary to the `allow_network` capability
+    #[test]
+    fn base_flags_cannot_grant_network() {
+        let config = |base: &str, replacement: &str| {
+            parse(&format!(
+                "firejail_base_flags = {base}\n\
+                 [profile.make]\n\
+                 root_marked_by = [\"Makefile\"]\n\
+                 base_flags_override = {replacement}"
+            ))
+            .unwrap()
+        };
+
+        config("[\"--seccomp\", \"--net=none\"]", "[\"--net=none\", \"--nonewprivs\"]")
+            .validate()
+            .unwrap();
+        assert_eq!(
+            config("[\"--net=eth0\"]", "[]").validate(),
+            Err(vec!["'firejail_base_flags' must not grant network access (use allow_network \
+                 instead): --net=eth0"
+                .to_owned()])
+        );
+        assert_eq!(
+            config("[]", "[\"--seccomp\", \"--netns=host\", \"--interface=eth1\"]").validate(),
+            Err(vec![
+                "[profile.make] 'base_flags_override' must not grant network access (use \
+                 allow_network instead): --netns=host"
+                    .to_owned(),
+                "[profile.make] 'base_flags_override' must not grant network access (use \
+                 allow_network instead): --interface=eth1"
+                    .to_owned(),
+            ])
+        );
+    }
+
+    /// Assert that the rest of the [`DANGEROUS_FLAGS`] are rejected in either place, with or
+    /// without a value, and that harmless options sharing a prefix with them aren't
+    #[test]
+    fn base_flags_cannot_undermine_sandbox() {
+        let config = |base: &str, replacement: &str| {
+            parse(&format!(
+                "firejail_base_flags = {base}\n\
+                 [profile.make]\n\
+                 root_marked_by = [\"Makefile\"]\n\
+                 base_flags_override = {replacement}"
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(
+            config("[\"--seccomp\", \"--noblacklist=/home/user/.config/nodo.toml\"]", "[]")
+                .validate(),
+            Err(vec!["'firejail_base_flags' must not un-blacklist paths, such as the \
+                      configuration file: --noblacklist=/home/user/.config/nodo.toml"
+                .to_owned()])
+        );
+        assert_eq!(
+            config("[]", "[\"--noblacklist=${HOME}\"]").validate(),
+            Err(vec!["[profile.make] 'base_flags_override' must not un-blacklist paths, such as \
+                      the configuration file: --noblacklist=${HOME}"
+                .to_owned()])
+        );
+        for flag in [
+            "--allow-debuggers",
+            "--caps.keep=sys_admin",
+            "--ignore=seccomp",
+            "--noprofile",
+            "--protocol=unix,inet",
+            "--read-write=/usr",
+            "--whitelist=/home/user/.config/nodo.toml",
+        ] {
+            let problems = config(&format!("[{flag:?}]"), "[]").validate().unwrap_err();
+            assert_eq!(problems.len(), 1, "{flag}");
+            assert!(problems[0].ends_with(&format!(": {flag}")), "{flag}");
+        }
+        config("[\"--caps.drop=all\", \"--nonewprivs\", \"--blacklist=/srv\"]", "[]")
+            .validate()
+            .unwrap();
+    }
+
+    /// Assert that profile names differing only by case are rejected by `.validate()`
+    #[test]
+    fn case_variant_profiles_rejected() {
+        let config = parse(
+            "firejail_base_flags=[]\n\
+             [profile.Make]\nroot_marked_by=[\"Makefile\"]\n\
+             [profile.make]\nroot_marked_by=[\"Makefile\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            config.validate(),
+            Err(vec!["Profile names must not differ only by case: Make, make".to_owned()])
+        );
+    }
+
+    /// Assert that `merge_defaults` adds missing top-level keys, with their explanatory comments,
+    /// without disturbing the user's comments, values, or profiles
+    #[test]
+    fn merge_defaults_preserves_user_file() {
+        let existing = "# My settings\n\
+                        firejail_base_flags = [\"--seccomp\"]  # keep it short\n\
+                        \n\
+                        [profile.make]\n\
+                        root_marked_by = [\"Makefile\"]  # not GNUmakefile\n";
+        let merged = merge_defaults(existing).unwrap();
+
+        assert!(merged.starts_with("# My settings\n"), "{merged}");
+        assert!(merged.contains("firejail_base_flags = [\"--seccomp\"]  # keep it short\n"));
+        assert!(merged.contains("root_marked_by = [\"Makefile\"]  # not GNUmakefile\n"));
+        assert!(merged.contains("# Reject unrecognized keys"), "{merged}");
+
+        let config = parse(&merged).unwrap();
+        assert!(config.strict);
+        assert_eq!(config.firejail_base_flags, ["--seccomp"]);
+        assert_eq!(config.root_blacklist.len(), 4);
+        assert_eq!(config.profiles.keys().map(ToString::to_string).collect::<Vec<_>>(), ["make"]);
+
+        // Merging is idempotent
+        assert_eq!(merge_defaults(&merged).unwrap(), merged);
+    }
+
+    /// Assert that the `[[profile]]` form produces the same profiles as the canonical form
+    #[test]
+    fn profile_array_of_tables() {
+        let canonical = parse(
+            "strict = true\nfirejail_base_flags=[]\n\
+             [profile.cargo]\nroot_marked_by=[\"Cargo.toml\"]\nallow_network_subcommands=[\"b\"]\n\
+             [profile.make]\nroot_marked_by=[\"Makefile\"]\ntimeout=\"00:10:00\"",
+        )
+        .unwrap();
+        let array = parse(
+            "strict = true\nfirejail_base_flags=[]\n\
+             [[profile]]\ncommand=\"make\"\nroot_marked_by=[\"Makefile\"]\ntimeout=\"00:10:00\"\n\
+             [[profile]]\ncommand=\"cargo\"\nroot_marked_by=[\"Cargo.toml\"]\n\
+             allow_network_subcommands=[\"b\"]",
+        )
+        .unwrap();
+        assert_eq!(canonical.profiles, array.profiles);
+        assert_eq!(array.profiles.len(), 2);
+
+        // Errors inside profiles and duplicates must still be reported
+        let err =
+            parse("firejail_base_flags=[]\n[[profile]]\ncommand=\"make\"\nroot_marked_by=[\"\"]")
+                .unwrap_err();
+        assert!(err.to_string().contains("empty string"), "{err}");
+        let err = parse(
+            "firejail_base_flags=[]\n\
+             [[profile]]\ncommand=\"make\"\nroot_marked_by=[\"Makefile\"]\n\
+             [[profile]]\ncommand=\"make\"\nroot_marked_by=[\"GNUmakefile\"]",
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("duplicate profile for make"), "{err}");
+
+        // ...as must typos in strict mode
+        let err = parse(
+            "strict = true\nfirejail_base_flags=[]\n\
+             [[profile]]\ncommand=\"make\"\nroot_marked_by=[\"Makefile\"]\nallow_netwrk=true",
+        )
+        .unwrap_err();
+        assert!(err.to_string().ends_with(": profile.0.allow_netwrk"), "{err}");
     }
 
     /// Assert that the Serde-level defaults for the top-level config, before `.validate()` is run,
@@ -236,6 +2825,45 @@ mod test {
         let config: Config = toml_from_str("firejail_base_flags = []\nprofile = {}").unwrap();
         assert!(config.profiles.is_empty());
         assert!(config.root_blacklist.is_empty());
+        assert!(!config.strict);
+        assert_eq!(config.allowed_commands, None);
+        assert!(config.forbidden_roots.is_empty());
+        assert_eq!(config.blacklist_mode, BlacklistMode::Hide);
+        assert!(!config.allow_nesting);
+        assert!(!config.backend_verbose);
+        assert_eq!(config.backend_path, None);
+        assert_eq!(config.max_args, None);
+        assert_eq!(config.pre_launch, None);
+        assert!(!config.match_by_binary);
+        assert_eq!(config.kill_grace(), Duration::from_secs(5));
+        assert!(config.fail_closed);
+        assert_eq!(
+            config.system_readonly,
+            [Path::new("/usr"), Path::new("/etc"), Path::new("/bin")]
+        );
+    }
+
+    /// Assert that the bundled `defaults.toml` passes the same checks user configs must and
+    /// doesn't ship anything looser than intended
+    #[test]
+    fn default_config_is_sane() {
+        let config = parse(DEFAULT_CONFIG).unwrap();
+        config.validate().unwrap();
+        assert!(!config.profiles.is_empty());
+        assert_eq!(config.allowed_commands, None);
+
+        for (command, profile) in &config.profiles {
+            // Network access is only ever granted per-subcommand
+            assert_eq!(profile.allow_network, caps::Network::ChildProcsOnly, "{command}");
+            assert_eq!(profile.network_interface, None, "{command}");
+            assert!(!profile.root_marked_by.is_empty(), "{command}");
+
+            // `Outermost` is an opt-in for tools known to nest projects (Cargo workspaces,
+            // recursive Make) and anything new should start out `Innermost`.
+            if profile.root_find_outermost == caps::ProjectRoot::Outermost {
+                assert!(["cargo", "make"].contains(&command.to_string().as_str()), "{command}");
+            }
+        }
     }
 
     // TODO: test the validate() methods and ensure they cannot be refactored to `&mut self`
@@ -0,0 +1,180 @@
+//! Errors which prevent a command from being run in a sandbox
+
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::types::{CommandName, SubcommandName};
+
+/// The reasons [`Config::resolve`](crate::config::Config::resolve) and the launch path built on it
+/// may refuse to proceed
+#[derive(Debug, Eq, PartialEq)]
+pub enum Error {
+    /// The child command line was empty
+    NoCommand,
+    /// A profile name couldn't be derived from `argv[0]` (eg. because it's empty, only path
+    /// separators, or its basename isn't a valid [`CommandName`])
+    BadCommandName(OsString),
+    /// No profile is configured for the command
+    NoProfile(CommandName),
+    /// No profile is named after the command and the `command_matches` of several match it
+    AmbiguousProfile(CommandName, Vec<CommandName>),
+    /// The command line has more arguments than `max_args` permits (given, limit)
+    TooManyArgs(usize, usize),
+    /// The profile's `command_path_prefix` is set and `argv[0]` doesn't resolve to a program
+    /// inside it (profile, `argv[0]`, prefix)
+    WrongCommandPath(CommandName, OsString, PathBuf),
+    /// `allowed_commands` is set and doesn't include the command
+    NotAllowed(CommandName),
+    /// None of the profile's `root_marked_by` entries exist in the working directory's ancestors
+    NoProjectRoot(CommandName, PathBuf),
+    /// The discovered project root is `/`, the home directory, or listed in `forbidden_roots`
+    RootTooBroad(CommandName, PathBuf),
+    /// The chosen project root doesn't contain the working directory once symlinks are resolved
+    RootOutsideCwd(CommandName, PathBuf, PathBuf),
+    /// The project root was replaced (eg. with a symlink) between being chosen and the sandbox
+    /// being launched
+    RootChanged(CommandName, PathBuf),
+    /// The root given with `--root` isn't an existing directory
+    NoSuchRoot(PathBuf),
+    /// The `no_root_base` (or home directory) used for a `no_root_subcommands` entry isn't an
+    /// existing directory
+    NoSuchNoRootBase(CommandName, PathBuf),
+    /// `nodo` was run from inside a `nodo` sandbox and `allow_nesting` isn't set
+    Nested,
+    /// The subcommand is listed in `deny_subcommands`
+    Denied(CommandName, SubcommandName),
+    /// The subcommand isn't mentioned by the profile and its `unknown_subcommand` is `"deny"`
+    UnknownSubcommand(CommandName, SubcommandName),
+    /// A `--stdout` or `--stderr` path is somewhere the sandbox couldn't write to itself (the
+    /// flag, the path)
+    BadRedirect(&'static str, PathBuf),
+    /// A configured path couldn't be parsed for expansion
+    BadPath(String, &'static str),
+    /// A configured path references an undefined or empty environment variable
+    UndefinedVariable(String, String),
+    /// A configured path wasn't absolute after expansion
+    NotAbsolute(String, PathBuf),
+    /// A `whitelist` entry and a `blacklist` or `root_blacklist` entry name the same path, or one
+    /// is inside the other, after expansion (whitelist entry, blacklist entry, the overlap)
+    Contradiction(String, String, PathBuf),
+}
+
+impl std::error::Error for Error {}
+
+impl fmt::Display for Error {
+    #[allow(clippy::too_many_lines)] // One arm per variant, which splitting would only obscure
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::NoCommand => write!(f, "No command specified"),
+            Self::BadCommandName(ref argv0) => {
+                // Quoted so an empty `argv[0]` doesn't produce a message trailing off into nothing
+                write!(f, "Could not determine a command name from \"{}\"", argv0.display())
+            },
+            Self::NoProfile(ref command) => write!(f, "No profile configured for {command}"),
+            Self::TooManyArgs(given, limit) => write!(
+                f,
+                "Refusing to run a command line of {given} arguments because max_args in the \
+                 configuration file is {limit}"
+            ),
+            Self::AmbiguousProfile(ref command, ref candidates) => {
+                let names: Vec<String> = candidates.iter().map(ToString::to_string).collect();
+                write!(
+                    f,
+                    "More than one profile's command_matches matches {command} ({}). Add a \
+                     [profile.{command}] section or narrow the patterns.",
+                    names.join(", ")
+                )
+            },
+            Self::WrongCommandPath(ref command, ref argv0, ref prefix) => write!(
+                f,
+                "Refusing to use the {command} profile for \"{}\" because its \
+                 command_path_prefix only matches programs in {}",
+                argv0.display(),
+                prefix.display()
+            ),
+            Self::NotAllowed(ref command) => write!(
+                f,
+                "Refusing to run {command} because it isn't listed in allowed_commands in the \
+                 configuration file"
+            ),
+            Self::NoProjectRoot(ref command, ref cwd) => write!(
+                f,
+                "Could not find a project root for {command} in any ancestor of {}",
+                cwd.display()
+            ),
+            Self::RootTooBroad(ref command, ref root) => write!(
+                f,
+                "Refusing to use {} as the project root for {command}. It is the filesystem \
+                 root, your home directory, or listed in forbidden_roots, so the sandbox would \
+                 expose far more than one project. Check for a stray root_marked_by file there.",
+                root.display()
+            ),
+            Self::RootOutsideCwd(ref command, ref root, ref cwd) => write!(
+                f,
+                "The project root {} chosen for {command} does not contain the current directory \
+                 {} once symlinks are resolved. This usually means the current directory was \
+                 reached through a symlink into an unrelated tree or a root_marked_by entry (or \
+                 --root) is pointing somewhere it shouldn't.",
+                root.display(),
+                cwd.display()
+            ),
+            Self::RootChanged(ref command, ref root) => write!(
+                f,
+                "Refusing to launch {command} because the project root {} was moved, replaced, or \
+                 became unreadable after it was chosen",
+                root.display()
+            ),
+            Self::NoSuchRoot(ref root) => {
+                write!(f, "The --root {} is not an existing directory", root.display())
+            },
+            Self::NoSuchNoRootBase(ref command, ref base) => write!(
+                f,
+                "The sandbox root {} for {command}'s no_root_subcommands is not an existing \
+                 directory. Create it or change the profile's no_root_base.",
+                base.display()
+            ),
+            Self::Nested => write!(
+                f,
+                "Refusing to create a sandbox inside another {0} sandbox ({1} is set). Run the \
+                 command directly or set allow_nesting = true to have nested {0} invocations do \
+                 so automatically.",
+                env!("CARGO_PKG_NAME"),
+                crate::sandbox::ACTIVE_ENV_VAR
+            ),
+            Self::Denied(ref command, ref subcommand) => write!(
+                f,
+                "Refusing to sandbox '{command} {subcommand}'. It must be run outside {} to \
+                 make your intent explicit.",
+                env!("CARGO_PKG_NAME")
+            ),
+            Self::UnknownSubcommand(ref command, ref subcommand) => write!(
+                f,
+                "Refusing to run '{command} {subcommand}' because the {command} profile doesn't \
+                 mention that subcommand and its unknown_subcommand is \"deny\""
+            ),
+            Self::BadPath(ref raw, reason) => write!(f, "Could not expand path {raw:?}: {reason}"),
+            Self::UndefinedVariable(ref raw, ref name) => {
+                write!(f, "Could not expand path {raw:?}: environment variable {name} is not set")
+            },
+            Self::NotAbsolute(ref raw, ref path) => write!(
+                f,
+                "Path {raw:?} must be absolute after expansion but was {}",
+                path.display()
+            ),
+            Self::BadRedirect(flag, ref path) => write!(
+                f,
+                "Refusing to redirect to {} with {flag} because it isn't a writable file in the \
+                 project root or a whitelisted location, so the sandbox couldn't write there \
+                 itself",
+                path.display()
+            ),
+            Self::Contradiction(ref whitelisted, ref blacklisted, ref path) => write!(
+                f,
+                "whitelist entry {whitelisted:?} and blacklist entry {blacklisted:?} overlap at {}, \
+                 which leaves what Firejail will do undefined",
+                path.display()
+            ),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Expansion of `~` and environment variables in configured paths
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::path::PathBuf;
+
+use crate::error::Error;
+
+/// A snapshot of the environment variables available to [`expand_path`]
+///
+/// (Taken as a parameter rather than read from the process environment so expansion can be
+/// tested without mutating global state.)
+#[derive(Debug, Default)]
+pub struct Env(HashMap<OsString, OsString>);
+
+impl Env {
+    /// Capture the environment of the current process
+    #[must_use]
+    pub fn current() -> Self {
+        env::vars_os().collect()
+    }
+
+    /// Look up a variable, treating empty values as undefined
+    ///
+    /// (An empty `$HOME` expanding `~/.cargo` to `/.cargo` is exactly the sort of surprise
+    /// refusing undefined variables is meant to prevent.)
+    pub(crate) fn get(&self, name: &str) -> Option<&OsStr> {
+        self.0.get(OsStr::new(name)).map(OsString::as_os_str).filter(|x| !x.is_empty())
+    }
+}
+
+impl<K: Into<OsString>, V: Into<OsString>> FromIterator<(K, V)> for Env {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Self(iter.into_iter().map(|(key, value)| (key.into(), value.into())).collect())
+    }
+}
+
+/// Expand a leading `~` and any `$VAR` or `${VAR}` references in a configured path
+///
+/// Undefined (or empty) variables are an error rather than expanding to an empty string, since
+/// silently turning `$CARGO_HOME/registry` into `/registry` would change which path a security
+/// policy applies to. For the same reason, results which aren't absolute are rejected.
+///
+/// `~user` isn't supported and there is no escape for a literal `$`.
+///
+/// # Errors
+///
+/// See above, plus [`Error::BadPath`] for unsupported syntax.
+pub fn expand_path(raw: &str, env: &Env) -> Result<PathBuf, Error> {
+    let syntax_error = |reason| Error::BadPath(raw.to_owned(), reason);
+    let mut expanded = OsString::new();
+
+    let mut rest = raw;
+    if let Some(after_tilde) = raw.strip_prefix('~') {
+        if !(after_tilde.is_empty() || after_tilde.starts_with('/')) {
+            return Err(syntax_error("`~user` is not supported"));
+        }
+        let home = env
+            .get("HOME")
+            .ok_or_else(|| Error::UndefinedVariable(raw.to_owned(), "HOME".to_owned()))?;
+        expanded.push(home);
+        rest = after_tilde;
+    }
+
+    while let Some((literal, after_dollar)) = rest.split_once('$') {
+        expanded.push(literal);
+        let (name, after_name) = if let Some(braced) = after_dollar.strip_prefix('{') {
+            let (name, after) =
+                braced.split_once('}').ok_or_else(|| syntax_error("unclosed `${`"))?;
+            (name, after)
+        } else {
+            let end = after_dollar
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(after_dollar.len());
+            after_dollar.split_at(end)
+        };
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(syntax_error("`$` must be followed by a variable name"));
+        }
+        let value = env
+            .get(name)
+            .ok_or_else(|| Error::UndefinedVariable(raw.to_owned(), name.to_owned()))?;
+        expanded.push(value);
+        rest = after_name;
+    }
+    expanded.push(rest);
+
+    let path = PathBuf::from(expanded);
+    if path.is_absolute() {
+        Ok(path)
+    } else {
+        Err(Error::NotAbsolute(raw.to_owned(), path))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A fixed environment for the tests below
+    fn test_env() -> Env {
+        [("HOME", "/home/user"), ("CARGO_HOME", "/opt/cargo"), ("EMPTY", "")].into_iter().collect()
+    }
+
+    /// Assert that a leading `~` expands to `$HOME` and nothing else is mistaken for one
+    #[test]
+    fn expand_path_tilde() {
+        let env = test_env();
+        assert_eq!(expand_path("~", &env), Ok(PathBuf::from("/home/user")));
+        assert_eq!(expand_path("~/.cargo", &env), Ok(PathBuf::from("/home/user/.cargo")));
+        assert_eq!(expand_path("/srv/~/x", &env), Ok(PathBuf::from("/srv/~/x")));
+        assert_eq!(
+            expand_path("~root/.cargo", &env),
+            Err(Error::BadPath("~root/.cargo".to_owned(), "`~user` is not supported"))
+        );
+        assert_eq!(
+            expand_path("~/.cargo", &Env::default()),
+            Err(Error::UndefinedVariable("~/.cargo".to_owned(), "HOME".to_owned()))
+        );
+    }
+
+    /// Assert that both variable syntaxes expand to defined values
+    #[test]
+    fn expand_path_defined_variable() {
+        let env = test_env();
+        assert_eq!(expand_path("$CARGO_HOME", &env), Ok(PathBuf::from("/opt/cargo")));
+        assert_eq!(
+            expand_path("${CARGO_HOME}/registry", &env),
+            Ok(PathBuf::from("/opt/cargo/registry"))
+        );
+        assert_eq!(
+            expand_path("$HOME/x-${CARGO_HOME}", &env),
+            Ok(PathBuf::from("/home/user/x-/opt/cargo"))
+        );
+    }
+
+    /// Assert that undefined or empty variables, bad syntax, and relative results are errors
+    /// rather than being silently expanded to something else
+    #[test]
+    fn expand_path_errors() {
+        let env = test_env();
+        assert_eq!(
+            expand_path("${UNSET}/registry", &env),
+            Err(Error::UndefinedVariable("${UNSET}/registry".to_owned(), "UNSET".to_owned()))
+        );
+        assert_eq!(
+            expand_path("$EMPTY/registry", &env),
+            Err(Error::UndefinedVariable("$EMPTY/registry".to_owned(), "EMPTY".to_owned()))
+        );
+        assert_eq!(
+            expand_path("${HOME", &env),
+            Err(Error::BadPath("${HOME".to_owned(), "unclosed `${`"))
+        );
+        assert_eq!(
+            expand_path("/srv/$/x", &env),
+            Err(Error::BadPath("/srv/$/x".to_owned(), "`$` must be followed by a variable name"))
+        );
+        assert_eq!(
+            expand_path(".cargo", &env),
+            Err(Error::NotAbsolute(".cargo".to_owned(), PathBuf::from(".cargo")))
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! `--explain` output describing what would be done with a command line without running it,
+//! along with the other listings of what the configuration would do (`--explain-all` and
+//! `--list-profiles`)
+
+use std::ffi::OsString;
+use std::fmt::Write;
+
+use serde_derive::Serialize;
+
+use crate::config::{Config, NetworkSource};
+use crate::resolve::Resolution;
+use crate::types::{caps, CommandName, RootMarker, SubcommandName};
+
+/// Output formats supported by `--explain`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// Aligned `key: value` lines meant for humans
+    Human,
+    /// A single JSON object meant for editor plugins, CI gates, and other tooling
+    Json,
+    /// The Firejail options that would be used, as the directives of a Firejail `.profile` file
+    ///
+    /// This needs the Firejail command line, which [`render`] doesn't have, so callers produce it
+    /// with [`crate::sandbox::firejail_syntax`] instead.
+    FirejailSyntax,
+}
+
+/// Render `resolution` in the requested format, ready to be printed to stdout
+///
+/// [`Format::FirejailSyntax`] falls back to [`Format::Human`]. (See its documentation.)
+///
+/// # Errors
+///
+/// Only fails if [`Format::Json`] serialization does.
+pub fn render(resolution: &Resolution<'_>, format: Format) -> Result<String, serde_json::Error> {
+    match format {
+        Format::Human | Format::FirejailSyntax => Ok(render_human(resolution)),
+        Format::Json => serde_json::to_string(resolution).map(|json| json + "\n"),
+    }
+}
+
+/// Describe the effective network access and which profile rule produced it
+///
+/// This is shared with the `--debug` diagnostics so "why can't my build reach the network?" gets
+/// the same answer both ways.
+#[must_use]
+pub fn network_summary(resolution: &Resolution<'_>) -> String {
+    let provenance = match (resolution.network_source, resolution.subcommand.as_ref()) {
+        (NetworkSource::AllowNetworkSubcommands, Some(subcommand)) => {
+            format!("allow_network_subcommands entry \"{subcommand}\"")
+        },
+        _ => "profile-wide allow_network default".to_owned(),
+    };
+    format!("{} (from {provenance})", resolution.network.description())
+}
+
+/// Render `resolution` as aligned `key: value` lines
+fn render_human(resolution: &Resolution<'_>) -> String {
+    // `fmt::Write` for `String` is infallible, so the results are safe to ignore
+    let mut out = String::new();
+    let _ = writeln!(out, "profile:      {}", resolution.command);
+    match resolution.subcommand {
+        Some(ref subcommand) => {
+            let _ = writeln!(out, "subcommand:   {subcommand}");
+            let aliases: Vec<String> =
+                resolution.profile.aliases_of(subcommand).iter().map(ToString::to_string).collect();
+            if !aliases.is_empty() {
+                let _ = writeln!(out, "aliases:      {}", aliases.join(", "));
+            }
+        },
+        None => _ = writeln!(out, "subcommand:   (none)"),
+    }
+    if resolution.denied {
+        let _ = writeln!(out, "denied:       Must be run outside the sandbox.");
+        return out;
+    }
+    let _ = writeln!(out, "network:      {}", network_summary(resolution));
+    write_root_policy(&mut out, resolution);
+    if let Some(ref root) = resolution.root {
+        let note = if resolution.projectless {
+            " (current directory)"
+        } else if resolution.no_root {
+            " (global, from no_root_subcommands)"
+        } else {
+            ""
+        };
+        let _ = writeln!(out, "root:         {}{note}", root.display());
+    }
+    out
+}
+
+/// Render the effective policy of the profile `resolution` was made with, independent of any
+/// subcommand, along with the Firejail flags in `firejail_argv`, for `--explain-all`
+///
+/// `resolution` should have no subcommand so the network access shown is the profile-wide
+/// default, with the subcommands which get more (or must run unsandboxed) listed after it.
+#[must_use]
+pub fn render_policy(resolution: &Resolution<'_>, firejail_argv: &[OsString]) -> String {
+    let list = |names: &[SubcommandName]| {
+        names.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+    };
+    let profile = resolution.profile;
+
+    // `fmt::Write` for `String` is infallible, so the results are safe to ignore
+    let mut out = format!("[profile.{}]\n", resolution.command);
+    let _ = writeln!(out, "network:      {}", network_summary(resolution));
+    if !profile.allow_network_subcommands().is_empty() {
+        let _ = writeln!(out, "networked:    {}", list(profile.allow_network_subcommands()));
+    }
+    if !profile.deny_subcommands().is_empty() {
+        let _ = writeln!(out, "denied:       {}", list(profile.deny_subcommands()));
+    }
+    write_root_policy(&mut out, resolution);
+    let flags: Vec<_> = firejail_argv
+        .iter()
+        .skip(1)
+        .take_while(|x| *x != "--")
+        .map(|x| x.to_string_lossy())
+        .collect();
+    let _ = writeln!(out, "flags:        {}", flags.join(" "));
+    out
+}
+
+/// One entry in the `--list-profiles --json` array
+///
+/// Every field comes from the configuration file, which TOML requires to be UTF-8, so there's no
+/// need for the byte-array encoding used for paths in [`Resolution`].
+#[derive(Debug, Serialize)]
+struct ProfileSummary<'cfg> {
+    /// The profile's name, as matched against `argv[0]`
+    command: &'cfg CommandName,
+    /// The profile's `root_marked_by` markers
+    root_marked_by: &'cfg [RootMarker],
+    /// The profile's `root_marked_by_all` group, if any
+    root_marked_by_all: &'cfg [RootMarker],
+    /// The network access subcommands get unless listed in `allow_network_subcommands`
+    allow_network: caps::Network,
+    /// The subcommands granted unrestricted network access
+    allow_network_subcommands: &'cfg [SubcommandName],
+    /// The subcommands which are refused
+    deny_subcommands: &'cfg [SubcommandName],
+    /// The subcommands which use the working directory as the root
+    projectless_subcommands: &'cfg [SubcommandName],
+}
+
+/// Render the list of `config`'s profiles for `--list-profiles`, in name order
+///
+/// [`Format::Human`] gives each name with its root markers, aligned, and [`Format::Json`] gives
+/// an array with one object per profile.
+///
+/// # Errors
+///
+/// Only fails if [`Format::Json`] serialization does.
+pub fn render_profiles(config: &Config, format: Format) -> Result<String, serde_json::Error> {
+    let summaries: Vec<ProfileSummary<'_>> = config
+        .profiles()
+        .map(|(command, profile)| ProfileSummary {
+            command,
+            root_marked_by: profile.root_marked_by(),
+            root_marked_by_all: profile.root_marked_by_all(),
+            allow_network: profile.allow_network(),
+            allow_network_subcommands: profile.allow_network_subcommands(),
+            deny_subcommands: profile.deny_subcommands(),
+            projectless_subcommands: profile.projectless_subcommands(),
+        })
+        .collect();
+    if format == Format::Json {
+        return serde_json::to_string(&summaries).map(|json| json + "\n");
+    }
+
+    let names: Vec<String> = summaries.iter().map(|x| x.command.to_string()).collect();
+    let width = names.iter().map(|x| x.chars().count()).max().unwrap_or(0);
+    let mut out = String::new();
+    for (name, summary) in names.iter().zip(&summaries) {
+        let mut markers: Vec<String> =
+            summary.root_marked_by.iter().map(ToString::to_string).collect();
+        if !summary.root_marked_by_all.is_empty() {
+            let group: Vec<String> =
+                summary.root_marked_by_all.iter().map(ToString::to_string).collect();
+            markers.push(format!("all of ({})", group.join(", ")));
+        }
+        // `fmt::Write` for `String` is infallible, so the result is safe to ignore
+        let _ = writeln!(out, "{name:<width$}  {}", markers.join(", "));
+    }
+    Ok(out)
+}
+
+/// Write the `root_marked_by` markers and search policy `resolution` used as aligned lines
+fn write_root_policy(out: &mut String, resolution: &Resolution<'_>) {
+    let markers: Vec<String> =
+        resolution.profile.root_marked_by().iter().map(ToString::to_string).collect();
+    let _ = writeln!(out, "root markers: {}", markers.join(", "));
+    let group: Vec<String> =
+        resolution.profile.root_marked_by_all().iter().map(ToString::to_string).collect();
+    if !group.is_empty() {
+        let _ = writeln!(out, "or all of:    {}", group.join(", "));
+    }
+    let _ = writeln!(out, "root policy:  {}", resolution.root_find_outermost.description());
+}
@@ -0,0 +1,64 @@
+//! A simple utility for launching build automation tools inside a Firejail sandbox without having
+//! to manually create a new profile for each project you work on.
+//!
+//! This is accomplished by matching the command and subcommand (if applicable) against
+//! a list of profiles to identify how the process should be sandboxed and then walking up the
+//! filesystem from the current directory, looking for a file or directory specified as defining
+//! the root directory for the current project. (eg. "The outermost `Makefile`")
+//!
+//! The `nodo` binary is a thin layer over this library, so other tools (eg. editor plugins or
+//! test harnesses) can apply the same sandboxing policy without shelling out. The pipeline is:
+//!
+//! 1. [`config::load`] (or [`config::parse`]) and [`config::Config::validate`]
+//! 2. [`config::Config::resolve`] to get a [`resolve::Resolution`]
+//! 3. [`sandbox::build_command`] to get the Firejail command line
+//!
+//! ```
+//! use std::path::Path;
+//!
+//! let config = nodo::config::parse(
+//!     "firejail_base_flags = []\n\
+//!      [profile.cargo]\n\
+//!      root_marked_by = [\"Cargo.toml\"]\n\
+//!      projectless_subcommands = [\"new\"]",
+//! )
+//! .unwrap();
+//! config.validate().unwrap();
+//!
+//! let argv = ["cargo".into(), "new".into(), "foo".into()];
+//! let resolution =
+//!     config.resolve(&argv, Path::new("/home/user/src"), &Default::default()).unwrap();
+//! assert_eq!(resolution.command.to_string(), "cargo");
+//! assert!(resolution.projectless);
+//! assert_eq!(resolution.root.as_deref(), Some(Path::new("/home/user/src")));
+//! ```
+
+// Copyright (c) 2021,2022 Stephan Sokolow
+
+#![warn(clippy::all, clippy::pedantic, clippy::restriction, clippy::cargo)]
+#![allow(
+    clippy::implicit_return,
+    clippy::needless_return,
+    clippy::missing_inline_in_public_items,
+    clippy::blanket_clippy_restriction_lints
+)]
+#![forbid(unsafe_code)] // Delegate anything `unsafe` to Firejail
+
+pub mod audit;
+pub mod cli;
+pub mod config;
+pub mod error;
+pub mod expand;
+pub mod explain;
+pub mod log;
+pub mod prune;
+pub mod replay;
+pub mod report;
+pub mod resolve;
+pub mod sandbox;
+pub mod sha256;
+pub mod temp;
+pub mod types;
+
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,113 @@
+//! A minimal leveled logger for `nodo`'s own messages on standard error
+//!
+//! (Hand-rolled rather than using the `log` crate and a backend for the same reason the argument
+//! parsing is: every dependency is another opportunity for a supply-chain attack.)
+//!
+//! The threshold is set once at startup with [`init`] and messages are written with [`log_at!`],
+//! which formats nothing if the message would be filtered out.
+
+use std::fmt;
+use std::sync::OnceLock;
+
+/// The environment variable which sets the threshold when none of `--debug`, `--trace`, or
+/// `--quiet` is given
+pub const LOG_ENV_VAR: &str = "NODO_LOG";
+
+/// How important a message is, from most to least
+#[derive(Clone, Copy, Debug, Default, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Level {
+    /// Failures which stop `nodo` from doing what was asked
+    Error,
+    /// Advisory warnings, as hidden by `--quiet`
+    #[default]
+    Warn,
+    /// Notable events which aren't problems
+    Info,
+    /// The diagnostics printed by `--debug`
+    Debug,
+    /// The extra detail printed by `--trace`
+    Trace,
+}
+
+impl Level {
+    /// Parse a level name as accepted in [`LOG_ENV_VAR`] (case-insensitively)
+    #[must_use]
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "error" => Some(Self::Error),
+            "warn" => Some(Self::Warn),
+            "info" => Some(Self::Info),
+            "debug" => Some(Self::Debug),
+            "trace" => Some(Self::Trace),
+            _ => None,
+        }
+    }
+
+    /// Return `true` if a message at this level passes `threshold`
+    #[must_use]
+    pub fn passes(self, threshold: Self) -> bool {
+        self <= threshold
+    }
+}
+
+/// The threshold set by [`init`]
+static THRESHOLD: OnceLock<Level> = OnceLock::new();
+
+/// Set the threshold for the rest of the process
+///
+/// Only the first call has any effect, so it can't change partway through a run. Until it's
+/// called, the threshold is [`Level::default`].
+pub fn init(threshold: Level) {
+    let _ = THRESHOLD.set(threshold);
+}
+
+/// Return `true` if a message at `level` would be written, for skipping work that only exists
+/// to produce one
+#[must_use]
+pub fn enabled(level: Level) -> bool {
+    level.passes(THRESHOLD.get().copied().unwrap_or_default())
+}
+
+/// Write `message` to standard error if `level` passes the threshold
+///
+/// Use [`log_at!`] rather than calling this directly.
+pub fn write(level: Level, message: fmt::Arguments<'_>) {
+    if enabled(level) {
+        eprintln!("{message}");
+    }
+}
+
+/// Write a `format!`-style message at the given [`Level`] variant, eg.
+/// `log_at!(Warn, "WARNING: {problem}")`
+#[macro_export]
+macro_rules! log_at {
+    ($level:ident, $($arg:tt)+) => {
+        $crate::log::write($crate::log::Level::$level, format_args!($($arg)+))
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Assert that a threshold lets through its own level and more important ones only
+    #[test]
+    fn threshold_filters_less_important() {
+        assert!(Level::Error.passes(Level::Error));
+        assert!(!Level::Warn.passes(Level::Error));
+        assert!(!Level::Info.passes(Level::Error));
+        assert!(Level::Info.passes(Level::Info));
+        assert!(Level::Error.passes(Level::Trace));
+        assert!(!Level::Trace.passes(Level::Debug));
+    }
+
+    /// Assert that level names are parsed case-insensitively and anything else is rejected
+    #[test]
+    fn level_names() {
+        assert_eq!(Level::parse("error"), Some(Level::Error));
+        assert_eq!(Level::parse("WARN"), Some(Level::Warn));
+        assert_eq!(Level::parse("Trace"), Some(Level::Trace));
+        assert_eq!(Level::parse("warning"), None);
+        assert_eq!(Level::parse(""), None);
+    }
+}
@@ -1,10 +1,5 @@
-//! A simple utility for launching build automation tools inside a Firejail sandbox without having
-//! to manually create a new profile for each project you work on.
-//!
-//! This is accomplished by matching the command and subcommand (if applicable) against
-//! a list of profiles to identify how the process should be sandboxed and then walking up the
-//! filesystem from the current directory, looking for a file or directory specified as defining
-//! the root directory for the current project. (eg. "The outermost `Makefile`")
+//! The `nodo` command. (All the sandboxing policy lives in the library half of the crate so it
+//! can be embedded. This only turns its results into output and exit codes.)
 
 // Copyright (c) 2021,2022 Stephan Sokolow
 
@@ -17,50 +12,709 @@
 )]
 #![forbid(unsafe_code)] // Delegate anything `unsafe` to Firejail
 
+use std::env;
 use std::error::Error;
+use std::ffi::OsString;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 
-mod cli;
-mod config;
-mod types;
+use nodo::audit::Clock as _;
+use nodo::log_at;
+use nodo::{
+    audit, cli, config, error, expand, explain, log, prune, replay, report, resolve, sandbox, types,
+};
+use report::ErrorFormat;
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let action = cli::parse_args(std::env::args_os());
+/// The exit code used when no profile matches the command
+///
+/// (Distinct from the `1` used for other failures so scripts can tell "not configured yet" apart
+/// from "broken".)
+const EXIT_NO_PROFILE: i32 = 2;
+
+/// The exit code used when the configuration file parses but fails [`config::Config::validate`]
+const EXIT_INVALID_CONFIG: i32 = 3;
+
+/// A failure which has already been reported to the user (eg. by [`report_no_profile`]), so
+/// `main` only has to exit with the code it carries
+#[derive(Debug)]
+struct Reported(i32);
+
+impl fmt::Display for Reported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "(already reported, exiting with status {})", self.0)
+    }
+}
+
+impl Error for Reported {}
+
+fn main() {
+    let mut argv: Vec<OsString> = env::args_os().collect();
+    let (format, result) = match cli::take_error_format(&mut argv) {
+        Ok(format) => match cli::take_config(&mut argv) {
+            Ok(source) => (format, run(argv, &source, format)),
+            Err(message) => (format, Err(report::Fatal::new("usage", message).into())),
+        },
+        Err(message) => (ErrorFormat::Human, Err(report::Fatal::new("usage", message).into())),
+    };
+    match result {
+        Ok(code) => std::process::exit(code),
+        Err(err) => {
+            if let Some(&Reported(code)) = err.downcast_ref::<Reported>() {
+                std::process::exit(code);
+            }
+            report_fatal(&*err, format);
+            std::process::exit(1);
+        },
+    }
+}
+
+/// Perform the action requested on the command line, returning the exit code or any error that
+/// should be reported
+///
+/// `std::process::exit` doesn't run destructors, so it's only called by `main` once everything
+/// here (eg. any [`nodo::temp::TempGuard`]) has been dropped.
+fn run(
+    raw_args: Vec<OsString>,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let action = cli::parse_args(raw_args.into_iter());
     if let cli::Action::Exit = action {
-        return Ok(());
+        return Ok(0);
     }
+    let from_env = match env::var_os(log::LOG_ENV_VAR) {
+        Some(value) => Some(log::Level::parse(&value.to_string_lossy()).ok_or_else(|| {
+            report::Fatal::new(
+                "usage",
+                format!("{} must be error, warn, info, debug, or trace", log::LOG_ENV_VAR),
+            )
+        })?),
+        None => None,
+    };
+    log::init(match action {
+        cli::Action::Sandbox(ref args) | cli::Action::Explain(_, ref args) => {
+            args.log_level(from_env)
+        },
+        _ => from_env.unwrap_or_default(),
+    });
 
-    let config: config::Config = toml_edit::de::from_str(config::DEFAULT_CONFIG)?;
     match action {
-        cli::Action::PathToConf => {
-            if let Some(path) = config::find_path() {
-                println!("{}", path.to_string_lossy());
+        cli::Action::PathToConf { canonical } => {
+            let mut path = conf_file_path(source)?;
+            if canonical {
+                path = config::canonicalize_path(&path)?;
+            }
+            println!("{}", path.to_string_lossy());
+            Ok(0)
+        },
+        cli::Action::WriteConf { merge } => {
+            let path = conf_file_path(source)?;
+            config::write_default(&path, merge)?;
+            println!("{}", path.to_string_lossy());
+            Ok(0)
+        },
+        cli::Action::BadUsage(message) => Err(report::Fatal::new("usage", message).into()),
+        cli::Action::DumpConf => {
+            let config = load_valid_config(source, format)?;
+            print!("{}", config.to_toml()?);
+            Ok(0)
+        },
+        cli::Action::ExplainAll => explain_all(source, format),
+        cli::Action::Lint => lint(source, format),
+        cli::Action::ListProfiles(list_format) => {
+            let config = load_valid_config(source, format)?;
+            print!("{}", explain::render_profiles(&config, list_format)?);
+            Ok(0)
+        },
+        cli::Action::PrintRoot(child_argv) => print_root(&child_argv, source, format),
+        cli::Action::PruneReport(dir) => {
+            let config = load_valid_config(source, format)?;
+            let usage = prune::scan(&config, &dir)
+                .map_err(|err| format!("Could not scan {}: {err}", dir.display()))?;
+            print!("{}", prune::render(&usage));
+            Ok(0)
+        },
+        cli::Action::Replay(dir) => replay(&dir, source, format),
+        cli::Action::TestResolve(child_argv, root) => {
+            test_resolve(&child_argv, root, source, format)
+        },
+        cli::Action::Explain(explain_format, args) => {
+            explain(explain_format, &args, source, format)
+        },
+        cli::Action::ExportProfile(command) => export_profile(command, source, format),
+        cli::Action::DiffProfiles(first, second, root) => {
+            diff_profiles([first, second], root, source, format)
+        },
+        cli::Action::Sandbox(args) => run_sandboxed(&args, source, format),
+        cli::Action::Exit => unreachable!(),
+    }
+}
+
+/// Describe what would be done with `args`' command line in `explain_format` (see
+/// [`explain::render`] and [`sandbox::firejail_syntax`])
+fn explain(
+    explain_format: explain::Format,
+    args: &cli::ChildArgs,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let config_path = source.path();
+    let config = load_valid_config(source, format)?;
+    let resolution = config
+        .resolve(&args.child_argv, &env::current_dir()?, &args.overrides)
+        .map_err(|err| resolve_failure(err, source, format))?;
+    if explain_format == explain::Format::FirejailSyntax && !resolution.denied {
+        let firejail_argv = sandbox::build_command(
+            &config,
+            &resolution,
+            config_path.as_deref(),
+            args,
+            &expand::Env::current(),
+        )?;
+        for line in sandbox::firejail_syntax(&firejail_argv) {
+            println!("{line}");
+        }
+    } else {
+        print!("{}", explain::render(&resolution, explain_format)?);
+    }
+    Ok(0)
+}
+
+/// Print the project root `--print-root` would use for `child_argv` (which may be empty)
+fn print_root(
+    child_argv: &[OsString],
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let config = load_valid_config(source, format)?;
+    let cwd = env::current_dir()?;
+    let root = if child_argv.is_empty() {
+        let mut markers: Vec<types::RootMarker> = config
+            .profiles()
+            .flat_map(|(_, profile)| profile.root_marked_by().iter().cloned())
+            .collect();
+        markers.sort_unstable();
+        markers.dedup();
+        let policy = types::caps::ProjectRoot::Innermost;
+        let groups = config.profiles().map(|(_, profile)| profile.root_marked_by_all());
+        // Each `root_marked_by_all` group has to be searched for on its own, so keep the innermost
+        std::iter::once(resolve::find_project_root(&cwd, &markers, policy, &mut |_, _| ()))
+            .chain(groups.filter(|x| !x.is_empty()).map(|group| {
+                resolve::find_project_root_matching(&cwd, &[], group, policy, &mut |_, _| ())
+            }))
+            .flatten()
+            .max_by_key(|root| root.components().count())
+            .ok_or_else(|| {
+                report::Fatal::new(
+                    "no_project_root",
+                    format!(
+                        "Could not find any profile's project root in any ancestor of {}",
+                        cwd.display()
+                    ),
+                )
+                .with_path(Some(&cwd))
+            })?
+    } else {
+        let resolution = config
+            .resolve(child_argv, &cwd, &resolve::Overrides::default())
+            .map_err(|err| resolve_failure(err, source, format))?;
+        resolution.check_denied()?;
+        resolution.root.unwrap_or(cwd)
+    };
+    println!("{}", root.display());
+    Ok(0)
+}
+
+/// Print the `--explain` decisions for `child_argv` without looking at the filesystem, using
+/// `root` (unchecked) as the project root if given
+fn test_resolve(
+    child_argv: &[OsString],
+    root: Option<PathBuf>,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let config = load_valid_config(source, format)?;
+    let overrides = resolve::Overrides { root, ..resolve::Overrides::default() };
+    let mut resolution = config
+        .resolve_unrooted(child_argv, &overrides)
+        .map_err(|err| resolve_failure(err, source, format))?;
+    if !resolution.denied {
+        resolution.root.clone_from(&overrides.root);
+    }
+    print!("{}", explain::render(&resolution, explain::Format::Human)?);
+    if !resolution.denied && resolution.root.is_none() {
+        println!(
+            "root:         {}",
+            if resolution.projectless {
+                "(the current directory)"
+            } else if resolution.no_root {
+                "(the profile's no_root_base, or the home directory)"
+            } else {
+                "(would search for the root markers above)"
+            }
+        );
+    }
+    Ok(0)
+}
+
+/// Print the effective policy of every configured profile (see [`explain::render_policy`]),
+/// separated by blank lines
+fn explain_all(source: &config::Source, format: ErrorFormat) -> Result<i32, Box<dyn Error>> {
+    let config_path = source.path();
+    let config = load_valid_config(source, format)?;
+    let root = PathBuf::from(sandbox::EXPORT_ROOT_PLACEHOLDER);
+    let env = expand::Env::current();
+    let mut sections = Vec::new();
+    for (command, _) in config.profiles() {
+        // Named explicitly so neither `command_matches` nor `command_path_prefix` get involved
+        let argv = [OsString::from(command.to_string())];
+        let overrides = resolve::Overrides { profile: Some(argv[0].clone()), root: None };
+        let resolution = match config.resolve_unrooted(&argv, &overrides) {
+            Err(error::Error::NotAllowed(_)) => {
+                sections.push(format!(
+                    "[profile.{command}]\nallowed:      No (missing from allowed_commands)\n"
+                ));
+                continue;
+            },
+            result => result?,
+        };
+        let resolution =
+            resolve::Resolution { root: Some(root.clone()), cwd: root.clone(), ..resolution };
+        let firejail_argv = sandbox::build_command(
+            &config,
+            &resolution,
+            config_path.as_deref(),
+            &cli::ChildArgs::default(),
+            &env,
+        )?;
+        sections.push(explain::render_policy(&resolution, &firejail_argv));
+    }
+    print!("{}", sections.join("\n"));
+    Ok(0)
+}
+
+/// Print `command`'s profile as a standalone Firejail `.profile` file (see
+/// [`sandbox::export_profile`])
+fn export_profile(
+    command: OsString,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let config_path = source.path();
+    let config = load_valid_config(source, format)?;
+    let resolution = config
+        .resolve_unrooted(&[command], &resolve::Overrides::default())
+        .map_err(|err| resolve_failure(err, source, format))?;
+    let env = expand::Env::current();
+    let exported = sandbox::export_profile(&config, resolution, config_path.as_deref(), &env)?;
+    print!("{exported}");
+    Ok(0)
+}
+
+/// Print the differences between the Firejail command lines generated for two commands' profiles,
+/// using `root` (unchecked) as the project root if given, and return 1 if there are any
+fn diff_profiles(
+    commands: [OsString; 2],
+    root: Option<PathBuf>,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let config_path = source.path();
+    let config = load_valid_config(source, format)?;
+    let root = root.unwrap_or_else(|| PathBuf::from(sandbox::EXPORT_ROOT_PLACEHOLDER));
+    let env = expand::Env::current();
+    let mut argvs = Vec::with_capacity(commands.len());
+    for command in &commands {
+        let resolution = config
+            .resolve_unrooted(std::slice::from_ref(command), &resolve::Overrides::default())
+            .map_err(|err| resolve_failure(err, source, format))?;
+        let resolution =
+            resolve::Resolution { root: Some(root.clone()), cwd: root.clone(), ..resolution };
+        // Built without the command itself, since that always differs
+        argvs.push(sandbox::build_command(
+            &config,
+            &resolution,
+            config_path.as_deref(),
+            &cli::ChildArgs::default(),
+            &env,
+        )?);
+    }
+
+    let [first, second] = commands.map(|x| x.to_string_lossy().into_owned());
+    println!("--- {first}\n+++ {second}");
+    let lines = sandbox::diff_commands(&argvs[0], &argvs[1]);
+    for &(marker, arg) in &lines {
+        println!("{marker} {}", arg.to_string_lossy());
+    }
+    Ok(i32::from(lines.iter().any(|&(marker, _)| marker != ' ')))
+}
+
+/// Check the configuration's profile resolution against the recorded cases in `dir`, printing
+/// a line per case and returning 1 if any didn't match
+fn replay(dir: &Path, source: &config::Source, format: ErrorFormat) -> Result<i32, Box<dyn Error>> {
+    let config = load_valid_config(source, format)?;
+    let results = match replay::replay_dir(&config, dir) {
+        Ok(results) => results,
+        // Already a complete report (eg. a scratch directory couldn't be created)
+        Err(err) if err.is::<report::Fatal>() => return Err(err),
+        Err(err) => {
+            return Err(report::Fatal::new("replay", err.to_string()).with_path(Some(dir)).into())
+        },
+    };
+    let mut failed = false;
+    for (name, mismatches) in results {
+        if mismatches.is_empty() {
+            println!("ok       {name}");
+        } else {
+            println!("MISMATCH {name}: {}", mismatches.join("; "));
+            failed = true;
+        }
+    }
+    Ok(i32::from(failed))
+}
+
+/// Print [`config::lint`]'s warnings about the configuration file (after making sure it's valid)
+fn lint(source: &config::Source, format: ErrorFormat) -> Result<i32, Box<dyn Error>> {
+    // Read once and parse the same text, since standard input can't be read twice
+    let (text, origin) = source.read().map_err(|err| {
+        report::Fatal::new("parse", err.to_string()).with_path(source.path().as_deref())
+    })?;
+    let config = config::parse_from(&text, origin.as_deref())
+        .map_err(|err| report::Fatal::new("parse", err.to_string()).with_path(origin.as_deref()))?;
+    ensure_valid(&config, origin.as_deref(), format)?;
+    for warning in config::lint(&text)? {
+        println!("WARNING: {warning}");
+    }
+    Ok(0)
+}
+
+/// Run `args.child_argv` in a sandbox (or preview the Firejail command line with `--dry-run`)
+fn run_sandboxed(
+    args: &cli::ChildArgs,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<i32, Box<dyn Error>> {
+    let config_path = source.path();
+    let config = load_valid_config(source, format)?;
+
+    let nested = sandbox::is_nested();
+    if nested && !config.allow_nesting() {
+        return Err(error::Error::Nested.into());
+    }
+
+    if *source == config::Source::Stdin {
+        log_at!(Debug, "Config: read from standard input, so there is no file to blacklist");
+    }
+    match (args.command(config.strip_suffixes()), args.subcommand()) {
+        (Some(command), Some(subcommand)) => {
+            log_at!(Debug, "Command: {command} (subcommand: {subcommand})");
+        },
+        (Some(command), None) => log_at!(Debug, "Command: {command} (no subcommand)"),
+        (None, _) => (),
+    }
+    let mut trace = |dir: &Path, markers: &[(&types::RootMarker, bool)]| {
+        if log::enabled(log::Level::Trace) {
+            let found: Vec<String> = markers
+                .iter()
+                .map(|(marker, exists)| {
+                    format!("{marker} ({})", if *exists { "found" } else { "missing" })
+                })
+                .collect();
+            log_at!(Trace, "Root search: {}: {}", dir.display(), found.join(", "));
+        }
+    };
+    let mut resolution = config
+        .resolve_traced(&args.child_argv, &env::current_dir()?, &args.overrides, &mut trace)
+        .map_err(|err| resolve_failure(err, source, format))?;
+    resolution.check_denied()?;
+    if nested {
+        return run_nested(&config, args);
+    }
+    let pinned = resolution.pin_root()?;
+
+    let env = expand::Env::current();
+    let firejail_argv =
+        sandbox::build_command(&config, &resolution, config_path.as_deref(), args, &env)?;
+    sandbox::check_redirects(&config, &resolution, &args.redirects, &env)?;
+    if log::enabled(log::Level::Warn) {
+        warn_before_launch(args, &config, &resolution, &env);
+    }
+    if log::enabled(log::Level::Debug) {
+        print_resolution(args, &resolution);
+    }
+    if args.dry_run {
+        // stdout stays just the command line, so `--debug` can't break scripts parsing it
+        log_at!(Debug, "Not running (--dry-run): {}", sandbox::format_argv(&firejail_argv));
+        println!("{}", sandbox::format_argv(&firejail_argv));
+        if args.check {
+            for problem in sandbox::check_paths(&resolution, &env) {
+                log_at!(Warn, "WARNING: {problem}");
+            }
+        }
+        return Ok(0);
+    }
+    check_backend(&config, &firejail_argv)?;
+    if let Some(hook) = config.pre_launch() {
+        if log::enabled(log::Level::Debug) {
+            let hook_argv: Vec<OsString> = hook.iter().map(OsString::from).collect();
+            log_at!(Debug, "Pre-launch: {}", sandbox::format_argv(&hook_argv));
+        }
+        let status = sandbox::run_pre_launch(hook).map_err(|err| {
+            report::Fatal::new("pre_launch_failed", format!("Could not run pre_launch: {err}"))
+        })?;
+        if !status.success() {
+            let message = format!("Not launching the sandbox because pre_launch failed ({status})");
+            return Err(report::Fatal::new("pre_launch_failed", message).into());
+        }
+    }
+    if let Some(ref pinned) = pinned {
+        pinned.verify(&resolution.command)?;
+    }
+    log_at!(Debug, "Running: {}", sandbox::format_argv(&firejail_argv));
+    let clock = audit::SystemClock;
+    let started = clock.now();
+    let exit_code = sandbox::launch(&firejail_argv, &args.redirects, config.kill_grace())?;
+    if log::enabled(log::Level::Debug) {
+        let root = resolution.root.as_deref();
+        log_at!(Debug, "{}", audit::summary(&clock, started, exit_code, &resolution.command, root));
+    }
+    Ok(resolution.profile.remap_exit_code(exit_code))
+}
+
+/// Run the command as-is (or preview doing so) because `nodo` is already inside a sandbox and
+/// `allow_nesting` is set, rather than nesting Firejail
+///
+/// This is only for once the command line has been resolved and checked like any other, so
+/// calling `nodo` from inside the sandbox can't get around the policy's restrictions.
+fn run_nested(config: &config::Config, args: &cli::ChildArgs) -> Result<i32, Box<dyn Error>> {
+    let child_argv = if args.shell {
+        vec![sandbox::interactive_shell(&expand::Env::current())]
+    } else {
+        args.child_argv.clone()
+    };
+    if args.dry_run {
+        println!("{}", sandbox::format_argv(&child_argv));
+        return Ok(0);
+    }
+    Ok(sandbox::launch(&child_argv, &args.redirects, config.kill_grace())?)
+}
+
+/// Make sure the installed Firejail supports every option in `firejail_argv`, refusing to launch
+/// (or, without `fail_closed`, warning) if it doesn't
+fn check_backend(config: &config::Config, firejail_argv: &[OsString]) -> Result<(), report::Fatal> {
+    let Some(program) = firejail_argv.first() else { return Ok(()) };
+    let problem = match sandbox::backend_help(program) {
+        Ok(help) => {
+            let unsupported = sandbox::unsupported_flags(firejail_argv, &help);
+            if unsupported.is_empty() {
                 return Ok(());
+            }
+            format!("the installed Firejail doesn't support {}", unsupported.join(", "))
+        },
+        // Leave reporting a missing Firejail to the launch itself
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => format!("Firejail couldn't be asked which options it supports ({err})"),
+    };
+    if config.fail_closed() {
+        let message = format!("Not launching the sandbox because {problem} (see fail_closed)");
+        return Err(report::Fatal::new("unsupported_option", message));
+    }
+    log_at!(Warn, "WARNING: Launching anyway because fail_closed is off, but {problem}");
+    Ok(())
+}
+
+/// Log the warnings `--quiet` suppresses about things which may make the sandboxed command
+/// behave unexpectedly
+fn warn_before_launch(
+    args: &cli::ChildArgs,
+    config: &config::Config,
+    resolution: &resolve::Resolution<'_>,
+    env: &expand::Env,
+) {
+    let base_flags =
+        resolution.profile.base_flags_override().unwrap_or_else(|| config.firejail_base_flags());
+    if base_flags.is_empty() {
+        log_at!(
+            Warn,
+            "WARNING: The Firejail base flags for this profile are empty, so the sandbox only \
+             restricts what the profile itself does. (Use --quiet to hide this warning.)"
+        );
+    }
+    if !args.shell {
+        let program = args.child_argv.first().and_then(|x| sandbox::setuid_program(x, env));
+        if let Some(path) = program {
+            log_at!(
+                Warn,
+                "WARNING: {} is setuid, which Firejail will prevent from gaining privileges, so \
+                 it may not behave as it would outside the sandbox. (Use --quiet to hide this \
+                 warning.)",
+                path.display()
+            );
+        }
+    }
+    if let Some(fs_type) = resolution.root.as_deref().and_then(sandbox::network_filesystem) {
+        log_at!(
+            Warn,
+            "WARNING: The project root is on a network filesystem ({fs_type}), which Firejail's \
+             mount namespace may not handle as expected. (Use --quiet to hide this warning.)"
+        );
+    }
+}
+
+/// Print the `--debug` diagnostics describing where the sandbox's network access and root came
+/// from
+fn print_resolution(args: &cli::ChildArgs, resolution: &resolve::Resolution<'_>) {
+    log_at!(Debug, "Network: {}", explain::network_summary(resolution));
+    if let Some(ref root) = resolution.root {
+        let source = if args.overrides.root.is_some() {
+            "--root"
+        } else if resolution.projectless {
+            "projectless_subcommands, so the current directory"
+        } else {
+            match resolution.root_find_outermost {
+                types::caps::ProjectRoot::Innermost => "innermost root_marked_by match",
+                types::caps::ProjectRoot::Outermost => "outermost root_marked_by match",
+            }
+        };
+        log_at!(Debug, "Root: {} (from {source})", root.display());
+    }
+}
+
+/// [`config::Source::load`], with any failure marked as a `"parse"` error for
+/// `--error-format json` and any [`config::Config::warnings`] logged
+fn load_config(source: &config::Source) -> Result<config::Config, report::Fatal> {
+    let config = source.load().map_err(|err| {
+        report::Fatal::new("parse", err.to_string()).with_path(source.path().as_deref())
+    })?;
+    for warning in config.warnings() {
+        log_at!(Warn, "WARNING: {warning}");
+    }
+    Ok(config)
+}
+
+/// [`load_config`], then [`ensure_valid`]
+fn load_valid_config(
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Result<config::Config, Box<dyn Error>> {
+    let config = load_config(source)?;
+    ensure_valid(&config, source.path().as_deref(), format)?;
+    Ok(config)
+}
+
+/// Report any problems found by [`config::Config::validate`], stopping with
+/// [`EXIT_INVALID_CONFIG`] if there are any
+fn ensure_valid(
+    config: &config::Config,
+    config_path: Option<&Path>,
+    format: ErrorFormat,
+) -> Result<(), Reported> {
+    config
+        .validate()
+        .map_err(|problems| Reported(report_invalid_config(&problems, config_path, format)))
+}
+
+/// Convert a failure to resolve a command line into the error to end [`run`] with, reporting
+/// [`error::Error::NoProfile`] (with its hints) straight away so it can exit with
+/// [`EXIT_NO_PROFILE`]
+fn resolve_failure(
+    err: error::Error,
+    source: &config::Source,
+    format: ErrorFormat,
+) -> Box<dyn Error> {
+    match err {
+        error::Error::NoProfile(command) => {
+            report_no_profile(&command, source, format);
+            Reported(EXIT_NO_PROFILE).into()
+        },
+        err => err.into(),
+    }
+}
+
+/// The configuration file `--path-to-conf` and `--write-conf` should act on
+fn conf_file_path(source: &config::Source) -> Result<PathBuf, Box<dyn Error>> {
+    match *source {
+        config::Source::Default => Ok(config::find_path()?),
+        config::Source::File(ref path) => Ok(path.clone()),
+        config::Source::Stdin => Err(report::Fatal::new(
+            "usage",
+            "--config - has no configuration file path to print or write to",
+        )
+        .into()),
+    }
+}
+
+/// Report an error which ended [`run`] in the requested format
+fn report_fatal(err: &(dyn Error + 'static), format: ErrorFormat) {
+    match format {
+        ErrorFormat::Human => log_at!(Error, "CRITICAL FAILURE: {err}"),
+        ErrorFormat::Json => {
+            if let Some(fatal) = err.downcast_ref::<report::Fatal>() {
+                fatal.emit_json();
+            } else if let Some(err) = err.downcast_ref::<error::Error>() {
+                report::Fatal::from(err).emit_json();
+            } else if let Some(err) = err.downcast_ref::<config::PathError>() {
+                report::Fatal::new("config_path", err.to_string()).emit_json();
             } else {
-                eprintln!(
-                    "CRITICAL FAILURE: Neither $XDG_CONFIG_HOME nor $HOME/.config are \
-                    absolute directory paths."
-                );
-                std::process::exit(1);
-                // TODO: Use a more consistent, less slipshod way to handle non-zero process exit
-            };
-        },
-        cli::Action::WriteConf => todo!(),
-        cli::Action::Sandbox(args) => {
-            // TODO: Integration test this and use prettier human-readable output
-            config.validate().unwrap();
-
-            // TODO: Actually use the config
-            println!("{:#?}", config);
-            println!("args: {:#?}", args);
-
-            todo!("Split last component off argv[0] and look up profile");
-            // TODO: If no profile exists, point the user at the configuration file so they can
-            // create one.
-
-            // TODO: Support some kind of --debug flag as the first argument (and only as
-            // the first argument) which will display the constructed Firejail command and any
-            // other useful information.
+                report::Fatal::new("error", err.to_string()).emit_json();
+            }
         },
-        cli::Action::Exit => unreachable!(),
     }
 }
+
+/// Tell the user which command has no profile and where they'd add one
+fn report_no_profile(command: &types::CommandName, source: &config::Source, format: ErrorFormat) {
+    let config_path = source.path();
+    if format == ErrorFormat::Json {
+        report::Fatal::from(&error::Error::NoProfile(command.clone()))
+            .with_path(config_path.as_deref())
+            .emit_json();
+        return;
+    }
+    log_at!(Error, "No profile configured for {command}.");
+    if *source == config::Source::Stdin {
+        log_at!(
+            Error,
+            "To sandbox it, add a [profile.{command}] section to the configuration passed on \
+             standard input. (It was read from there because of --config -.)"
+        );
+        return;
+    }
+    match config_path {
+        Some(path) if path.exists() => {
+            log_at!(
+                Error,
+                "To sandbox it, add a [profile.{command}] section to {}",
+                path.display()
+            );
+        },
+        Some(path) => log_at!(
+            Error,
+            "To sandbox it, run `{} --write-conf` and then add a [profile.{command}] section to {}",
+            env!("CARGO_BIN_NAME"),
+            path.display()
+        ),
+        None => log_at!(
+            Error,
+            "To sandbox it, add a [profile.{command}] section to your configuration file. (Neither \
+             $XDG_CONFIG_HOME nor $HOME/.config are absolute directory paths.)"
+        ),
+    }
+}
+
+/// Report every problem found by [`config::Config::validate`] and return the exit code to use
+fn report_invalid_config(
+    problems: &[String],
+    config_path: Option<&Path>,
+    format: ErrorFormat,
+) -> i32 {
+    if format == ErrorFormat::Json {
+        report::Fatal::new("validation", problems.join("\n")).with_path(config_path).emit_json();
+        return EXIT_INVALID_CONFIG;
+    }
+    for problem in problems {
+        log_at!(Error, "CRITICAL FAILURE: {problem}");
+    }
+    EXIT_INVALID_CONFIG
+}
@@ -0,0 +1,83 @@
+//! `--prune-report` scanning for profiles whose project markers appear nowhere in a directory tree
+
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::config::{CommandProfile, Config};
+use crate::types::CommandName;
+
+/// How many levels below the scanned directory to look for markers
+///
+/// (Deep enough for a typical `~/src/<group>/<project>` layout without wandering into every
+/// `node_modules` or `target` directory on the system.)
+pub const MAX_DEPTH: usize = 4;
+
+/// What [`scan`] found for a single profile
+#[derive(Debug, Eq, PartialEq)]
+pub struct ProfileUsage<'cfg> {
+    /// The name of the profile
+    pub command: &'cfg CommandName,
+    /// A directory in which one of the profile's markers was found, if any
+    pub example: Option<PathBuf>,
+}
+
+/// Search `dir`, up to [`MAX_DEPTH`] levels deep, for each profile's `root_marked_by` markers
+///
+/// Symlinked directories aren't followed, so the scan can't loop or escape `dir`. Subdirectories
+/// which can't be read are skipped, since this is only advisory.
+///
+/// # Errors
+///
+/// Fails if `dir` itself can't be read.
+pub fn scan<'cfg>(config: &'cfg Config, dir: &Path) -> io::Result<Vec<ProfileUsage<'cfg>>> {
+    // Fail early and clearly on a typo rather than reporting every profile as unused
+    fs::read_dir(dir)?;
+
+    let mut usage: Vec<ProfileUsage<'_>> =
+        config.profiles().map(|(command, _)| ProfileUsage { command, example: None }).collect();
+    let mut pending = vec![(dir.to_owned(), 0)];
+    while let Some((current, depth)) = pending.pop() {
+        for (entry, (_, profile)) in usage.iter_mut().zip(config.profiles()) {
+            if entry.example.is_none() && has_marker(&current, profile) {
+                entry.example = Some(current.clone());
+            }
+        }
+        if depth < MAX_DEPTH {
+            let Ok(entries) = fs::read_dir(&current) else { continue };
+            pending.extend(
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|x| x.file_type().is_ok_and(|kind| kind.is_dir()))
+                    .map(|x| (x.path(), depth + 1)),
+            );
+        }
+    }
+    Ok(usage)
+}
+
+/// Return `true` if `dir` would be accepted as a project root for `profile`
+fn has_marker(dir: &Path, profile: &CommandProfile) -> bool {
+    let all_of = profile.root_marked_by_all();
+    profile.root_marked_by().iter().any(|marker| marker.is_in(dir))
+        || (!all_of.is_empty() && all_of.iter().all(|marker| marker.is_in(dir)))
+}
+
+/// Render the results of [`scan`] as a summary for humans
+#[must_use]
+pub fn render(usage: &[ProfileUsage<'_>]) -> String {
+    // `fmt::Write` for `String` is infallible, so the results are safe to ignore
+    let mut out = String::new();
+    let _ = writeln!(out, "Profiles with markers present:");
+    for entry in usage {
+        if let Some(ref example) = entry.example {
+            let _ = writeln!(out, "    {} (eg. {})", entry.command, example.display());
+        }
+    }
+    let _ = writeln!(out, "Profiles with no markers present (candidates for removal):");
+    for entry in usage.iter().filter(|x| x.example.is_none()) {
+        let _ = writeln!(out, "    {}", entry.command);
+    }
+    out
+}
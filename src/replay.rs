@@ -0,0 +1,202 @@
+//! Regression testing of profile resolution against recorded cases (`--replay`)
+//!
+//! Each case is a TOML file giving a command line, a layout of files to create in a scratch
+//! directory, the directory within it to resolve from, and the decisions expected, eg.
+//!
+//! ```toml
+//! argv = ["cargo", "build"]
+//! cwd = "project/src"
+//! files = ["project/Cargo.toml", "project/src/"]
+//!
+//! [expected]
+//! profile = "cargo"
+//! network = "all_networks"
+//! root = "project"
+//! ```
+//!
+//! The keys in `[expected]` are those of `--explain --json`, with `root` relative to the scratch
+//! directory, plus `error` for the `--error-format json` kind of an expected failure. Only the
+//! keys given are compared, so a case can pin down as much or as little as it needs to.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::report::Fatal;
+use crate::resolve::Overrides;
+use crate::temp::TempGuard;
+
+/// One recorded resolution, as read from a case file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Case {
+    /// The child command line to resolve
+    argv: Vec<String>,
+    /// The directory to resolve from, relative to the scratch directory
+    #[serde(default)]
+    cwd: PathBuf,
+    /// Empty files (or, with a trailing `/`, directories) to create in the scratch directory
+    /// first, relative to it
+    #[serde(default)]
+    files: Vec<String>,
+    /// The expected values of the keys described in the module documentation
+    expected: BTreeMap<String, Value>,
+}
+
+impl Case {
+    /// Parse a case file's text
+    ///
+    /// # Errors
+    ///
+    /// Fails if `text` isn't a valid case or any of its paths could escape the scratch directory.
+    pub fn parse(text: &str) -> Result<Self, Box<dyn Error>> {
+        let case: Self = toml_edit::de::from_str(text)?;
+        let escapes = |path: &Path| {
+            path.components().any(|x| !matches!(x, Component::Normal(_) | Component::CurDir))
+        };
+        if let Some(path) =
+            case.files.iter().map(Path::new).chain([case.cwd.as_path()]).find(|x| escapes(x))
+        {
+            return Err(
+                format!("paths must be relative and without '..': {}", path.display()).into()
+            );
+        }
+        if case.argv.is_empty() {
+            return Err("argv must not be empty".into());
+        }
+        Ok(case)
+    }
+
+    /// Lay out the case's files in `scratch`, resolve its command line with `config`, and return
+    /// a description of each expected value that didn't match
+    ///
+    /// `scratch` should be an empty directory with no symlinks in its path, so roots found in it
+    /// can be reported relative to it.
+    ///
+    /// # Errors
+    ///
+    /// Fails if the files couldn't be created.
+    pub fn run(&self, config: &Config, scratch: &Path) -> Result<Vec<String>, Box<dyn Error>> {
+        for file in &self.files {
+            let path = scratch.join(file);
+            if file.ends_with('/') {
+                fs::create_dir_all(&path)?;
+            } else {
+                fs::create_dir_all(path.parent().unwrap_or(scratch))?;
+                fs::write(&path, "")?;
+            }
+        }
+        let cwd = scratch.join(&self.cwd);
+        fs::create_dir_all(&cwd)?;
+
+        let argv: Vec<OsString> = self.argv.iter().map(OsString::from).collect();
+        let actual = match config.resolve(&argv, &cwd, &Overrides::default()) {
+            Ok(resolution) => {
+                let root = resolution
+                    .root
+                    .as_deref()
+                    .map(|x| x.strip_prefix(scratch).unwrap_or(x).to_string_lossy().into_owned());
+                let mut actual = match serde_json::to_value(&resolution)? {
+                    Value::Object(fields) => fields,
+                    _ => serde_json::Map::new(),
+                };
+                actual.insert("root".to_owned(), root.map_or(Value::Null, Value::String));
+                actual
+            },
+            Err(err) => {
+                let mut actual = serde_json::Map::new();
+                actual.insert("error".to_owned(), Fatal::from(&err).kind.into());
+                actual
+            },
+        };
+
+        Ok(self
+            .expected
+            .iter()
+            .filter(|&(key, value)| actual.get(key) != Some(value))
+            .map(|(key, value)| match actual.get(key) {
+                Some(found) => format!("{key}: expected {value}, got {found}"),
+                None => format!("{key}: expected {value}, got nothing"),
+            })
+            .collect())
+    }
+}
+
+/// A case's file name with a description of each of its mismatches
+pub type Outcome = (String, Vec<String>);
+
+/// Run every `*.toml` case in `dir` (in name order), each in a fresh scratch directory, returning
+/// each case's file name with its mismatches (if any)
+///
+/// # Errors
+///
+/// Fails if `dir` can't be read or any case can't be parsed or laid out.
+pub fn replay_dir(config: &Config, dir: &Path) -> Result<Vec<Outcome>, Box<dyn Error>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|x| x == "toml") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut results = Vec::with_capacity(paths.len());
+    for (index, path) in paths.iter().enumerate() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        let case = Case::parse(&fs::read_to_string(path)?)
+            .map_err(|err| format!("Could not parse {}: {err}", path.display()))?;
+        let guard = TempGuard::create_dir(&format!("{}-replay-{index}", env!("CARGO_PKG_NAME")))?;
+        results.push((name, case.run(config, guard.path())?));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::test_dir;
+
+    /// Assert that cases are compared only on the keys they give, with roots relative to the
+    /// scratch directory and failures compared by kind
+    #[test]
+    fn compares_given_keys() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             allow_network_subcommands = [\"build\"]\n\
+             root_marked_by = [\"Cargo.toml\"]",
+        )
+        .unwrap();
+        let guard = test_dir("replay");
+        let scratch = guard.path().join("scratch");
+        fs::create_dir(&scratch).unwrap();
+
+        let case = Case::parse(
+            "argv = [\"cargo\", \"build\"]\ncwd = \"project/src\"\nfiles = [\"project/Cargo.toml\"]\n\
+             [expected]\nprofile = \"cargo\"\nroot = \"project\"\nnetwork = \"child_procs_only\"",
+        )
+        .unwrap();
+        assert_eq!(
+            case.run(&config, &scratch).unwrap(),
+            ["network: expected \"child_procs_only\", got \"all_networks\""]
+        );
+
+        let case = Case::parse("argv = [\"ninja\"]\n[expected]\nerror = \"no_profile\"").unwrap();
+        assert!(case.run(&config, &scratch).unwrap().is_empty());
+
+        for bad in ["cwd = \"..\"", "files = [\"/etc/passwd\"]", "files = [\"a/../../b\"]"] {
+            assert!(
+                Case::parse(&format!("argv = [\"ninja\"]\n{bad}\n[expected]")).is_err(),
+                "{bad}"
+            );
+        }
+        assert!(Case::parse("argv = []\n[expected]").is_err());
+    }
+}
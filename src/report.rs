@@ -0,0 +1,139 @@
+//! Reporting of fatal errors in the format selected by `--error-format`
+
+use std::fmt;
+use std::path::Path;
+
+use serde_derive::Serialize;
+
+use crate::error::Error;
+
+/// Output formats supported by `--error-format`
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ErrorFormat {
+    /// `CRITICAL FAILURE: ...` lines and hints meant for humans
+    #[default]
+    Human,
+    /// A single JSON object per failure, meant for editor integrations
+    Json,
+}
+
+/// A fatal error in the stable form emitted by `--error-format json`
+///
+/// This is also an [`std::error::Error`] whose `Display` is just the message, so failures without
+/// a more specific type (eg. configuration parse errors) can be given a `kind` without changing
+/// how they look to humans.
+#[derive(Debug, Serialize)]
+pub struct Fatal {
+    /// A stable, machine-readable name for what went wrong (eg. `"no_profile"`)
+    pub kind: &'static str,
+    /// The same explanation a human would be shown
+    pub message: String,
+    /// The file or directory the error concerns, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    /// The profile the error concerns, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+}
+
+impl Fatal {
+    /// Construct a report with no `path` or `profile`
+    pub fn new(kind: &'static str, message: impl Into<String>) -> Self {
+        Self { kind, message: message.into(), path: None, profile: None }
+    }
+
+    /// Attach the file or directory the error concerns
+    #[must_use]
+    pub fn with_path(mut self, path: Option<&Path>) -> Self {
+        self.path = path.map(|x| x.to_string_lossy().into_owned());
+        self
+    }
+
+    /// Print the report to stderr as a single line of JSON
+    ///
+    /// (Human-readable output is left to the callers, since each kind of failure has its own
+    /// hints to give.)
+    pub fn emit_json(&self) {
+        match serde_json::to_string(self) {
+            Ok(json) => eprintln!("{json}"),
+            // Only reachable if `serde_json` can't serialize strings, but never stay silent
+            Err(err) => eprintln!("CRITICAL FAILURE: {}: {err}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Fatal {}
+
+impl fmt::Display for Fatal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl From<&Error> for Fatal {
+    fn from(err: &Error) -> Self {
+        let (kind, profile, path) = match *err {
+            Error::NoCommand => ("no_command", None, None),
+            Error::BadCommandName(_) => ("bad_command_name", None, None),
+            Error::NoProfile(ref command) => ("no_profile", Some(command), None),
+            Error::AmbiguousProfile(ref command, _) => ("ambiguous_profile", Some(command), None),
+            Error::TooManyArgs(..) => ("too_many_args", None, None),
+            Error::WrongCommandPath(ref command, _, ref prefix) => {
+                ("wrong_command_path", Some(command), Some(prefix.as_path()))
+            },
+            Error::NotAllowed(ref command) => ("not_allowed", Some(command), None),
+            Error::NoProjectRoot(ref command, ref cwd) => {
+                ("no_project_root", Some(command), Some(cwd.as_path()))
+            },
+            Error::RootTooBroad(ref command, ref root) => {
+                ("root_too_broad", Some(command), Some(root.as_path()))
+            },
+            Error::RootOutsideCwd(ref command, ref root, _) => {
+                ("root_outside_cwd", Some(command), Some(root.as_path()))
+            },
+            Error::RootChanged(ref command, ref root) => {
+                ("root_changed", Some(command), Some(root.as_path()))
+            },
+            Error::NoSuchRoot(ref root) => ("no_such_root", None, Some(root.as_path())),
+            Error::NoSuchNoRootBase(ref command, ref base) => {
+                ("no_such_no_root_base", Some(command), Some(base.as_path()))
+            },
+            Error::Nested => ("nested", None, None),
+            Error::Denied(ref command, _) => ("denied", Some(command), None),
+            Error::UnknownSubcommand(ref command, _) => ("unknown_subcommand", Some(command), None),
+            Error::BadRedirect(_, ref path) => ("bad_redirect", None, Some(path.as_path())),
+            Error::BadPath(..) => ("bad_path", None, None),
+            Error::UndefinedVariable(..) => ("undefined_variable", None, None),
+            Error::NotAbsolute(_, ref path) => ("not_absolute", None, Some(path.as_path())),
+            Error::Contradiction(_, _, ref path) => ("contradiction", None, Some(path.as_path())),
+        };
+        Self { profile: profile.map(ToString::to_string), ..Self::new(kind, err.to_string()) }
+            .with_path(path)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::types::CommandName;
+
+    /// Assert that the JSON form has the documented shape and omits absent fields
+    #[test]
+    fn fatal_json_shape() {
+        let make = CommandName::try_from("make".to_owned()).unwrap();
+        let json = serde_json::to_value(Fatal::from(&Error::NoProfile(make))).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "kind": "no_profile",
+                "message": "No profile configured for make",
+                "profile": "make",
+            })
+        );
+
+        let json =
+            serde_json::to_value(Fatal::new("parse", "oops").with_path(Some(Path::new("/x"))))
+                .unwrap();
+        assert_eq!(json, serde_json::json!({"kind": "parse", "message": "oops", "path": "/x"}));
+    }
+}
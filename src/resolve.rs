@@ -0,0 +1,1296 @@
+//! Routines for mapping a command line onto the profile, subcommand, and project root it implies
+
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::AsRawFd;
+use std::path::{Component, Path, PathBuf};
+
+use serde::Serializer;
+use serde_derive::Serialize;
+
+use crate::config::{CommandProfile, Config, NetworkSource, UnknownSubcommand};
+use crate::error::Error;
+use crate::expand::Env;
+use crate::sandbox::find_program;
+use crate::types::{caps, CommandName, RootMarker, SubcommandName};
+
+/// Every per-command decision needed to explain, preview, or launch a sandboxed command
+///
+/// This is the single source of truth consumed by `--explain`, `--dry-run`, and the real launch
+/// path so they can't drift out of agreement.
+#[derive(Debug, Serialize)]
+pub struct Resolution<'cfg> {
+    /// The name of the matched profile (the basename of `argv[0]` unless `--profile` or a
+    /// `command_matches` pattern chose a different one)
+    #[serde(rename = "profile")]
+    pub command: CommandName,
+    /// The matched profile itself
+    #[serde(skip)]
+    pub profile: &'cfg CommandProfile,
+    /// The subcommand after `subcommand_aliases` was applied, if there was one
+    pub subcommand: Option<SubcommandName>,
+    /// `true` if the subcommand must be run outside the sandbox and will be refused
+    pub denied: bool,
+    /// `true` if the current working directory is used as the sandbox root because the subcommand
+    /// is projectless (and `--root` wasn't given)
+    pub projectless: bool,
+    /// `true` if the subcommand operates globally, so the profile's `no_root_base` (or the home
+    /// directory) is used as the sandbox root (and `--root` wasn't given)
+    pub no_root: bool,
+    /// The effective network access after `allow_network_subcommands` was applied
+    pub network: caps::Network,
+    /// The profile field that [`Self::network`] was taken from
+    pub network_source: NetworkSource,
+    /// The policy used to choose between multiple `root_marked_by` matches
+    pub root_find_outermost: caps::ProjectRoot,
+    /// The chosen sandbox root (`None` if the subcommand is denied, since no search is done, or
+    /// if this came from [`Config::resolve_unrooted`])
+    #[serde(serialize_with = "serialize_path")]
+    pub root: Option<PathBuf>,
+    /// The working directory the command was run from, which the child should also run in
+    #[serde(skip)]
+    pub cwd: PathBuf,
+}
+
+/// A diagnostic sink for [`find_project_root`], called with each directory examined and whether
+/// each marker was found in it
+pub type RootTrace<'a> = dyn FnMut(&Path, &[(&RootMarker, bool)]) + 'a;
+
+/// Per-invocation replacements for decisions [`Config::resolve`] would otherwise make itself
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Overrides {
+    /// The name of the profile to use instead of the one matching `argv[0]` (`--profile`)
+    pub profile: Option<OsString>,
+    /// The sandbox root to use instead of searching for `root_marked_by` markers (`--root`)
+    ///
+    /// This replaces discovery (and `projectless_subcommands`), but the root must still be an
+    /// existing directory containing the working directory and not be too broad.
+    pub root: Option<PathBuf>,
+}
+
+impl Config {
+    /// Decide how the child command line `argv`, run from `cwd`, should be sandboxed, with some
+    /// decisions replaced by `overrides`
+    ///
+    /// A denied subcommand is reported via [`Resolution::denied`] rather than as an error so that
+    /// `--explain` can still describe it. A command missing from `allowed_commands` or
+    /// a discovered root which is too broad to be a project is an error.
+    ///
+    /// # Errors
+    ///
+    /// Fails if no profile can be found for the command or no acceptable project root can be found
+    /// for it. (See [`Error`] for the specifics.)
+    pub fn resolve(
+        &self,
+        argv: &[OsString],
+        cwd: &Path,
+        overrides: &Overrides,
+    ) -> Result<Resolution<'_>, Error> {
+        self.resolve_traced(argv, cwd, overrides, &mut |_, _| ())
+    }
+
+    /// [`Self::resolve`], but reporting each directory examined during root discovery to `trace`
+    ///
+    /// (See [`find_project_root`] for what `trace` is passed.) Discovery is skipped entirely,
+    /// without examining any directory, when the subcommand is denied, projectless, or global or
+    /// [`Overrides::root`] is given, since the markers can't affect the result.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`Self::resolve`].
+    pub fn resolve_traced(
+        &self,
+        argv: &[OsString],
+        cwd: &Path,
+        overrides: &Overrides,
+        trace: &mut RootTrace<'_>,
+    ) -> Result<Resolution<'_>, Error> {
+        let mut resolution = self.resolve_unrooted(argv, overrides)?;
+        let (command, profile) = (&resolution.command, resolution.profile);
+        cwd.clone_into(&mut resolution.cwd);
+        // Whichever way the root is chosen, it must contain the working directory and not be
+        // too broad to be a project
+        let checked = |root: PathBuf| {
+            if !contains_cwd(&root, cwd) {
+                return Err(Error::RootOutsideCwd(command.clone(), root, cwd.to_owned()));
+            }
+            if self.is_too_broad(&root) {
+                return Err(Error::RootTooBroad(command.clone(), root));
+            }
+            Ok(Some(root))
+        };
+        resolution.root = if resolution.denied {
+            None
+        } else if let Some(ref root) = overrides.root {
+            // Canonicalized because Firejail refuses paths containing `..`
+            match cwd.join(root).canonicalize() {
+                Ok(root) if root.is_dir() => checked(root)?,
+                _ => return Err(Error::NoSuchRoot(root.clone())),
+            }
+        } else if resolution.projectless {
+            Some(cwd.to_owned())
+        } else if resolution.no_root {
+            #[allow(deprecated)] // See `config::find_path` for why `home_dir` is acceptable here
+            let base = profile.no_root_base().map(Path::to_path_buf).or_else(env::home_dir);
+            let base = base.ok_or_else(|| Error::NoProjectRoot(command.clone(), cwd.to_owned()))?;
+            if !base.is_dir() {
+                return Err(Error::NoSuchNoRootBase(command.clone(), base));
+            }
+            // Deliberately exempt from the too-broad part of `checked`, since the point is to
+            // reach beyond a project (which, with the home directory default, means exposing
+            // all of it)
+            if !contains_cwd(&base, cwd) {
+                resolution.cwd.clone_from(&base);
+            }
+            Some(base)
+        } else {
+            let root = find_project_root_matching(
+                cwd,
+                profile.root_marked_by(),
+                profile.root_marked_by_all(),
+                profile.root_find_outermost(),
+                trace,
+            )
+            .ok_or_else(|| Error::NoProjectRoot(command.clone(), cwd.to_owned()))?;
+            checked(root)?
+        };
+        Ok(resolution)
+    }
+
+    /// The part of [`Self::resolve`] which doesn't depend on the working directory: everything
+    /// but the project root, which is left as `None`, and the working directory, which is left
+    /// empty
+    ///
+    /// This is for answering "what would happen?" (eg. `--test-resolve`) without being in
+    /// a project. No root discovery is done, but it isn't pure. `argv[0]` is still looked up via
+    /// the current `$PATH` for `match_by_binary` and `command_path_prefix`. [`Overrides::root`] is only used to decide whether the subcommand counts as
+    /// projectless.
+    ///
+    /// # Errors
+    ///
+    /// The same as [`Self::resolve`], minus those concerning the project root.
+    pub fn resolve_unrooted(
+        &self,
+        argv: &[OsString],
+        overrides: &Overrides,
+    ) -> Result<Resolution<'_>, Error> {
+        let argv0 = argv.first().ok_or(Error::NoCommand)?;
+        if let Some(limit) = self.max_args() {
+            if argv.len() > limit {
+                return Err(Error::TooManyArgs(argv.len(), limit));
+            }
+        }
+        if let Some(allowed) = self.allowed_commands() {
+            // Checked against what will actually run, regardless of `--profile`
+            let name = canonical_command_name(argv0, self.strip_suffixes())
+                .ok_or_else(|| Error::BadCommandName(argv0.clone()))?;
+            if !allowed.contains(&name) {
+                return Err(Error::NotAllowed(name));
+            }
+        }
+        let (command, profile) = if let Some(ref name) = overrides.profile {
+            // Unlike `argv[0]`, a path here is a mistake rather than something to take the
+            // basename of, and an explicitly named profile isn't subject to `command_matches`
+            let command = name
+                .to_str()
+                .and_then(|x| CommandName::try_from(x.to_owned()).ok())
+                .ok_or_else(|| Error::BadCommandName(name.clone()))?;
+            let profile =
+                self.profile(&command).ok_or_else(|| Error::NoProfile(command.clone()))?;
+            (command, profile)
+        } else {
+            let command = canonical_command_name(argv0, self.strip_suffixes())
+                .ok_or_else(|| Error::BadCommandName(argv0.clone()))?;
+            let (name, profile) = match self.match_profile(&command) {
+                Err(Error::NoProfile(_)) if self.match_by_binary() => self
+                    .match_binary(argv0, &Env::current())
+                    .ok_or_else(|| Error::NoProfile(command.clone()))?,
+                result => result?,
+            };
+            if let Some(prefix) = profile.command_path_prefix() {
+                if !program_under(argv0, prefix, &Env::current()) {
+                    return Err(Error::WrongCommandPath(
+                        name.clone(),
+                        argv0.clone(),
+                        prefix.into(),
+                    ));
+                }
+            }
+            (name.clone(), profile)
+        };
+
+        let subcommand = raw_subcommand(argv.get(1).map(OsString::as_os_str))
+            .map(|x| profile.canonical_subcommand(x));
+        let denied = profile.is_denied(subcommand.as_ref());
+        let projectless = profile.is_projectless(subcommand.as_ref());
+        let no_root = profile.is_no_root(subcommand.as_ref());
+        if let Some(ref subcommand) = subcommand {
+            if profile.unknown_subcommand() == UnknownSubcommand::Deny
+                && !profile.is_known_subcommand(Some(subcommand))
+            {
+                return Err(Error::UnknownSubcommand(command, subcommand.clone()));
+            }
+        }
+
+        let (network, network_source) = profile.network_for(subcommand.as_ref());
+        Ok(Resolution {
+            network,
+            network_source,
+            root_find_outermost: profile.root_find_outermost(),
+            command,
+            profile,
+            subcommand,
+            denied,
+            projectless: projectless && overrides.root.is_none(),
+            no_root: no_root && overrides.root.is_none(),
+            root: None,
+            cwd: PathBuf::new(),
+        })
+    }
+}
+
+impl Config {
+    /// Return `true` if `root` is `/`, the home directory, or in `forbidden_roots`
+    ///
+    /// This only applies to discovered roots. Projectless subcommands use the working directory
+    /// the user explicitly chose to run them in.
+    fn is_too_broad(&self, root: &Path) -> bool {
+        #[allow(deprecated)] // See `config::find_path` for why `home_dir` is acceptable here
+        let home = env::home_dir();
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_owned());
+        let matches = |x: &Path| x.canonicalize().unwrap_or_else(|_| x.to_owned()) == root;
+        matches(Path::new("/"))
+            || home.as_deref().is_some_and(matches)
+            || self.forbidden_roots().iter().any(|x| matches(x))
+    }
+}
+
+impl Resolution<'_> {
+    /// Convert a denied resolution into the error the launch path should report
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Denied`] if [`Self::denied`] is set.
+    pub fn check_denied(&self) -> Result<(), Error> {
+        match self.subcommand {
+            Some(ref subcommand) if self.denied => {
+                Err(Error::Denied(self.command.clone(), subcommand.clone()))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// Open the chosen root (if any) and replace [`Self::root`] with the real path of the
+    /// directory that was opened, so it can be checked with [`PinnedRoot::verify`] just before
+    /// launching
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::RootChanged`] if the root can no longer be opened as a directory.
+    pub fn pin_root(&mut self) -> Result<Option<PinnedRoot>, Error> {
+        let Some(ref root) = self.root else { return Ok(None) };
+        let pinned = PinnedRoot::open(root)
+            .map_err(|_| Error::RootChanged(self.command.clone(), root.clone()))?;
+        self.root = Some(pinned.path.clone());
+        Ok(Some(pinned))
+    }
+}
+
+/// A project root held open between being chosen and the sandbox being launched, to narrow the
+/// window in which it could be swapped for something else (eg. a symlink to `$HOME`)
+///
+/// The root's real path is read back from the open directory via `/proc/self/fd`, so symlinks
+/// which were present when it was chosen are resolved to the directory actually examined.
+/// [`Self::verify`] then confirms that this path still leads to the same device and inode.
+///
+/// **Residual risk:** Firejail only accepts paths, not file descriptors, so a swap between
+/// [`Self::verify`] and Firejail's own mounts can't be detected, and someone able to rename
+/// directories along the root's path can still race it. This only turns an easy, reliable swap
+/// into a narrow race.
+#[derive(Debug)]
+pub struct PinnedRoot {
+    /// The real path of the opened directory
+    path: PathBuf,
+    /// The device and inode of the opened directory
+    identity: (u64, u64),
+    /// The open directory, held so its inode can't be reused while we're comparing against it
+    _dir: File,
+}
+
+impl PinnedRoot {
+    /// Open `root` and resolve its real path
+    ///
+    /// # Errors
+    ///
+    /// Fails if `root` can't be opened, isn't a directory, or its real path can't be determined.
+    pub fn open(root: &Path) -> io::Result<Self> {
+        let dir = File::open(root)?;
+        let meta = dir.metadata()?;
+        if !meta.is_dir() {
+            return Err(io::ErrorKind::NotADirectory.into());
+        }
+        // Fall back to resolving the path again if `/proc` isn't mounted, which `verify` will
+        // catch if it doesn't lead to the directory we opened
+        let path = fs::read_link(format!("/proc/self/fd/{}", dir.as_raw_fd()))
+            .or_else(|_| root.canonicalize())?;
+        let pinned = Self { path, identity: (meta.dev(), meta.ino()), _dir: dir };
+        pinned.matches_path()?;
+        Ok(pinned)
+    }
+
+    /// The real path of the root, as should be passed to Firejail
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Check that [`Self::path`] still leads to the directory that was opened, without following
+    /// a symlink in its place
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::RootChanged`] (attributed to `command`) if it doesn't.
+    pub fn verify(&self, command: &CommandName) -> Result<(), Error> {
+        self.matches_path().map_err(|_| Error::RootChanged(command.clone(), self.path.clone()))
+    }
+
+    /// The check behind [`Self::verify`], failing if the path can't be examined or leads
+    /// somewhere else
+    fn matches_path(&self) -> io::Result<()> {
+        let meta = fs::symlink_metadata(&self.path)?;
+        if meta.is_dir() && (meta.dev(), meta.ino()) == self.identity {
+            Ok(())
+        } else {
+            Err(io::ErrorKind::InvalidData.into())
+        }
+    }
+}
+
+/// Derive the name used to look up a profile from the child's `argv[0]`
+///
+/// Only the final path component is considered, so `cargo` and `/usr/bin/cargo` share a profile.
+/// The first of `strip_suffixes` (the `strip_suffixes` setting) it ends with is then removed, so
+/// `cargo.exe` can share it too, unless that would leave nothing.
+///
+/// An `argv[0]` with no final component (`""`, `"/"`, `"//"`, `".."`) yields `None` and can never
+/// match a profile. ([`Config::resolve`] reports this as [`Error::BadCommandName`].)
+#[must_use]
+pub fn canonical_command_name(argv0: &OsStr, strip_suffixes: &[String]) -> Option<CommandName> {
+    let name = Path::new(argv0).file_name()?.to_str()?;
+    let name = strip_suffixes
+        .iter()
+        .find_map(|suffix| name.strip_suffix(suffix.as_str()).filter(|x| !x.is_empty()))
+        .unwrap_or(name);
+    CommandName::try_from(name.to_owned()).ok()
+}
+
+impl Config {
+    /// Find the profile whose name, looked up via the `$PATH` in `env`, is the same file as
+    /// `argv0` (for the `match_by_binary` setting)
+    ///
+    /// If several profiles name the same file, the first in name order wins.
+    #[must_use]
+    pub fn match_binary(
+        &self,
+        argv0: &OsStr,
+        env: &Env,
+    ) -> Option<(&CommandName, &CommandProfile)> {
+        let identity = |program: &OsStr| {
+            let meta = fs::metadata(find_program(program, env)?).ok()?;
+            Some((meta.dev(), meta.ino()))
+        };
+        let target = identity(argv0)?;
+        self.profiles().find(|(name, _)| identity(OsStr::new(&name.to_string())) == Some(target))
+    }
+}
+
+/// Return `true` if `argv0`, looked up via the `$PATH` in `env`, is a program inside `prefix` (for
+/// a profile's `command_path_prefix`)
+///
+/// The comparison is lexical, so a symlink placed inside `prefix` counts. A relative result (eg.
+/// `./make`) or one containing `..` never does, since neither says where the program really is.
+#[must_use]
+pub fn program_under(argv0: &OsStr, prefix: &Path, env: &Env) -> bool {
+    find_program(argv0, env).is_some_and(|path| {
+        path.is_absolute()
+            && !path.components().any(|x| x == Component::ParentDir)
+            && path.starts_with(prefix)
+    })
+}
+
+/// Derive the subcommand (before alias resolution) from the child's `argv[1]`, if any
+///
+/// Arguments beginning with `-` are flags rather than subcommands and yield `None`.
+#[must_use]
+pub fn raw_subcommand(argv1: Option<&OsStr>) -> Option<SubcommandName> {
+    let name = argv1?.to_str()?;
+    if name.starts_with('-') {
+        return None;
+    }
+    SubcommandName::try_from(name.to_owned()).ok()
+}
+
+/// Walk up from `start` looking for a directory containing any of `markers`
+///
+/// With [`caps::ProjectRoot::Innermost`], the first match wins. With
+/// [`caps::ProjectRoot::Outermost`], the walk continues to the filesystem root and the last
+/// match wins.
+///
+/// Because this is a single walk up through `start`'s ancestors, there are never competing
+/// matches on divergent branches. A directory containing several of `markers` (eg. both
+/// `Makefile` and `.git`) is simply one match, regardless of which or how many markers it holds.
+///
+/// `trace` is called for every directory examined, in order, before deciding whether to stop.
+pub fn find_project_root(
+    start: &Path,
+    markers: &[RootMarker],
+    policy: caps::ProjectRoot,
+    trace: &mut RootTrace<'_>,
+) -> Option<PathBuf> {
+    find_project_root_matching(start, markers, &[], policy, trace)
+}
+
+/// [`find_project_root`], but also accepting a directory which contains every one of `all_of`
+/// (if it isn't empty), as for a profile's `root_marked_by_all`
+///
+/// `trace` is given the results for `all_of` after those for `any_of`.
+pub fn find_project_root_matching(
+    start: &Path,
+    any_of: &[RootMarker],
+    all_of: &[RootMarker],
+    policy: caps::ProjectRoot,
+    trace: &mut RootTrace<'_>,
+) -> Option<PathBuf> {
+    let mut found = None;
+    for dir in start.ancestors() {
+        let results: Vec<_> =
+            any_of.iter().chain(all_of).map(|marker| (marker, marker.is_in(dir))).collect();
+        trace(dir, &results);
+        let (any_results, all_results) = results.split_at(any_of.len());
+        if any_results.iter().any(|&(_, exists)| exists)
+            || (!all_results.is_empty() && all_results.iter().all(|&(_, exists)| exists))
+        {
+            found = Some(dir.to_owned());
+            if policy == caps::ProjectRoot::Innermost {
+                break;
+            }
+        }
+    }
+    found
+}
+
+/// Return `true` if `root` is `cwd` or one of its ancestors once symlinks are resolved
+///
+/// [`find_project_root`] only walks lexical ancestors, so this guards against a `cwd` reached
+/// through a symlink causing an unrelated directory to be whitelisted as the sandbox root.
+fn contains_cwd(root: &Path, cwd: &Path) -> bool {
+    match (root.canonicalize(), cwd.canonicalize()) {
+        (Ok(root), Ok(cwd)) => cwd.starts_with(root),
+        _ => false,
+    }
+}
+
+/// Serialize a path as a string if it's valid UTF-8 or as an array of bytes if it isn't
+///
+/// This keeps the common case easy to consume while still being lossless, since JSON strings
+/// can't represent arbitrary POSIX path bytes.
+#[allow(clippy::ref_option)] // Signature dictated by `serialize_with`
+fn serialize_path<S: Serializer>(path: &Option<PathBuf>, serializer: S) -> Result<S::Ok, S::Error> {
+    match path.as_deref().map(|x| (x.to_str(), x)) {
+        None => serializer.serialize_none(),
+        Some((Some(utf8), _)) => serializer.serialize_str(utf8),
+        Some((None, raw)) => serializer.collect_seq(raw.as_os_str().as_bytes()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::test_dir;
+    use crate::types::FileName;
+    use std::ffi::OsString;
+    use std::fs;
+
+    /// Helper to construct a list of [`RootMarker::Name`] markers concisely
+    fn markers(names: &[&str]) -> Vec<RootMarker> {
+        names
+            .iter()
+            .map(|x| RootMarker::Name(FileName::try_from((*x).to_owned()).unwrap()))
+            .collect()
+    }
+
+    /// Helper to turn string literals into an `argv`
+    fn argv(args: &[&str]) -> Vec<OsString> {
+        args.iter().map(OsString::from).collect()
+    }
+
+    /// Assert that [`Config::resolve`] makes the expected decisions for various `argv` shapes
+    #[test]
+    fn resolve_argv_shapes() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             allow_network_subcommands = [\"fetch\"]\n\
+             deny_subcommands = [\"install\"]\n\
+             projectless_subcommands = [\"new\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             subcommand_aliases = { f = \"fetch\", i = \"install\" }",
+        )
+        .unwrap();
+        let cwd = Path::new("/nodo/test/cwd");
+        let none = Overrides::default();
+        let cargo = CommandName::try_from("cargo".to_owned()).unwrap();
+        let subcommand = |name: &str| Some(SubcommandName::try_from(name.to_owned()).unwrap());
+
+        // Projectless, with the full path to the command
+        let resolution =
+            config.resolve(&argv(&["/usr/bin/cargo", "new", "foo"]), cwd, &none).unwrap();
+        assert_eq!(resolution.command, cargo);
+        assert_eq!(resolution.subcommand, subcommand("new"));
+        assert!(resolution.projectless);
+        assert_eq!(resolution.root.as_deref(), Some(cwd));
+        assert_eq!(resolution.network, caps::Network::ChildProcsOnly);
+        resolution.check_denied().unwrap();
+
+        // Denied via an alias, which must not require a project root
+        let resolution = config.resolve(&argv(&["cargo", "i", "foo"]), cwd, &none).unwrap();
+        assert_eq!(resolution.subcommand, subcommand("install"));
+        assert!(resolution.denied);
+        assert_eq!(resolution.root, None);
+        assert_eq!(
+            resolution.check_denied(),
+            Err(Error::Denied(cargo.clone(), subcommand("install").unwrap()))
+        );
+
+        // Network granted via an alias, but no marker exists above `cwd`
+        assert_eq!(
+            config.resolve(&argv(&["cargo", "f"]), cwd, &none).unwrap_err(),
+            Error::NoProjectRoot(cargo.clone(), cwd.to_owned())
+        );
+
+        // Failures to find a profile
+        assert_eq!(config.resolve(&[], cwd, &none).unwrap_err(), Error::NoCommand);
+        assert_eq!(
+            config.resolve(&argv(&["make"]), cwd, &none).unwrap_err(),
+            Error::NoProfile(CommandName::try_from("make".to_owned()).unwrap())
+        );
+        assert_eq!(
+            config.resolve(&argv(&["/"]), cwd, &none).unwrap_err(),
+            Error::BadCommandName(OsString::from("/"))
+        );
+    }
+
+    /// Assert that an `argv[0]` with no basename is a clear error rather than a panic or a match
+    #[test]
+    fn argv0_without_basename() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n[profile.make]\nroot_marked_by = [\"Makefile\"]",
+        )
+        .unwrap();
+        for argv0 in ["", "/", "///", ".."] {
+            assert_eq!(canonical_command_name(OsStr::new(argv0), &[]), None, "{argv0:?}");
+            let err =
+                config.resolve(&[argv0.into()], Path::new("/"), &Overrides::default()).unwrap_err();
+            assert_eq!(err, Error::BadCommandName(argv0.into()));
+            assert_eq!(
+                err.to_string(),
+                format!("Could not determine a command name from \"{argv0}\"")
+            );
+        }
+    }
+
+    /// Assert that [`Overrides::profile`] replaces the profile lookup but nothing else
+    #[test]
+    fn resolve_with_profile_override() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             [profile.make]\n\
+             projectless_subcommands = [\"all\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]",
+        )
+        .unwrap();
+        let cwd = Path::new("/nodo/test/cwd");
+        let overrides =
+            |name: &str| Overrides { profile: Some(name.into()), ..Overrides::default() };
+
+        let resolution = config.resolve(&argv(&["cargo", "all"]), cwd, &overrides("make")).unwrap();
+        assert_eq!(resolution.command, CommandName::try_from("make".to_owned()).unwrap());
+        assert!(resolution.projectless);
+
+        assert_eq!(
+            config.resolve(&argv(&["cargo"]), cwd, &overrides("ninja")).unwrap_err(),
+            Error::NoProfile(CommandName::try_from("ninja".to_owned()).unwrap())
+        );
+        assert_eq!(
+            config.resolve(&argv(&["cargo"]), cwd, &overrides("/usr/bin/make")).unwrap_err(),
+            Error::BadCommandName(OsString::from("/usr/bin/make"))
+        );
+    }
+
+    /// Assert that `command_matches` is only a fallback for commands without an exact profile
+    /// and that overlapping patterns are refused rather than guessed between
+    #[test]
+    fn resolve_command_matches_fallback() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             command_matches = [\"cargo-*\"]\n\
+             projectless_subcommands = [\"x\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             [profile.cargo-audit]\n\
+             projectless_subcommands = [\"x\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             [profile.nextest]\n\
+             command_matches = [\"*-nextest-*\"]\n\
+             projectless_subcommands = [\"x\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]",
+        )
+        .unwrap();
+        let cwd = Path::new("/nodo/test/cwd");
+        let none = Overrides::default();
+        let name = |x: &str| CommandName::try_from(x.to_owned()).unwrap();
+
+        let resolution = config.resolve(&argv(&["cargo-nextest", "x"]), cwd, &none).unwrap();
+        assert_eq!(resolution.command, name("cargo"));
+        let resolution = config.resolve(&argv(&["/usr/bin/cargo-audit", "x"]), cwd, &none).unwrap();
+        assert_eq!(resolution.command, name("cargo-audit"));
+
+        assert_eq!(
+            config.resolve(&argv(&["cargo-nextest-run", "x"]), cwd, &none).unwrap_err(),
+            Error::AmbiguousProfile(
+                name("cargo-nextest-run"),
+                vec![name("cargo"), name("nextest")]
+            )
+        );
+        assert_eq!(
+            config.resolve(&argv(&["rustc", "x"]), cwd, &none).unwrap_err(),
+            Error::NoProfile(name("rustc"))
+        );
+        // `--profile` names a profile exactly, so patterns don't apply to it
+        assert_eq!(
+            config
+                .resolve(
+                    &argv(&["cargo", "x"]),
+                    cwd,
+                    &Overrides { profile: Some("cargo-x".into()), ..Overrides::default() }
+                )
+                .unwrap_err(),
+            Error::NoProfile(name("cargo-x"))
+        );
+    }
+
+    /// Assert that `max_args` refuses over-long command lines before anything else is checked
+    #[test]
+    fn resolve_honours_max_args() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             max_args = 3\n\
+             [profile.make]\n\
+             projectless_subcommands = [\"all\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]",
+        )
+        .unwrap();
+        let cwd = Path::new("/nodo/test/cwd");
+        let none = Overrides::default();
+
+        assert!(config.resolve(&argv(&["make", "all", "-j4"]), cwd, &none).is_ok());
+
+        let mut long = vec!["make", "all"];
+        long.extend(std::iter::repeat_n("-k", 10_000));
+        let err = config.resolve(&argv(&long), cwd, &none).unwrap_err();
+        assert_eq!(err, Error::TooManyArgs(10_002, 3));
+        assert_eq!(
+            err.to_string(),
+            "Refusing to run a command line of 10002 arguments because max_args in the \
+             configuration file is 3"
+        );
+
+        // Even a command with no profile is refused on length alone
+        assert_eq!(
+            config.resolve(&argv(&["cargo", "a", "b", "c"]), cwd, &none).unwrap_err(),
+            Error::TooManyArgs(4, 3)
+        );
+    }
+
+    /// Assert that `allowed_commands` refuses unlisted commands even if they have profiles
+    #[test]
+    fn resolve_honours_allowed_commands() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             allowed_commands = [\"make\"]\n\
+             [profile.cargo]\n\
+             projectless_subcommands = [\"all\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             [profile.make]\n\
+             projectless_subcommands = [\"all\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]",
+        )
+        .unwrap();
+        let cwd = Path::new("/nodo/test/cwd");
+        let none = Overrides::default();
+
+        assert!(config.resolve(&argv(&["/usr/bin/make", "all"]), cwd, &none).is_ok());
+
+        let cargo = CommandName::try_from("cargo".to_owned()).unwrap();
+        let err = config.resolve(&argv(&["cargo", "all"]), cwd, &none).unwrap_err();
+        assert_eq!(err, Error::NotAllowed(cargo.clone()));
+        assert_eq!(
+            err.to_string(),
+            "Refusing to run cargo because it isn't listed in allowed_commands in the \
+             configuration file"
+        );
+
+        // `--profile` can't be used to smuggle an unlisted command past the check
+        let overrides = Overrides { profile: Some("make".into()), ..Overrides::default() };
+        assert_eq!(
+            config.resolve(&argv(&["cargo", "all"]), cwd, &overrides).unwrap_err(),
+            Error::NotAllowed(cargo)
+        );
+    }
+
+    /// Assert that a marker found at a forbidden boundary (standing in for `/`, which tests can't
+    /// write to) is refused rather than used as the sandbox root
+    #[test]
+    fn resolve_rejects_too_broad_root() {
+        let guard = test_dir("too_broad_root");
+        let base = guard.path().to_owned();
+        let start = base.join("projects/thing/src");
+        fs::create_dir_all(&start).unwrap();
+        fs::write(base.join("Makefile"), "").unwrap();
+
+        let config: Config = toml_edit::de::from_str(&format!(
+            "firejail_base_flags = []\n\
+             forbidden_roots = [{:?}]\n\
+             [profile.make]\n\
+             root_marked_by = [\"Makefile\"]\n\
+             root_find_outermost = true",
+            base.to_str().unwrap()
+        ))
+        .unwrap();
+        let none = Overrides::default();
+        assert_eq!(
+            config.resolve(&argv(&["make"]), &start, &none).unwrap_err(),
+            Error::RootTooBroad(CommandName::try_from("make".to_owned()).unwrap(), base.clone())
+        );
+
+        // A legitimate root beneath the boundary is still found
+        fs::write(base.join("projects/thing/Makefile"), "").unwrap();
+        let config: Config = toml_edit::de::from_str(&format!(
+            "firejail_base_flags = []\n\
+             forbidden_roots = [{:?}]\n\
+             [profile.make]\n\
+             root_marked_by = [\"Makefile\"]",
+            base.to_str().unwrap()
+        ))
+        .unwrap();
+        let resolution = config.resolve(&argv(&["make"]), &start, &none).unwrap();
+        assert_eq!(resolution.root, Some(base.join("projects/thing")));
+
+        assert!(config.is_too_broad(Path::new("/")));
+    }
+
+    /// Assert that with `match_by_binary`, two symlinks to one binary both get the profile named
+    /// after the binary, while names matching nothing (or any name, without it) don't
+    #[test]
+    fn match_by_binary_follows_links() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let guard = test_dir("match_by_binary");
+        let base = guard.path().to_owned();
+        let multi = base.join("multi");
+        fs::write(&multi, "").unwrap();
+        fs::set_permissions(&multi, fs::Permissions::from_mode(0o755)).unwrap();
+        symlink(&multi, base.join("first")).unwrap();
+        symlink("multi", base.join("second")).unwrap();
+        fs::write(base.join("other"), "").unwrap();
+        fs::set_permissions(base.join("other"), fs::Permissions::from_mode(0o755)).unwrap();
+
+        let config_with = |setting: &str| -> Config {
+            toml_edit::de::from_str(&format!(
+                "firejail_base_flags = []\n{setting}\n\
+                 [profile.make]\nroot_marked_by = [\"Makefile\"]\n\
+                 [profile.multi]\nroot_marked_by = [\"Makefile\"]"
+            ))
+            .unwrap()
+        };
+        let config = config_with("match_by_binary = true");
+        let env: Env = [("PATH", base.as_os_str())].into_iter().collect();
+        let matched = |argv0: &str| config.match_binary(OsStr::new(argv0), &env).map(|x| x.0);
+
+        let multi_name = CommandName::try_from("multi".to_owned()).unwrap();
+        assert_eq!(matched("first"), Some(&multi_name));
+        assert_eq!(matched("second"), Some(&multi_name));
+        assert_eq!(matched(base.join("second").to_str().unwrap()), Some(&multi_name));
+        assert_eq!(matched("other"), None);
+        assert_eq!(matched("missing"), None);
+
+        // Name-based matching is the default, so without the setting, `first` has no profile
+        let first = CommandName::try_from("first".to_owned()).unwrap();
+        let argv0 = [base.join("first").into_os_string()];
+        assert_eq!(
+            config_with("").resolve_unrooted(&argv0, &Overrides::default()).unwrap_err(),
+            Error::NoProfile(first)
+        );
+    }
+
+    /// Assert that with `command_path_prefix`, a `make` found in the prefix gets the profile while
+    /// a project-local `./make` doesn't, and that leaving it unset changes nothing
+    #[test]
+    fn command_path_prefix_restricts_matches() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let guard = test_dir("command_path_prefix");
+        let base = guard.path().to_owned();
+        let bin = base.join("usr/bin");
+        let project = base.join("project");
+        fs::create_dir_all(&bin).unwrap();
+        fs::create_dir_all(&project).unwrap();
+        for dir in [&bin, &project] {
+            fs::write(dir.join("make"), "").unwrap();
+            fs::set_permissions(dir.join("make"), fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        let env: Env = [("PATH", bin.as_os_str())].into_iter().collect();
+        let under = |argv0: &Path| program_under(argv0.as_os_str(), &bin, &env);
+        assert!(under(Path::new("make")));
+        assert!(under(&bin.join("make")));
+        assert!(!under(Path::new("./make")));
+        assert!(!under(&project.join("make")));
+        assert!(!under(&bin.join("../../project/make")));
+        assert!(!under(Path::new("missing")));
+
+        let config_with = |setting: &str| -> Config {
+            toml_edit::de::from_str(&format!(
+                "firejail_base_flags = []
+                 [profile.make]
+root_marked_by = [\"Makefile\"]
+{setting}"
+            ))
+            .unwrap()
+        };
+        let none = Overrides::default();
+        let make = CommandName::try_from("make".to_owned()).unwrap();
+        let config = config_with(&format!("command_path_prefix = {:?}", bin.to_str().unwrap()));
+        let system = [bin.join("make").into_os_string()];
+        let local = [project.join("make").into_os_string()];
+        assert_eq!(config.resolve_unrooted(&system, &none).unwrap().command, make);
+        assert_eq!(
+            config.resolve_unrooted(&local, &none).unwrap_err(),
+            Error::WrongCommandPath(make.clone(), local[0].clone(), bin.clone())
+        );
+        assert_eq!(config_with("").resolve_unrooted(&local, &none).unwrap().command, make);
+    }
+
+    /// Assert that no directory is examined for root markers when the result can't depend on
+    /// them: for projectless and denied subcommands and with `--root`
+    #[test]
+    fn resolve_skips_walk_when_irrelevant() {
+        let guard = test_dir("skips_walk");
+        let base = guard.path().to_owned();
+        let project = base.join("project");
+        let sub = project.join("sub");
+        fs::create_dir_all(&sub).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             deny_subcommands = [\"install\"]\n\
+             projectless_subcommands = [\"new\"]\n\
+             root_marked_by = [\"Cargo.toml\"]",
+        )
+        .unwrap();
+        let examined = |args: &[&str], overrides: &Overrides| {
+            let mut count = 0;
+            let resolution = config
+                .resolve_traced(&argv(args), &sub, overrides, &mut |_, _| count += 1)
+                .unwrap();
+            (count, resolution.root)
+        };
+        let none = Overrides::default();
+        let rooted = Overrides { root: Some(project.clone()), ..Overrides::default() };
+
+        assert_eq!(examined(&["cargo", "new", "foo"], &none), (0, Some(sub.clone())));
+        assert_eq!(examined(&["cargo", "install", "foo"], &none), (0, None));
+        assert_eq!(
+            examined(&["cargo", "build"], &rooted),
+            (0, Some(project.canonicalize().unwrap()))
+        );
+        // Control: otherwise, the walk goes up from `sub` to the marker
+        assert_eq!(examined(&["cargo", "build"], &none), (2, Some(project.clone())));
+    }
+
+    /// Assert that a `no_root_subcommands` entry gets the configured base as its root without any walk,
+    /// and is run from the base when the working directory is outside it
+    #[test]
+    fn resolve_no_root_uses_base() {
+        let guard = test_dir("no_root");
+        let base = guard.path().to_owned();
+        let project = base.join("project");
+        let tools = base.join("tools");
+        let tools_sub = tools.join("sub");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&tools_sub).unwrap();
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        let config: Config = toml_edit::de::from_str(&format!(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             no_root_base = {:?}\n\
+             no_root_subcommands = [\"install\"]\n\
+             root_marked_by = [\"Cargo.toml\"]",
+            tools.to_str().unwrap()
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        let resolve = |args: &[&str], cwd: &Path, overrides: &Overrides| {
+            let mut count = 0;
+            let resolution =
+                config.resolve_traced(&argv(args), cwd, overrides, &mut |_, _| count += 1).unwrap();
+            (count, resolution.no_root, resolution.root, resolution.cwd)
+        };
+        let none = Overrides::default();
+
+        assert_eq!(
+            resolve(&["cargo", "install", "foo"], &project, &none),
+            (0, true, Some(tools.clone()), tools.clone())
+        );
+        assert_eq!(
+            resolve(&["cargo", "install", "foo"], &tools_sub, &none),
+            (0, true, Some(tools.clone()), tools_sub.clone())
+        );
+        // `--root` takes precedence, as it does over projectless
+        let rooted = Overrides { root: Some(project.clone()), ..Overrides::default() };
+        let canonical = project.canonicalize().unwrap();
+        assert_eq!(
+            resolve(&["cargo", "install", "foo"], &project, &rooted),
+            (0, false, Some(canonical), project.clone())
+        );
+        // Control: other subcommands still walk to the marker
+        assert_eq!(
+            resolve(&["cargo", "build"], &project, &none),
+            (1, false, Some(project.clone()), project.clone())
+        );
+
+        // A base which doesn't exist is an error rather than a sandbox root
+        let missing = base.join("missing");
+        let config: Config = toml_edit::de::from_str(&format!(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             no_root_base = {:?}\n\
+             no_root_subcommands = [\"install\"]\n\
+             root_marked_by = [\"Cargo.toml\"]",
+            missing.to_str().unwrap()
+        ))
+        .unwrap();
+        let command = CommandName::try_from("cargo".to_owned()).unwrap();
+        assert_eq!(
+            config.resolve(&argv(&["cargo", "install", "foo"]), &project, &none).unwrap_err(),
+            Error::NoSuchNoRootBase(command, missing)
+        );
+    }
+
+    /// Assert that a pinned root resolves symlinks and detects the root being swapped for a
+    /// symlink to somewhere else between discovery and launch
+    #[test]
+    fn pinned_root_detects_swap() {
+        use std::os::unix::fs::symlink;
+
+        let guard = test_dir("pinned_root");
+        let base = guard.path().to_owned();
+        let project = base.join("project");
+        let elsewhere = base.join("elsewhere");
+        fs::create_dir_all(&project).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        symlink(&project, base.join("link")).unwrap();
+        let make = CommandName::try_from("make".to_owned()).unwrap();
+
+        let pinned = PinnedRoot::open(&base.join("link")).unwrap();
+        assert_eq!(pinned.path(), project.canonicalize().unwrap());
+        pinned.verify(&make).unwrap();
+
+        // Simulate an attacker replacing the root after discovery
+        fs::rename(&project, base.join("moved")).unwrap();
+        symlink(&elsewhere, &project).unwrap();
+        assert_eq!(
+            pinned.verify(&make),
+            Err(Error::RootChanged(make.clone(), pinned.path().to_owned()))
+        );
+        // ...or with a directory of its own
+        fs::remove_file(&project).unwrap();
+        fs::create_dir(&project).unwrap();
+        assert!(pinned.verify(&make).is_err());
+        assert!(PinnedRoot::open(&base.join("missing")).is_err());
+    }
+
+    /// Assert that only the basename of `argv[0]` is used for profile lookup
+    #[test]
+    fn canonical_command_name_uses_basename() {
+        let cargo = CommandName::try_from("cargo".to_owned()).unwrap();
+        assert_eq!(canonical_command_name(OsStr::new("cargo"), &[]), Some(cargo.clone()));
+        assert_eq!(canonical_command_name(OsStr::new("/usr/bin/cargo"), &[]), Some(cargo.clone()));
+        assert_eq!(canonical_command_name(OsStr::new("./cargo"), &[]), Some(cargo));
+    }
+
+    /// Assert that `strip_suffixes` lets `cargo.exe` use the `cargo` profile only when `.exe` is
+    /// listed, and never strips a name down to nothing
+    #[test]
+    fn strip_suffixes_applied() {
+        let cargo = CommandName::try_from("cargo".to_owned()).unwrap();
+        let strip = [".cmd".to_owned(), ".exe".to_owned()];
+        assert_eq!(canonical_command_name(OsStr::new("/opt/bin/cargo.exe"), &strip), Some(cargo));
+        assert_eq!(
+            canonical_command_name(OsStr::new(".exe"), &strip),
+            Some(CommandName::try_from(".exe".to_owned()).unwrap())
+        );
+
+        let config_with = |setting: &str| -> Config {
+            toml_edit::de::from_str(&format!(
+                "firejail_base_flags = []\n{setting}\n\
+                 [profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                 projectless_subcommands = [\"new\"]"
+            ))
+            .unwrap()
+        };
+        let argv = argv(&["cargo.exe", "new"]);
+        let none = Overrides::default();
+        let stripping = config_with("strip_suffixes = [\".exe\"]");
+        assert_eq!(
+            stripping.resolve(&argv, Path::new("/"), &none).unwrap().command.to_string(),
+            "cargo"
+        );
+        assert_eq!(
+            config_with("").resolve(&argv, Path::new("/"), &none).unwrap_err(),
+            Error::NoProfile(CommandName::try_from("cargo.exe".to_owned()).unwrap())
+        );
+    }
+
+    /// Assert that `unknown_subcommand = "deny"` refuses only unlisted subcommands, while the
+    /// default runs them with the profile's defaults
+    #[test]
+    fn unknown_subcommand_policy() {
+        let config_with = |policy: &str| -> Config {
+            toml_edit::de::from_str(&format!(
+                "firejail_base_flags = []\n\
+                 [profile.cargo]\n\
+                 allow_network_subcommands = [\"fetch\"]\n\
+                 projectless_subcommands = [\"build\", \"fetch\", \"plugin\"]\n\
+                 root_marked_by = [\"Cargo.toml\"]\n\
+                 subcommand_aliases = {{ b = \"build\" }}\n\
+                 {policy}"
+            ))
+            .unwrap()
+        };
+        let cwd = Path::new("/");
+        let none = Overrides::default();
+        let cargo = CommandName::try_from("cargo".to_owned()).unwrap();
+
+        let deny = config_with("unknown_subcommand = \"deny\"\ndeny_subcommands = [\"publish\"]");
+        for known in [&["cargo", "fetch"][..], &["cargo", "b"], &["cargo"], &["cargo", "--version"]]
+        {
+            deny.resolve_unrooted(&argv(known), &none).unwrap();
+        }
+        assert!(deny.resolve_unrooted(&argv(&["cargo", "publish"]), &none).unwrap().denied);
+        assert_eq!(
+            deny.resolve_unrooted(&argv(&["cargo", "miri"]), &none).unwrap_err(),
+            Error::UnknownSubcommand(cargo, SubcommandName::try_from("miri".to_owned()).unwrap())
+        );
+
+        for policy in ["", "unknown_subcommand = \"sandbox\""] {
+            let config = config_with(policy);
+            let resolution = config.resolve(&argv(&["cargo", "plugin"]), cwd, &none).unwrap();
+            assert_eq!(resolution.network, caps::Network::ChildProcsOnly);
+            assert!(!resolution.denied);
+        }
+    }
+
+    /// Assert that flags in `argv[1]` aren't mistaken for subcommands
+    #[test]
+    fn raw_subcommand_skips_flags() {
+        let build = SubcommandName::try_from("build".to_owned()).unwrap();
+        assert_eq!(raw_subcommand(Some(OsStr::new("build"))), Some(build));
+        assert_eq!(raw_subcommand(Some(OsStr::new("--version"))), None);
+        assert_eq!(raw_subcommand(Some(OsStr::new("-j4"))), None);
+        assert_eq!(raw_subcommand(None), None);
+        assert_eq!(raw_subcommand(Some(&OsString::new())), None);
+    }
+
+    /// Assert that `find_project_root` honours both [`caps::ProjectRoot`] policies
+    #[test]
+    fn find_project_root_policies() {
+        let guard = test_dir("find_project_root");
+        let base = guard.path().to_owned();
+        let inner = base.join("outer/inner");
+        let start = inner.join("src/bin");
+        fs::create_dir_all(&start).unwrap();
+        fs::write(base.join("outer/Makefile"), "").unwrap();
+        fs::write(inner.join("Makefile"), "").unwrap();
+
+        let makefile = markers(&["Makefile"]);
+        assert_eq!(
+            find_project_root(&start, &makefile, caps::ProjectRoot::Innermost, &mut |_, _| ()),
+            Some(inner.clone())
+        );
+        assert_eq!(
+            find_project_root(&start, &makefile, caps::ProjectRoot::Outermost, &mut |_, _| ()),
+            Some(base.join("outer"))
+        );
+        assert_eq!(
+            find_project_root(
+                &start,
+                &markers(&["nodo-no-such-marker"]),
+                caps::ProjectRoot::Outermost,
+                &mut |_, _| ()
+            ),
+            None
+        );
+    }
+
+    /// Assert that several markers in the same directory count as a single match, so the result
+    /// doesn't depend on which markers are present or the order they're listed in
+    #[test]
+    fn find_project_root_markers_in_same_dir() {
+        let guard = test_dir("find_project_root_same_dir");
+        let base = guard.path().to_owned();
+        let project = base.join("project");
+        let start = project.join("src");
+        fs::create_dir_all(project.join(".git")).unwrap();
+        fs::create_dir_all(&start).unwrap();
+        fs::write(project.join("Makefile"), "").unwrap();
+
+        for policy in [caps::ProjectRoot::Innermost, caps::ProjectRoot::Outermost] {
+            for names in [&["Makefile", ".git"], &[".git", "Makefile"]] {
+                assert_eq!(
+                    find_project_root(&start, &markers(names), policy, &mut |_, _| ()),
+                    Some(project.clone()),
+                    "{policy:?} {names:?}"
+                );
+            }
+            assert_eq!(
+                find_project_root(&start, &markers(&["Makefile"]), policy, &mut |_, _| ()),
+                find_project_root(&start, &markers(&["Makefile", ".git"]), policy, &mut |_, _| ()),
+            );
+        }
+    }
+
+    /// Assert that a directory only matches `root_marked_by_all` if it holds every member of the
+    /// group, while `root_marked_by` still matches on any one
+    #[test]
+    fn find_project_root_all_of() {
+        let guard = test_dir("find_project_root_all_of");
+        let base = guard.path().to_owned();
+        let inner = base.join("outer/inner");
+        let start = inner.join("src");
+        fs::create_dir_all(&start).unwrap();
+        fs::create_dir_all(base.join("outer/.git")).unwrap();
+        fs::write(base.join("outer/Cargo.toml"), "").unwrap();
+        fs::write(inner.join("Cargo.toml"), "").unwrap();
+
+        let group = markers(&["Cargo.toml", ".git"]);
+        let policy = caps::ProjectRoot::Innermost;
+        let mut seen = Vec::new();
+        assert_eq!(
+            find_project_root_matching(&start, &[], &group, policy, &mut |dir, results| {
+                seen.push((dir.to_owned(), results.len()));
+            }),
+            Some(base.join("outer"))
+        );
+        assert_eq!(seen, [(start.clone(), 2), (inner.clone(), 2), (base.join("outer"), 2)]);
+
+        // ...whereas a single marker is enough for the "any of" list
+        assert_eq!(
+            find_project_root_matching(
+                &start,
+                &markers(&["Cargo.toml"]),
+                &group,
+                policy,
+                &mut |_, _| ()
+            ),
+            Some(inner.clone())
+        );
+        assert_eq!(
+            find_project_root_matching(
+                &start,
+                &[],
+                &markers(&["Cargo.toml", "nodo-no-such-marker"]),
+                caps::ProjectRoot::Outermost,
+                &mut |_, _| ()
+            ),
+            None
+        );
+    }
+
+    /// Assert that a content marker only matches a file containing its substring, so a same-named
+    /// file without it is skipped in favour of one further out
+    #[test]
+    fn find_project_root_content_marker() {
+        let guard = test_dir("find_project_root_content");
+        let base = guard.path().to_owned();
+        let poetry = base.join("poetry");
+        let start = poetry.join("plain/src");
+        fs::create_dir_all(&start).unwrap();
+        fs::write(poetry.join("pyproject.toml"), "[build-system]\n\n[tool.poetry]\n").unwrap();
+        fs::write(poetry.join("plain/pyproject.toml"), "[build-system]\n").unwrap();
+
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n\
+             [profile.poetry]\n\
+             root_marked_by = [{ name = \"pyproject.toml\", contains = \"[tool.poetry]\" }]",
+        )
+        .unwrap();
+        let command = CommandName::try_from("poetry".to_owned()).unwrap();
+        let marker = config.profile(&command).unwrap().root_marked_by();
+        assert_eq!(
+            find_project_root(&start, marker, caps::ProjectRoot::Innermost, &mut |_, _| ()),
+            Some(poetry.clone())
+        );
+
+        // A bare name still matches the nearer file regardless of its contents
+        assert_eq!(
+            find_project_root(
+                &start,
+                &markers(&["pyproject.toml"]),
+                caps::ProjectRoot::Innermost,
+                &mut |_, _| ()
+            ),
+            Some(poetry.join("plain"))
+        );
+    }
+
+    /// Assert that a root which doesn't really contain the working directory is refused
+    #[test]
+    fn resolve_rejects_root_outside_cwd() {
+        let config: Config = toml_edit::de::from_str(
+            "firejail_base_flags = []\n[profile.make]\nroot_marked_by = [\"Makefile\"]",
+        )
+        .unwrap();
+        let guard = test_dir("root_outside_cwd");
+        let base = guard.path().to_owned();
+        let project = base.join("project");
+        let elsewhere = base.join("elsewhere");
+        fs::create_dir_all(project.join("src")).unwrap();
+        fs::create_dir_all(&elsewhere).unwrap();
+        fs::write(project.join("Makefile"), "").unwrap();
+        std::os::unix::fs::symlink(&elsewhere, project.join("link")).unwrap();
+        let none = Overrides::default();
+
+        // The lexical walk from `project/link` finds `project`, but the real cwd is elsewhere
+        let cwd = project.join("link");
+        assert_eq!(
+            config.resolve(&argv(&["make"]), &cwd, &none).unwrap_err(),
+            Error::RootOutsideCwd(
+                CommandName::try_from("make".to_owned()).unwrap(),
+                project.clone(),
+                cwd
+            )
+        );
+
+        // ...while a genuine subdirectory is fine
+        let resolution = config.resolve(&argv(&["make"]), &project.join("src"), &none).unwrap();
+        assert_eq!(resolution.root, Some(project));
+    }
+}
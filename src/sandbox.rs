@@ -0,0 +1,1608 @@
+//! Construction and launching of the Firejail command line for a [`Resolution`]
+
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{self, SigSet, Signal};
+use nix::unistd::Pid;
+
+use crate::cli::ChildArgs;
+use crate::config::{BlacklistMode, CommandProfile, Config, ProjectAccess};
+use crate::error::Error;
+use crate::expand::{expand_path, Env};
+use crate::resolve::Resolution;
+use crate::types::{caps, Capability};
+
+/// The Firejail executable, as looked up via `$PATH`, unless `backend_path` is set
+pub const FIREJAIL: &str = "firejail";
+
+/// The socket protocols a sandbox may use, unless `allow_abstract_sockets` narrows them to Unix
+/// domain sockets in place of a network namespace
+///
+/// (Firejail merges repeated `--protocol` options rather than letting the last one win, which is
+/// why `--protocol` is generated here rather than accepted in `firejail_base_flags`.)
+pub const SOCKET_PROTOCOLS: &str = "unix,inet,inet6,netlink";
+
+/// The environment variable set for sandboxed commands so a nested `nodo` can detect them
+pub const ACTIVE_ENV_VAR: &str = "NODO_ACTIVE";
+
+/// Build the complete Firejail command line (starting with Firejail's own `argv[0]`) that
+/// sandboxes `args.child_argv` according to `resolution`
+///
+/// The flags are emitted in this order:
+///
+/// 1. Hard-coded flags, such as blacklisting `config_path` so the sandboxed command can't rewrite
+///    its own sandboxing policy
+/// 2. `--profile=` for the profile's `firejail_profile`, if any, so everything after it is layered
+///    on top of that base
+/// 3. `firejail_base_flags`, or the profile's `base_flags_override` if it has one (minus `--quiet`
+///    if either `args.debug` or the `backend_verbose` setting is set, and with `--caps.drop=all`
+///    replaced by `--caps.keep=` if the profile has `keep_caps`)
+/// 4. `--read-only=` for each of the `system_readonly` directories
+/// 5. Flags derived from the profile and the resolved project root
+/// 6. `--` followed by the child's command line, prefixed by an `unshare` wrapper for the
+///    profile's `map_uid`, a `/bin/sh -c` wrapper that sets its `umask`, and another which runs
+///    its `pre_exec` check first, if it has them (or, with `args.shell`, [`interactive_shell`]
+///    in place of the command line and without the `pre_exec` check, so a failing check can be
+///    investigated with exactly the flags the command would have run with)
+///
+/// # Errors
+///
+/// Fails if the profile's `whitelist` or `blacklist` entries can't be expanded using `env` or
+/// [`check_contradictions`] finds a problem.
+pub fn build_command(
+    config: &Config,
+    resolution: &Resolution<'_>,
+    config_path: Option<&Path>,
+    args: &ChildArgs,
+    env: &Env,
+) -> Result<Vec<OsString>, Error> {
+    check_contradictions(config, resolution, env)?;
+    let backend = config.backend_path().map_or_else(|| FIREJAIL.into(), OsString::from);
+    let mut command = vec![backend];
+
+    if let Some(path) = config_path {
+        command.push(path_flag("--blacklist=", path));
+    }
+
+    if let Some(profile) = resolution.profile.firejail_profile() {
+        command.push(format!("--profile={profile}").into());
+    }
+
+    command.extend(
+        resolution
+            .profile
+            .base_flags_override()
+            .unwrap_or_else(|| config.firejail_base_flags())
+            .iter()
+            .filter(|flag| !((args.debug || config.backend_verbose()) && *flag == "--quiet"))
+            .filter(|flag| resolution.profile.keep_caps().is_empty() || *flag != "--caps.drop=all")
+            .map(OsString::from),
+    );
+    if !resolution.profile.keep_caps().is_empty() {
+        let names: Vec<String> =
+            resolution.profile.keep_caps().iter().map(Capability::firejail_name).collect();
+        command.push(format!("--caps.keep={}", names.join(",")).into());
+    }
+    command.extend(config.system_readonly().iter().map(|dir| path_flag("--read-only=", dir)));
+
+    let mut protocols = SOCKET_PROTOCOLS;
+    let has_network = match resolution.network {
+        caps::Network::AllNetworks => true,
+        caps::Network::ChildProcsOnly => match resolution.profile.network_interface() {
+            Some(iface) if !iface.is_loopback() => {
+                command.push(format!("--net={iface}").into());
+                true
+            },
+            _ => {
+                match resolution.profile.allow_abstract_sockets() {
+                    caps::AbstractSockets::Isolated => command.push("--net=none".into()),
+                    caps::AbstractSockets::Shared => protocols = "unix",
+                }
+                false
+            },
+        },
+    };
+    command.push(format!("--protocol={protocols}").into());
+    if has_network {
+        command.extend(resolution.profile.dns().iter().map(|ip| format!("--dns={ip}").into()));
+    }
+
+    if let Some(timeout) = resolution.profile.timeout() {
+        command.push(format!("--timeout={timeout}").into());
+    }
+    if let Some(hostname) = resolution.profile.hostname() {
+        command.push(format!("--hostname={hostname}").into());
+    }
+    if !resolution.profile.private_bin().is_empty() {
+        let names: Vec<String> =
+            resolution.profile.private_bin().iter().map(ToString::to_string).collect();
+        command.push(format!("--private-bin={}", names.join(",")).into());
+    }
+
+    if let Some(ref root) = resolution.root {
+        command.push(path_flag("--whitelist=", root));
+        if resolution.profile.project_access() == ProjectAccess::ReadOnly {
+            command.push(path_flag("--read-only=", root));
+            for subdir in resolution.profile.writable_subdirs() {
+                command.push(path_flag("--read-write=", &subdir.join_to(root)));
+            }
+        }
+        let flag = match config.blacklist_mode() {
+            BlacklistMode::Hide => "--blacklist=",
+            BlacklistMode::ReadOnly => "--read-only=",
+        };
+        for entry in config.root_blacklist_for(resolution.profile) {
+            command.push(path_flag(flag, &entry.join_to(root)));
+        }
+    }
+
+    // Firejail keeps host paths unchanged inside the sandbox, so the host cwd (which `resolve`
+    // guarantees is inside the root, possibly the root itself) is also the child's cwd
+    if resolution.root.is_some() {
+        command.push(path_flag("--private-cwd=", &resolution.cwd));
+    }
+
+    for raw in resolution.profile.whitelist() {
+        command.push(path_flag("--whitelist=", &expand_path(raw, env)?));
+    }
+    for raw in resolution.profile.blacklist() {
+        command.push(path_flag("--blacklist=", &expand_path(raw, env)?));
+    }
+    let mut env_set = resolution.profile.env_set().clone();
+    env_set.extend(args.env.iter().map(|(key, value)| (key.clone(), value.clone())));
+    command.extend(env_set.iter().map(|(key, value)| format!("--env={key}={value}").into()));
+
+    command.push("--".into());
+    push_wrappers(&mut command, resolution.profile, !args.shell);
+    if args.shell {
+        command.push(interactive_shell(env));
+    } else {
+        command.extend(args.child_argv.iter().cloned());
+    }
+    Ok(command)
+}
+
+/// Append the wrappers the child's command line runs inside (see step 6 of [`build_command`]),
+/// leaving out the `pre_exec` check unless `check` is set
+fn push_wrappers(command: &mut Vec<OsString>, profile: &CommandProfile, check: bool) {
+    if let Some(uid) = profile.map_uid() {
+        command
+            .extend(["unshare", "--user", &format!("--map-user={uid}"), "--"].map(OsString::from));
+    }
+    if let Some(umask) = profile.umask() {
+        // `$0` is only used in the shell's error messages. (`Umask` is only ever octal digits, so
+        // it's safe to splice into the script.)
+        let script = format!("umask {umask} && exec \"$@\"");
+        command.extend(["/bin/sh", "-c", &script, env!("CARGO_PKG_NAME")].map(OsString::from));
+    }
+    if let Some(check) = profile.pre_exec().filter(|_| check) {
+        // A separate layer so its failure can be reported without disturbing the `umask` one
+        let check: Vec<String> = check.iter().map(|x| shell_quote(OsStr::new(x))).collect();
+        let script = format!(
+            "{} || {{ status=$?; echo \"$0: pre_exec check failed with status $status\" >&2; \
+             exit $status; }}; exec \"$@\"",
+            check.join(" ")
+        );
+        command.extend(["/bin/sh", "-c", &script, env!("CARGO_PKG_NAME")].map(OsString::from));
+    }
+}
+
+/// Check that nothing whitelisted is also blacklisted once `whitelist`, `blacklist`, and (with
+/// the `hide` `blacklist_mode`) `root_blacklist` have been expanded, since Firejail's behaviour
+/// when they overlap is undefined
+///
+/// An exact match or either entry being an ancestor of the other is an overlap, so blacklisting
+/// `~/.cargo/credentials.toml` while whitelisting `~/.cargo` is refused just like whitelisting
+/// `~/.ssh/config` while blacklisting `~/.ssh`. The project root itself isn't a `whitelist`
+/// entry, so hiding parts of it with `root_blacklist` is unaffected.
+///
+/// # Errors
+///
+/// Returns [`Error::Contradiction`] for the first overlap found, or any error from expanding the
+/// entries.
+pub fn check_contradictions(
+    config: &Config,
+    resolution: &Resolution<'_>,
+    env: &Env,
+) -> Result<(), Error> {
+    let mut blacklisted = Vec::new();
+    for raw in resolution.profile.blacklist() {
+        blacklisted.push((raw.clone(), expand_path(raw, env)?));
+    }
+    if let (Some(ref root), BlacklistMode::Hide) = (&resolution.root, config.blacklist_mode()) {
+        blacklisted.extend(
+            config
+                .root_blacklist_for(resolution.profile)
+                .into_iter()
+                .map(|entry| (entry.to_string(), entry.join_to(root))),
+        );
+    }
+    for raw in resolution.profile.whitelist() {
+        let whitelisted = expand_path(raw, env)?;
+        for (blacklist_raw, path) in &blacklisted {
+            let overlap = if whitelisted.starts_with(path) {
+                whitelisted.clone()
+            } else if path.starts_with(&whitelisted) {
+                path.clone()
+            } else {
+                continue;
+            };
+            return Err(Error::Contradiction(raw.clone(), blacklist_raw.clone(), overlap));
+        }
+    }
+    Ok(())
+}
+
+/// The stand-in for the project root in profiles written by [`export_profile`]
+pub const EXPORT_ROOT_PLACEHOLDER: &str = "/PROJECT_ROOT";
+
+/// Render the sandbox [`build_command`] would create for `resolution` as a standalone Firejail
+/// `.profile` file, for use with `firejail --profile=` outside of `nodo`
+///
+/// Firejail accepts its options in a profile with the leading `--` removed and the `=` replaced
+/// by a space, so this is mostly a change of syntax. What can't be expressed statically is
+/// handled as follows:
+///
+/// - The project root, which `nodo` finds anew on every run, is written as
+///   [`EXPORT_ROOT_PLACEHOLDER`], which must be replaced before use.
+/// - `--private-cwd` is left out, since it follows the working directory.
+/// - The `map_uid`, `umask`, and `pre_exec` wrappers are left out, since they're part of the
+///   command line rather than the sandbox.
+/// - `--profile=` becomes an `include`, which Firejail searches for the same way.
+///
+/// The header comment lists what's missing so the output can't be mistaken for the whole story.
+///
+/// # Errors
+///
+/// Fails if the profile's `whitelist` or `blacklist` entries can't be expanded using `env`.
+pub fn export_profile(
+    config: &Config,
+    resolution: Resolution<'_>,
+    config_path: Option<&Path>,
+    env: &Env,
+) -> Result<String, Error> {
+    let resolution = Resolution {
+        root: Some(PathBuf::from(EXPORT_ROOT_PLACEHOLDER)),
+        cwd: PathBuf::from(EXPORT_ROOT_PLACEHOLDER),
+        ..resolution
+    };
+    let argv = build_command(config, &resolution, config_path, &ChildArgs::default(), env)?;
+
+    let mut out = format!(
+        "# Firejail profile exported by {name} from its [profile.{command}] section\n\
+         #\n\
+         # Replace {EXPORT_ROOT_PLACEHOLDER} with the project's root directory before use, since\n\
+         # {name} finds it anew on every run. Not included: --private-cwd, map_uid, umask,\n\
+         # pre_exec, and network access granted only to specific subcommands.\n",
+        name = env!("CARGO_PKG_NAME"),
+        command = resolution.command,
+    );
+    for line in firejail_syntax(&argv).iter().filter(|x| !x.starts_with("private-cwd ")) {
+        out.push_str(line);
+        out.push('\n');
+    }
+    Ok(out)
+}
+
+/// Convert the Firejail options in `firejail_argv` (as produced by [`build_command`]) to the
+/// lines of a Firejail `.profile` file, one directive per line
+///
+/// The leading `--` is removed and the `=` replaced by a space (eg. `--net=none` becomes
+/// `net none`), except that `--profile=` becomes an `include`, which Firejail searches for the
+/// same way. (A bare name gets `.profile` appended, even if it already contains a dot, since
+/// that's what Firejail looks for.) The executable and everything after the `--` separator are
+/// left out.
+#[must_use]
+pub fn firejail_syntax(firejail_argv: &[OsString]) -> Vec<String> {
+    let mut lines = Vec::new();
+    for arg in firejail_argv.iter().skip(1).take_while(|x| *x != "--") {
+        let arg = arg.to_string_lossy();
+        let Some(option) = arg.strip_prefix("--") else { continue };
+        lines.push(match option.split_once('=') {
+            Some(("profile", profile))
+                if profile.starts_with('/') || profile.ends_with(".profile") =>
+            {
+                format!("include {profile}")
+            },
+            Some(("profile", profile)) => format!("include {profile}.profile"),
+            Some((name, value)) => format!("{name} {value}"),
+            None => option.to_owned(),
+        });
+    }
+    lines
+}
+
+/// Compare two command lines (as produced by [`build_command`]) argument by argument, returning
+/// every argument from either, in order, paired with `' '` if both have it, `'-'` if only `first`
+/// does, or `'+'` if only `second` does
+///
+/// This is a longest-common-subsequence diff, so a flag added in the middle shows up as a single
+/// `+` rather than shifting everything after it.
+#[must_use]
+pub fn diff_commands<'a>(first: &'a [OsString], second: &'a [OsString]) -> Vec<(char, &'a OsStr)> {
+    // `common[i][j]` is the length of the longest common subsequence of `first[i..]` and
+    // `second[j..]`
+    let mut common = vec![vec![0_usize; second.len() + 1]; first.len() + 1];
+    for (i, a) in first.iter().enumerate().rev() {
+        for (j, b) in second.iter().enumerate().rev() {
+            common[i][j] = if a == b {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    let mut lines = Vec::with_capacity(first.len().max(second.len()));
+    while i < first.len() || j < second.len() {
+        if i < first.len() && j < second.len() && first[i] == second[j] {
+            lines.push((' ', first[i].as_os_str()));
+            (i, j) = (i + 1, j + 1);
+        } else if j == second.len() || (i < first.len() && common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', first[i].as_os_str()));
+            i += 1;
+        } else {
+            lines.push(('+', second[j].as_os_str()));
+            j += 1;
+        }
+    }
+    lines
+}
+
+/// The shell `--shell` runs in place of the command: `$SHELL`, or `/bin/sh` if it's unset
+#[must_use]
+pub fn interactive_shell(env: &Env) -> OsString {
+    env.get("SHELL").unwrap_or_else(|| OsStr::new("/bin/sh")).to_owned()
+}
+
+/// Check that the paths [`build_command`] would expose to the sandbox exist and are accessible,
+/// returning a description of each problem found
+///
+/// This is for `--dry-run --check`, so nothing is refused. (Firejail itself is the authority on
+/// whether a path it's given is acceptable. This only catches mistakes like typos early.)
+#[must_use]
+pub fn check_paths(resolution: &Resolution<'_>, env: &Env) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(ref root) = resolution.root {
+        if !root.is_dir() {
+            problems.push(format!("Project root {} is not a directory", root.display()));
+        } else if let Err(err) = root.read_dir() {
+            problems.push(format!("Project root {} can't be read: {err}", root.display()));
+        }
+    }
+    for raw in resolution.profile.whitelist() {
+        match expand_path(raw, env) {
+            Ok(path) if !path.exists() => problems
+                .push(format!("whitelist entry {raw:?} ({}) does not exist", path.display())),
+            Ok(_) => (),
+            Err(err) => problems.push(err.to_string()),
+        }
+    }
+    problems
+}
+
+/// Where `--stdin` should connect the command's standard input
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Input {
+    /// Nothing, so reading gets end-of-file immediately (`--stdin null`)
+    Null,
+    /// A file to read from
+    File(PathBuf),
+}
+
+/// The command's standard streams, as redirected by `--stdin`, `--stdout`, and `--stderr`
+///
+/// Streams left as `None` are inherited from `nodo`. Firejail passes its own streams through to
+/// the command, so these apply to Firejail's messages too.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Redirects {
+    /// Where to read standard input from
+    pub stdin: Option<Input>,
+    /// The file to write standard output to, replacing its contents
+    pub stdout: Option<PathBuf>,
+    /// The file to write standard error to, replacing its contents
+    pub stderr: Option<PathBuf>,
+}
+
+impl Redirects {
+    /// Open the files to redirect to, as `(stdin, stdout, stderr)`
+    ///
+    /// # Errors
+    ///
+    /// Returns any error encountered while opening them, including when an output path is
+    /// a symlink, which [`check_redirects`] refuses but could have been planted since.
+    fn open(&self) -> io::Result<(Stdio, Stdio, Stdio)> {
+        let stdin = match self.stdin {
+            None => Stdio::inherit(),
+            Some(Input::Null) => Stdio::null(),
+            Some(Input::File(ref path)) => File::open(path)?.into(),
+        };
+        let create = |path: &PathBuf| {
+            OpenOptions::new()
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .custom_flags(nix::libc::O_NOFOLLOW)
+                .open(path)
+        };
+        let output = |path: Option<&PathBuf>| -> io::Result<Stdio> {
+            Ok(path.map(create).transpose()?.map_or_else(Stdio::inherit, Stdio::from))
+        };
+        Ok((stdin, output(self.stdout.as_ref())?, output(self.stderr.as_ref())?))
+    }
+}
+
+/// Check that the `--stdout` and `--stderr` paths in `redirects` are files the sandbox for
+/// `resolution` could write to itself
+///
+/// The files are opened by `nodo`, outside the sandbox, so without this a redirect could be used
+/// to write somewhere the profile doesn't allow. A path is acceptable if it's inside the project
+/// root (or, with a read-only root, one of its `writable_subdirs`) or a `whitelist` entry, isn't
+/// inside a `blacklist` or `root_blacklist` entry, and isn't a symlink, an existing read-only
+/// file, or a directory. (A symlink planted in the root could otherwise point anywhere.)
+/// Relative paths are taken relative to `resolution.cwd`.
+///
+/// # Errors
+///
+/// Returns [`Error::BadRedirect`] for the first unacceptable path, or any error from expanding
+/// the profile's `whitelist` and `blacklist` entries.
+pub fn check_redirects(
+    config: &Config,
+    resolution: &Resolution<'_>,
+    redirects: &Redirects,
+    env: &Env,
+) -> Result<(), Error> {
+    let resolve = |path: &Path| {
+        let path = resolution.cwd.join(path);
+        let dir = path.parent()?.canonicalize().ok()?;
+        Some(dir.join(path.file_name()?))
+    };
+    let real = |path: PathBuf| path.canonicalize().unwrap_or(path);
+
+    let mut writable = Vec::new();
+    let mut forbidden = Vec::new();
+    if let Some(ref root) = resolution.root {
+        if resolution.profile.project_access() == ProjectAccess::ReadOnly {
+            writable.extend(resolution.profile.writable_subdirs().iter().map(|x| x.join_to(root)));
+        } else {
+            writable.push(root.clone());
+        }
+        forbidden
+            .extend(config.root_blacklist_for(resolution.profile).iter().map(|x| x.join_to(root)));
+    }
+    for raw in resolution.profile.whitelist() {
+        writable.push(real(expand_path(raw, env)?));
+    }
+    for raw in resolution.profile.blacklist() {
+        forbidden.push(real(expand_path(raw, env)?));
+    }
+
+    for (flag, path) in [("--stdout", &redirects.stdout), ("--stderr", &redirects.stderr)] {
+        let Some(path) = path else { continue };
+        let acceptable = resolve(path).is_some_and(|target| {
+            let usable = fs::symlink_metadata(&target)
+                .map_or(true, |meta| meta.is_file() && !meta.permissions().readonly());
+            usable
+                && writable.iter().any(|dir| target.starts_with(dir))
+                && !forbidden.iter().any(|dir| target.starts_with(dir))
+        });
+        if !acceptable {
+            return Err(Error::BadRedirect(flag, path.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Run a command line produced by [`build_command`] and wait for it to exit
+///
+/// [`ACTIVE_ENV_VAR`] is set for the child so a `nodo` invoked inside the sandbox can tell, and
+/// its standard streams are connected as `redirects` says.
+///
+/// A `SIGINT` or `SIGTERM` sent to `nodo` while it waits is passed on to the child, which is then
+/// killed with `SIGKILL` if it hasn't exited once `grace` has passed. (So that neither can be
+/// missed, they stay blocked in the calling thread from just before the child is started until
+/// `nodo` exits.)
+///
+/// Returns the exit code `nodo` should pass along, following the shell convention of
+/// `128 + signal number` for children killed by a signal.
+///
+/// # Errors
+///
+/// Fails if `argv` is empty, a file in `redirects` couldn't be opened, the command couldn't be
+/// started, or waiting for it or signalling it failed.
+pub fn launch(argv: &[OsString], redirects: &Redirects, grace: Duration) -> io::Result<i32> {
+    let (program, rest) = argv.split_first().ok_or(io::ErrorKind::InvalidInput)?;
+    let (stdin, stdout, stderr) = redirects.open()?;
+
+    // `SIGCHLD` is included so the same wait notices the child exiting. (The child doesn't
+    // inherit the mask, since `std` clears it for the programs it starts.)
+    let signals = SigSet::from_iter([Signal::SIGINT, Signal::SIGTERM, Signal::SIGCHLD]);
+    signals.thread_block()?;
+    let mut child = Command::new(program)
+        .args(rest)
+        .env(ACTIVE_ENV_VAR, "1")
+        .stdin(stdin)
+        .stdout(stdout)
+        .stderr(stderr)
+        .spawn()?;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        match signals.wait()? {
+            Signal::SIGCHLD => (),
+            signal => break terminate(&mut child, signal, grace)?,
+        }
+    };
+    Ok(status.code().or_else(|| status.signal().map(|signal| 128 + signal)).unwrap_or(1))
+}
+
+/// Pass `signal` on to `child` and, if it's still running once `grace` has passed, kill it with
+/// `SIGKILL`, returning its exit status either way
+fn terminate(child: &mut Child, signal: Signal, grace: Duration) -> io::Result<ExitStatus> {
+    if let Some(status) = child.try_wait()? {
+        return Ok(status);
+    }
+    let pid = i32::try_from(child.id()).map_err(|_| io::ErrorKind::InvalidInput)?;
+    signal::kill(Pid::from_raw(pid), signal)?;
+    let deadline = Instant::now() + grace;
+    while Instant::now() < deadline {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    child.kill()?;
+    child.wait()
+}
+
+/// Ask the Firejail at `program` for its `--help` text, which lists every option it supports
+///
+/// # Errors
+///
+/// Returns any error encountered while attempting to run `program`.
+pub fn backend_help(program: &OsStr) -> io::Result<String> {
+    let output = Command::new(program).arg("--help").stdin(Stdio::null()).output()?;
+    let mut help = String::from_utf8_lossy(&output.stdout).into_owned();
+    help.push_str(&String::from_utf8_lossy(&output.stderr));
+    Ok(help)
+}
+
+/// Return the options in the Firejail part of `argv` (as built by [`build_command`]) which
+/// `help` (as returned by [`backend_help`]) never mentions
+///
+/// An option counts as mentioned if its name (eg. `--net` for `--net=none`) appears anywhere in
+/// `help` as a whole word. Each unsupported option is only listed once.
+#[must_use]
+pub fn unsupported_flags(argv: &[OsString], help: &str) -> Vec<String> {
+    // `.` counts so (eg.) `--seccomp.drop` doesn't vouch for `--seccomp`
+    let is_word_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '.';
+    let mentioned = |name: &str| {
+        help.match_indices(name).any(|(idx, _)| {
+            !help[..idx].ends_with(is_word_char)
+                && !help[idx + name.len()..].starts_with(is_word_char)
+        })
+    };
+    let mut unsupported: Vec<String> = Vec::new();
+    for arg in argv.iter().skip(1).take_while(|x| *x != "--") {
+        let arg = arg.to_string_lossy();
+        let name = arg.split_once('=').map_or(&*arg, |(name, _)| name);
+        if name.starts_with("--") && !mentioned(name) && !unsupported.iter().any(|x| x == name) {
+            unsupported.push(name.to_owned());
+        }
+    }
+    unsupported
+}
+
+/// Run the `pre_launch` command line on the host, returning its exit status
+///
+/// Unlike [`launch`], [`ACTIVE_ENV_VAR`] isn't set, since the command isn't sandboxed.
+///
+/// # Errors
+///
+/// Returns any error encountered while attempting to spawn the command, including `hook` being
+/// empty.
+pub fn run_pre_launch(hook: &[String]) -> io::Result<ExitStatus> {
+    let (program, rest) = hook.split_first().ok_or(io::ErrorKind::InvalidInput)?;
+    Command::new(program).args(rest).status()
+}
+
+/// Look `program` up the way `execvp` would, using the `$PATH` in `env`
+///
+/// Names containing a `/` are used as-is. Otherwise, the first regular file with an execute bit
+/// set in a `$PATH` entry wins.
+#[must_use]
+pub fn find_program(program: &OsStr, env: &Env) -> Option<PathBuf> {
+    if program.as_bytes().contains(&b'/') {
+        return Some(PathBuf::from(program));
+    }
+    std::env::split_paths(env.get("PATH")?).map(|dir| dir.join(program)).find(|candidate| {
+        fs::metadata(candidate).is_ok_and(|meta| meta.is_file() && meta.mode() & 0o111 != 0)
+    })
+}
+
+/// Return the path `program` resolves to (see [`find_program`]) if it has the setuid bit set
+///
+/// The sandbox can't meaningfully restrict a setuid binary (and Firejail's `nonewprivs` will stop
+/// it from gaining privileges at all), so this is worth warning about before launching.
+#[must_use]
+pub fn setuid_program(program: &OsStr, env: &Env) -> Option<PathBuf> {
+    find_program(program, env)
+        .filter(|path| fs::metadata(path).is_ok_and(|meta| meta.mode() & 0o4000 != 0))
+}
+
+/// Filesystem types (as named in `/proc/self/mountinfo`) which are backed by a network share
+const NETWORK_FILESYSTEMS: &[&str] = &[
+    "9p",
+    "afs",
+    "ceph",
+    "cifs",
+    "fuse.sshfs",
+    "glusterfs",
+    "ncpfs",
+    "nfs",
+    "nfs4",
+    "smb3",
+    "smbfs",
+];
+
+/// Return the type of the filesystem `path` is on, according to `mountinfo` (the contents of
+/// `/proc/self/mountinfo`), or `None` if no mount covers it
+///
+/// `path` should already be canonical, since mount points are matched as path prefixes and the
+/// last matching line wins, as later mounts shadow earlier ones.
+#[must_use]
+pub fn filesystem_type<'a>(mountinfo: &'a str, path: &Path) -> Option<&'a str> {
+    let mut best: Option<(usize, &str)> = None;
+    for line in mountinfo.lines() {
+        // The mount point is the 5th field and the type follows the `-` ending the optional fields
+        let mut fields = line.split(' ');
+        let Some(mount_point) = fields.nth(4) else { continue };
+        let Some(fs_type) = fields.skip_while(|x| *x != "-").nth(1) else { continue };
+        let mount_point = unescape_mountinfo(mount_point);
+        if path.starts_with(&mount_point)
+            && best.is_none_or(|(len, _)| mount_point.as_os_str().len() >= len)
+        {
+            best = Some((mount_point.as_os_str().len(), fs_type));
+        }
+    }
+    best.map(|(_, fs_type)| fs_type)
+}
+
+/// Decode the `\NNN` octal escapes the kernel uses for whitespace and backslashes in mountinfo
+fn unescape_mountinfo(field: &str) -> PathBuf {
+    let (bytes, mut out) = (field.as_bytes(), Vec::with_capacity(field.len()));
+    let mut idx = 0;
+    while idx < bytes.len() {
+        let escape =
+            bytes.get(idx + 1..idx + 4).filter(|x| x.iter().all(|b| (b'0'..=b'7').contains(b)));
+        match escape {
+            Some(digits) if bytes[idx] == b'\\' => {
+                out.push(
+                    digits.iter().fold(0_u8, |acc, b| acc.wrapping_mul(8).wrapping_add(b - b'0')),
+                );
+                idx += 4;
+            },
+            _ => {
+                out.push(bytes[idx]);
+                idx += 1;
+            },
+        }
+    }
+    PathBuf::from(OsStr::from_bytes(&out))
+}
+
+/// Return the filesystem type of `root` if it's a network filesystem, which Firejail's mount
+/// namespace can behave oddly with
+///
+/// This is best-effort. If `/proc/self/mountinfo` can't be read, nothing is reported.
+#[must_use]
+pub fn network_filesystem(root: &Path) -> Option<String> {
+    let mountinfo = fs::read_to_string("/proc/self/mountinfo").ok()?;
+    filesystem_type(&mountinfo, root)
+        .filter(|fs_type| NETWORK_FILESYSTEMS.contains(fs_type))
+        .map(str::to_owned)
+}
+
+/// Return `true` if `nodo` is itself running inside a sandbox created by [`launch`]
+#[must_use]
+pub fn is_nested() -> bool {
+    std::env::var_os(ACTIVE_ENV_VAR).is_some_and(|x| !x.is_empty())
+}
+
+/// Render a command line for display in a form that can be pasted into a POSIX shell
+///
+/// Non-UTF-8 arguments are rendered lossily, so this is for humans and not for re-parsing.
+#[must_use]
+pub fn format_argv(argv: &[OsString]) -> String {
+    let quoted: Vec<String> = argv.iter().map(|arg| shell_quote(arg)).collect();
+    quoted.join(" ")
+}
+
+/// Quote a single argument for [`format_argv`] (or a generated script) if it contains anything
+/// a shell might interpret
+///
+/// This is only lossless for UTF-8 arguments.
+fn shell_quote(arg: &OsStr) -> String {
+    let arg = arg.to_string_lossy();
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.into_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Build a `--flag=/some/path` argument without a lossy round-trip through `String`
+fn path_flag(flag: &str, path: &Path) -> OsString {
+    let mut arg = OsString::from(flag);
+    arg.push(path);
+    arg
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::resolve::Overrides;
+    use crate::test_util::test_dir;
+    use std::io::Read;
+    use std::path::PathBuf;
+
+    use toml_edit::de::from_str as toml_from_str;
+
+    /// Helper to build a command line for `child_argv` with a fixed root and config path
+    fn build(config: &Config, child_argv: &[&str], debug: bool) -> Vec<OsString> {
+        let args = ChildArgs {
+            debug,
+            child_argv: child_argv.iter().map(OsString::from).collect(),
+            ..ChildArgs::default()
+        };
+        let resolution = config.resolve(&args.child_argv, Path::new("/"), &args.overrides).unwrap();
+        let resolution = Resolution {
+            root: Some(PathBuf::from("/project")),
+            cwd: PathBuf::from("/project"),
+            ..resolution
+        };
+        let env: Env = [("HOME", "/home/user")].into_iter().collect();
+        build_command(config, &resolution, Some(Path::new("/conf/nodo.toml")), &args, &env).unwrap()
+    }
+
+    /// A minimal config whose `build` and `fetch` are projectless so resolution never needs to
+    /// search the filesystem
+    fn test_config() -> Config {
+        toml_from_str(
+            "firejail_base_flags = [\"--seccomp\", \"--quiet\"]\n\
+             root_blacklist = [\".git\"]\n\
+             [profile.cargo]\n\
+             allow_network_subcommands = [\"fetch\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             projectless_subcommands = [\"build\", \"fetch\"]",
+        )
+        .unwrap()
+    }
+
+    /// Parse a config with no base flags whose `cargo` profile has `build` projectless and
+    /// `extra` appended to its table
+    fn try_profile_config(extra: &str) -> Result<Config, toml_edit::de::Error> {
+        toml_from_str(&format!(
+            "firejail_base_flags = []\n\
+             [profile.cargo]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             projectless_subcommands = [\"build\"]\n\
+             {extra}"
+        ))
+    }
+
+    /// [`try_profile_config`] for tests which only ever give it valid settings
+    fn profile_config(extra: &str) -> Config {
+        try_profile_config(extra).unwrap()
+    }
+
+    /// The flags starting with `prefix` in the command line built for `cargo build`
+    fn flags_with_prefix(config: &Config, prefix: &str) -> Vec<OsString> {
+        build(config, &["cargo", "build"], false)
+            .into_iter()
+            .filter(|x| x.to_string_lossy().starts_with(prefix))
+            .collect()
+    }
+
+    /// Everything after the `--` in the command line built for `cargo build`
+    fn after_separator(config: &Config) -> Vec<OsString> {
+        let command = build(config, &["cargo", "build"], false);
+        let start = command.iter().position(|x| x == "--").unwrap();
+        command[start + 1..].to_vec()
+    }
+
+    /// Assert the ordering documented on [`build_command`]
+    #[test]
+    fn build_command_ordering() {
+        assert_eq!(
+            build(&test_config(), &["cargo", "build", "--release"], false),
+            [
+                "firejail",
+                "--blacklist=/conf/nodo.toml",
+                "--seccomp",
+                "--quiet",
+                "--read-only=/usr",
+                "--read-only=/etc",
+                "--read-only=/bin",
+                "--net=none",
+                "--protocol=unix,inet,inet6,netlink",
+                "--whitelist=/project",
+                "--blacklist=/project/.git",
+                "--private-cwd=/project",
+                "--",
+                "cargo",
+                "build",
+                "--release"
+            ]
+        );
+    }
+
+    /// Assert that the `system_readonly` defaults are mounted read-only and that setting it
+    /// replaces them
+    #[test]
+    fn build_command_system_readonly() {
+        let read_only = |setting: &str| -> Vec<OsString> {
+            let config: Config = toml_from_str(&format!(
+                "firejail_base_flags = []\n\
+                 {setting}\n\
+                 [profile.cargo]\n\
+                 root_marked_by = [\"nodo-test-marker\"]\n\
+                 projectless_subcommands = [\"build\"]"
+            ))
+            .unwrap();
+            flags_with_prefix(&config, "--read-only=")
+        };
+
+        assert_eq!(read_only(""), ["--read-only=/usr", "--read-only=/etc", "--read-only=/bin"]);
+        assert_eq!(
+            read_only("system_readonly = [\"/usr\", \"/opt\"]"),
+            ["--read-only=/usr", "--read-only=/opt"]
+        );
+        assert!(read_only("system_readonly = []").is_empty());
+    }
+
+    /// Assert that `blacklist_mode` picks the flag used for `root_blacklist` entries
+    #[test]
+    fn build_command_blacklist_mode() {
+        let config_with = |mode: &str| -> Config {
+            toml_from_str(&format!(
+                "firejail_base_flags = []\n\
+                 root_blacklist = [\".git\", \".hg\"]\n\
+                 {mode}\n\
+                 [profile.cargo]\n\
+                 root_marked_by = [\"nodo-test-marker\"]\n\
+                 projectless_subcommands = [\"build\"]"
+            ))
+            .unwrap()
+        };
+
+        let hide = ["--blacklist=/project/.git", "--blacklist=/project/.hg"];
+        assert_eq!(flags_with_prefix(&config_with(""), "--blacklist=/project/"), hide);
+        let config = config_with("blacklist_mode = \"hide\"");
+        assert_eq!(flags_with_prefix(&config, "--blacklist=/project/"), hide);
+        let config = config_with("blacklist_mode = \"read_only\"");
+        assert!(flags_with_prefix(&config, "--blacklist=/project/").is_empty());
+        assert_eq!(
+            flags_with_prefix(&config, "--read-only=/project/"),
+            ["--read-only=/project/.git", "--read-only=/project/.hg"]
+        );
+        assert!(toml_from_str::<Config>(
+            "firejail_base_flags = []\nblacklist_mode = \"append\"\nprofile = {}"
+        )
+        .is_err());
+    }
+
+    /// Assert that a profile's own `root_blacklist` entries follow the top-level ones (without
+    /// repeating any) for that command only
+    #[test]
+    fn build_command_profile_root_blacklist() {
+        let config: Config = toml_from_str(
+            "firejail_base_flags = []\n\
+             root_blacklist = [\".git\", \".hg\"]\n\
+             [profile.cargo]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             projectless_subcommands = [\"build\"]\n\
+             [profile.npm]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             projectless_subcommands = [\"install\"]\n\
+             root_blacklist = [\".env\", \".git\"]",
+        )
+        .unwrap();
+        let root_flags = |child_argv: &[&str]| -> Vec<OsString> {
+            build(&config, child_argv, false)
+                .into_iter()
+                .filter(|x| x.to_string_lossy().starts_with("--blacklist=/project/"))
+                .collect()
+        };
+
+        assert_eq!(
+            root_flags(&["npm", "install"]),
+            ["--blacklist=/project/.git", "--blacklist=/project/.hg", "--blacklist=/project/.env"]
+        );
+        assert_eq!(
+            root_flags(&["cargo", "build"]),
+            ["--blacklist=/project/.git", "--blacklist=/project/.hg"]
+        );
+    }
+
+    /// Assert that `--net=none` is only omitted when network access was granted
+    #[test]
+    fn build_command_network() {
+        let net_none = OsString::from("--net=none");
+        assert!(build(&test_config(), &["cargo", "build"], false).contains(&net_none));
+        assert!(!build(&test_config(), &["cargo", "fetch"], false).contains(&net_none));
+    }
+
+    /// Assert that `allow_abstract_sockets` swaps `--net=none` for `--protocol=unix` but has no
+    /// effect when network access is granted
+    #[test]
+    fn build_command_abstract_sockets() {
+        for isolated in
+            ["", "allow_abstract_sockets = false", "allow_abstract_sockets = \"isolated\""]
+        {
+            let config = profile_config(isolated);
+            assert_eq!(flags_with_prefix(&config, "--net"), ["--net=none"], "{isolated}");
+            assert_eq!(
+                flags_with_prefix(&config, "--protocol"),
+                ["--protocol=unix,inet,inet6,netlink"],
+                "{isolated}"
+            );
+        }
+        let shared = profile_config("allow_abstract_sockets = true");
+        assert!(flags_with_prefix(&shared, "--net").is_empty());
+        assert_eq!(flags_with_prefix(&shared, "--protocol"), ["--protocol=unix"]);
+
+        let networked = profile_config(
+            "allow_abstract_sockets = true\nallow_network_subcommands = [\"build\"]",
+        );
+        assert!(flags_with_prefix(&networked, "--net").is_empty());
+        assert_eq!(
+            flags_with_prefix(&networked, "--protocol"),
+            ["--protocol=unix,inet,inet6,netlink"]
+        );
+    }
+
+    /// Assert that `firejail_profile` comes after the hard-coded flags but before the base flags
+    #[test]
+    fn build_command_firejail_profile() {
+        let config = profile_config(
+            "firejail_profile = \"/etc/firejail/cargo.profile\"\n\
+             base_flags_override = [\"--seccomp\"]",
+        );
+        assert_eq!(
+            build(&config, &["cargo", "build"], false)[1..4],
+            ["--blacklist=/conf/nodo.toml", "--profile=/etc/firejail/cargo.profile", "--seccomp"]
+        );
+        assert!(flags_with_prefix(&test_config(), "--profile").is_empty());
+    }
+
+    /// Assert that `project_access = "read_only"` makes the root read-only except for the
+    /// `writable_subdirs`, which are re-granted after it and before `root_blacklist` is applied
+    #[test]
+    fn build_command_read_only_root() {
+        let config_with =
+            |access: &str| profile_config(&format!("root_blacklist = [\".git\"]\n{access}"));
+        let root_flags = |config: &Config| -> Vec<OsString> {
+            build(config, &["cargo", "build"], false)
+                .into_iter()
+                .filter(|x| x.to_string_lossy().contains("=/project"))
+                .collect()
+        };
+
+        assert_eq!(
+            root_flags(&config_with(
+                "project_access = \"read_only\"\nwritable_subdirs = [\"target\", \"tmp\"]"
+            )),
+            [
+                "--whitelist=/project",
+                "--read-only=/project",
+                "--read-write=/project/target",
+                "--read-write=/project/tmp",
+                "--blacklist=/project/.git",
+                "--private-cwd=/project",
+            ]
+        );
+        assert_eq!(
+            root_flags(&config_with("")),
+            ["--whitelist=/project", "--blacklist=/project/.git", "--private-cwd=/project"]
+        );
+    }
+
+    /// Assert that `base_flags_override` replaces the global base flags rather than adding to them
+    #[test]
+    fn build_command_base_flags_override() {
+        let config: Config = toml_from_str(
+            "firejail_base_flags = [\"--seccomp\", \"--quiet\"]\n\
+             [profile.cargo]\n\
+             base_flags_override = [\"--noroot\"]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             projectless_subcommands = [\"build\"]",
+        )
+        .unwrap();
+        assert_eq!(
+            build(&config, &["cargo", "build"], false)[1..3],
+            ["--blacklist=/conf/nodo.toml", "--noroot"]
+        );
+    }
+
+    /// Assert that `network_interface` replaces `--net=none` only when network access is
+    /// restricted, with loopback being equivalent to `--net=none`
+    #[test]
+    fn build_command_network_interface() {
+        let iface = |name: &str| format!("network_interface = \"{name}\"");
+
+        assert_eq!(flags_with_prefix(&profile_config(&iface("eth0")), "--net"), ["--net=eth0"]);
+        let networked =
+            profile_config(&format!("{}\nallow_network_subcommands = [\"build\"]", iface("eth0")));
+        assert!(flags_with_prefix(&networked, "--net").is_empty());
+        assert_eq!(flags_with_prefix(&profile_config(&iface("lo")), "--net"), ["--net=none"]);
+
+        // The boolean-only behaviour is unchanged
+        assert_eq!(flags_with_prefix(&profile_config(""), "--net"), ["--net=none"]);
+        let networked = profile_config("allow_network_subcommands = [\"build\"]");
+        assert!(flags_with_prefix(&networked, "--net").is_empty());
+
+        assert!(try_profile_config(&iface("eth0 --noroot")).is_err());
+    }
+
+    /// Assert that `diff_commands` reports insertions, removals, and changes without misaligning
+    /// the arguments around them
+    #[test]
+    fn diff_commands_aligns() {
+        let argv = |args: &[&str]| args.iter().map(OsString::from).collect::<Vec<_>>();
+        let diff = |first: &[&str], second: &[&str]| -> Vec<(char, String)> {
+            diff_commands(&argv(first), &argv(second))
+                .into_iter()
+                .map(|(marker, arg)| (marker, arg.to_string_lossy().into_owned()))
+                .collect()
+        };
+        let lines = |expected: &[(char, &str)]| -> Vec<(char, String)> {
+            expected.iter().map(|&(marker, arg)| (marker, arg.to_owned())).collect()
+        };
+
+        assert_eq!(diff(&["a", "b"], &["a", "b"]), lines(&[(' ', "a"), (' ', "b")]));
+        assert_eq!(
+            diff(&["a", "b", "c"], &["a", "x", "b", "c"]),
+            lines(&[(' ', "a"), ('+', "x"), (' ', "b"), (' ', "c")])
+        );
+        assert_eq!(
+            diff(&["a", "b", "c"], &["a", "y", "c"]),
+            lines(&[(' ', "a"), ('-', "b"), ('+', "y"), (' ', "c")])
+        );
+        assert_eq!(diff(&["a"], &[]), lines(&[('-', "a")]));
+        assert_eq!(diff(&[], &["a"]), lines(&[('+', "a")]));
+    }
+
+    /// Assert that `--stdout` and `--stderr` may only point where the sandbox could write itself
+    #[test]
+    fn redirects_checked_against_sandbox() {
+        let guard = test_dir("redirects");
+        let base = guard.path().to_owned();
+        let project = base.join("project");
+        for dir in ["project/.git", "project/target", "cache", "outside"] {
+            fs::create_dir_all(base.join(dir)).unwrap();
+        }
+        let config_with = |setting: &str| -> Config {
+            toml_from_str(&format!(
+                "firejail_base_flags = []\nroot_blacklist = [\".git\"]\n\
+                 [profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                 whitelist = [{:?}]\n{setting}",
+                base.join("cache").to_str().unwrap()
+            ))
+            .unwrap()
+        };
+        let check = |config: &Config, path: &str| {
+            let argv = [OsString::from("cargo")];
+            let resolution = config.resolve_unrooted(&argv, &Overrides::default()).unwrap();
+            let resolution =
+                Resolution { root: Some(project.clone()), cwd: project.clone(), ..resolution };
+            let redirects = Redirects { stderr: Some(path.into()), ..Redirects::default() };
+            check_redirects(config, &resolution, &redirects, &Env::default()).is_ok()
+        };
+
+        let config = config_with("");
+        assert!(check(&config, "build.log"));
+        assert!(check(&config, project.join("target/build.log").to_str().unwrap()));
+        assert!(check(&config, base.join("cache/build.log").to_str().unwrap()));
+        assert!(!check(&config, ".git/hooks"));
+        assert!(!check(&config, base.join("outside/build.log").to_str().unwrap()));
+        assert!(!check(&config, "../outside/build.log"));
+        assert!(!check(&config, "missing/build.log"));
+        assert!(!check(&config, "target"));
+
+        let read_only =
+            config_with("project_access = \"read_only\"\nwritable_subdirs = [\"target\"]");
+        assert!(!check(&read_only, "build.log"));
+        assert!(check(&read_only, "target/build.log"));
+    }
+
+    /// Assert that `backend_path` replaces the `firejail` looked up via `$PATH` and that one which
+    /// doesn't exist (or isn't executable) makes the configuration invalid
+    #[test]
+    fn build_command_backend_path() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let guard = test_dir("backend_path");
+        let base = guard.path();
+        let backend = base.join("firejail-git");
+        fs::write(&backend, "").unwrap();
+        fs::set_permissions(&backend, fs::Permissions::from_mode(0o755)).unwrap();
+        fs::write(base.join("not-executable"), "").unwrap();
+
+        let config_with = |path: &Path| -> Config {
+            toml_from_str(&format!(
+                "firejail_base_flags = []\nbackend_path = {:?}\n\
+                 [profile.cargo]\nroot_marked_by = [\"nodo-test-marker\"]\n\
+                 projectless_subcommands = [\"build\"]",
+                path.to_str().unwrap()
+            ))
+            .unwrap()
+        };
+        let config = config_with(&backend);
+        config.validate().unwrap();
+        assert_eq!(build(&config, &["cargo", "build"], false)[0], backend.as_os_str());
+        assert_eq!(build(&test_config(), &["cargo", "build"], false)[0], FIREJAIL);
+
+        for bad in [base.join("missing"), base.join("not-executable"), base.to_owned()] {
+            assert_eq!(
+                config_with(&bad).validate(),
+                Err(vec![format!(
+                    "'backend_path' must be the absolute path of an executable file: {}",
+                    bad.display()
+                )])
+            );
+        }
+    }
+
+    /// Assert that `keep_caps` replaces `--caps.drop=all` with `--caps.keep=` and that unknown
+    /// capabilities are refused when the configuration is parsed
+    #[test]
+    fn build_command_keep_caps() {
+        let config_with = |keep: &str| {
+            try_profile_config(&format!(
+                "base_flags_override = [\"--caps.drop=all\", \"--seccomp\"]\nkeep_caps = [{keep}]"
+            ))
+        };
+
+        assert_eq!(flags_with_prefix(&config_with("").unwrap(), "--caps"), ["--caps.drop=all"]);
+        let config = config_with("\"CAP_NET_BIND_SERVICE\", \"CAP_CHOWN\"").unwrap();
+        assert_eq!(flags_with_prefix(&config, "--caps"), ["--caps.keep=net_bind_service,chown"]);
+        let err = config_with("\"CAP_NET_BIND\"").unwrap_err().to_string();
+        assert!(err.contains("capabilities(7)"), "{err}");
+    }
+
+    /// Assert that `private_bin` is passed to Firejail as a single comma-separated list, in the
+    /// order given, and left out entirely when empty
+    #[test]
+    fn build_command_private_bin() {
+        let private_bin_flags = |list: &str| {
+            flags_with_prefix(&profile_config(&format!("private_bin = {list}")), "--private-bin")
+        };
+
+        assert_eq!(
+            private_bin_flags("[\"cargo\", \"rustc\", \"cc\"]"),
+            ["--private-bin=cargo,rustc,cc"]
+        );
+        assert_eq!(private_bin_flags("[\"make\"]"), ["--private-bin=make"]);
+        assert!(private_bin_flags("[]").is_empty());
+    }
+
+    /// Assert that `hostname` is passed to Firejail and an invalid one is refused when the
+    /// configuration is parsed
+    #[test]
+    fn build_command_hostname() {
+        let config_with =
+            |hostname: &str| try_profile_config(&format!("hostname = \"{hostname}\""));
+
+        let config = config_with("builder").unwrap();
+        assert_eq!(flags_with_prefix(&config, "--hostname"), ["--hostname=builder"]);
+        assert!(flags_with_prefix(&test_config(), "--hostname").is_empty());
+        for bad in ["build_host", &"a".repeat(64)] {
+            let err = config_with(bad).unwrap_err().to_string();
+            assert!(err.contains("host name"), "{bad:?}: {err}");
+        }
+    }
+
+    /// Assert that `dns` servers are only passed along when the invocation has network access
+    #[test]
+    fn build_command_dns() {
+        let dns = "dns = [\"9.9.9.9\", \"2620:fe::fe\"]";
+        let networked = profile_config(&format!("{dns}\nallow_network_subcommands = [\"build\"]"));
+        assert_eq!(
+            build(&networked, &["cargo", "build"], false)[6..8],
+            ["--dns=9.9.9.9", "--dns=2620:fe::fe"]
+        );
+
+        assert!(flags_with_prefix(&profile_config(dns), "--dns").is_empty());
+    }
+
+    /// Assert that a profile's `timeout` is passed through as `--timeout`
+    #[test]
+    fn build_command_timeout() {
+        let timeout = OsString::from("--timeout=01:30:00");
+        assert!(!build(&test_config(), &["cargo", "build"], false).contains(&timeout));
+
+        let config = profile_config("timeout = \"01:30:00\"");
+        assert!(build(&config, &["cargo", "build"], false).contains(&timeout));
+    }
+
+    /// Assert that a profile's `whitelist` and `blacklist` are expanded after the root flags
+    #[test]
+    fn build_command_expanded_paths() {
+        let config = profile_config("whitelist = [\"~/.cargo\"]\nblacklist = [\"~/.ssh\"]");
+        let command = build(&config, &["cargo", "build"], false);
+        assert_eq!(
+            command[7..11],
+            [
+                "--whitelist=/project",
+                "--private-cwd=/project",
+                "--whitelist=/home/user/.cargo",
+                "--blacklist=/home/user/.ssh"
+            ]
+        );
+    }
+
+    /// Assert that `env_set` entries become `--env=` flags just before the child's command line
+    #[test]
+    fn build_command_env_set() {
+        let config = profile_config(
+            "env_set = { CARGO_HOME = \"/opt/cargo\", RUSTFLAGS = \"-C debuginfo=0\" }",
+        );
+        let command = build(&config, &["cargo", "build"], false);
+        let end = command.len() - 3;
+        assert_eq!(
+            command[end - 2..],
+            [
+                "--env=CARGO_HOME=/opt/cargo",
+                "--env=RUSTFLAGS=-C debuginfo=0",
+                "--",
+                "cargo",
+                "build"
+            ]
+        );
+    }
+
+    /// Assert that a `umask` wraps the child in a shell which sets it
+    #[test]
+    fn build_command_umask() {
+        assert_eq!(
+            after_separator(&profile_config("umask = \"0077\"")),
+            ["/bin/sh", "-c", "umask 0077 && exec \"$@\"", "nodo", "cargo", "build"]
+        );
+    }
+
+    /// Assert that `pre_exec` wraps the command in a shell which runs the quoted check first, in
+    /// its own layer inside any `umask` wrapper so the check gets the umask too
+    #[test]
+    fn build_command_pre_exec() {
+        let config_with = |extra: &str| {
+            profile_config(&format!("pre_exec = [\"rustc\", \"--print\", \"it's\"]\n{extra}"))
+        };
+        let script = "rustc --print 'it'\\''s' || { status=$?; echo \"$0: pre_exec check failed \
+                      with status $status\" >&2; exit $status; }; exec \"$@\"";
+
+        assert_eq!(
+            after_separator(&config_with("")),
+            ["/bin/sh", "-c", script, "nodo", "cargo", "build"]
+        );
+        assert_eq!(
+            after_separator(&config_with("umask = \"0077\"")),
+            [
+                "/bin/sh",
+                "-c",
+                "umask 0077 && exec \"$@\"",
+                "nodo",
+                "/bin/sh",
+                "-c",
+                script,
+                "nodo",
+                "cargo",
+                "build"
+            ]
+        );
+
+        let problems = config_with("").validate();
+        assert!(problems.is_ok());
+        let empty: Config = toml_from_str(
+            "firejail_base_flags = []\n[profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+             pre_exec = []",
+        )
+        .unwrap();
+        assert_eq!(
+            empty.validate().unwrap_err(),
+            ["[profile.cargo] 'pre_exec' must start with a non-empty command name"]
+        );
+    }
+
+    /// Assert that `map_uid` wraps the command in `unshare` only when set, outside any `umask`
+    /// wrapper so the uid applies to it too
+    #[test]
+    fn build_command_map_uid() {
+        assert_eq!(after_separator(&profile_config("")), ["cargo", "build"]);
+        assert_eq!(
+            after_separator(&profile_config("map_uid = 1500")),
+            ["unshare", "--user", "--map-user=1500", "--", "cargo", "build"]
+        );
+        assert_eq!(
+            after_separator(&profile_config("map_uid = 1500\numask = \"0077\"")),
+            [
+                "unshare",
+                "--user",
+                "--map-user=1500",
+                "--",
+                "/bin/sh",
+                "-c",
+                "umask 0077 && exec \"$@\"",
+                "nodo",
+                "cargo",
+                "build"
+            ]
+        );
+    }
+
+    /// Assert that the child is started in the same directory `nodo` was run from
+    #[test]
+    fn build_command_private_cwd() {
+        let config = test_config();
+        let args =
+            ChildArgs { child_argv: vec!["cargo".into(), "build".into()], ..ChildArgs::default() };
+        let resolution = config.resolve(&args.child_argv, Path::new("/"), &args.overrides).unwrap();
+        let resolution = Resolution {
+            root: Some(PathBuf::from("/project")),
+            cwd: PathBuf::from("/project/crates/inner"),
+            ..resolution
+        };
+        let command = build_command(&config, &resolution, None, &args, &Env::default()).unwrap();
+        assert!(command.contains(&OsString::from("--private-cwd=/project/crates/inner")));
+    }
+
+    /// Assert that `--debug` removes `--quiet` and nothing else
+    #[test]
+    fn build_command_debug_omits_quiet() {
+        let quiet = build(&test_config(), &["cargo", "build"], false);
+        let mut debug = build(&test_config(), &["cargo", "build"], true);
+        assert!(!debug.contains(&OsString::from("--quiet")));
+
+        debug.insert(3, "--quiet".into());
+        assert_eq!(quiet, debug);
+    }
+
+    /// Assert that `--quiet` survives only when neither `backend_verbose` nor `--debug` is set
+    #[test]
+    fn build_command_backend_verbose_matrix() {
+        let verbose: Config = toml_from_str(
+            "firejail_base_flags = [\"--seccomp\", \"--quiet\"]\n\
+             backend_verbose = true\n\
+             [profile.cargo]\n\
+             root_marked_by = [\"nodo-test-marker\"]\n\
+             projectless_subcommands = [\"build\"]",
+        )
+        .unwrap();
+        let has_quiet = |config: &Config, debug: bool| {
+            build(config, &["cargo", "build"], debug).contains(&OsString::from("--quiet"))
+        };
+
+        assert!(has_quiet(&test_config(), false));
+        assert!(!has_quiet(&test_config(), true));
+        assert!(!has_quiet(&verbose, false));
+        assert!(!has_quiet(&verbose, true));
+    }
+
+    /// Assert that [`format_argv`] only quotes when necessary and survives embedded quotes
+    #[test]
+    fn format_argv_quoting() {
+        let argv: Vec<OsString> = ["firejail", "--net=none", "two words", "it's", ""]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(format_argv(&argv), r"firejail --net=none 'two words' 'it'\''s' ''");
+    }
+
+    /// Assert that the most specific mount wins, escapes are decoded, and network types are told
+    /// apart from local ones
+    #[test]
+    fn filesystem_type_from_mountinfo() {
+        let mountinfo = "\
+            22 1 8:1 / / rw,relatime shared:1 - ext4 /dev/sda1 rw\n\
+            40 22 0:40 / /home rw,relatime shared:20 - btrfs /dev/sda2 rw\n\
+            51 40 0:51 / /home/user/net rw,relatime shared:31 master:4 - nfs4 srv:/export rw\n\
+            52 40 0:52 / /home/user/my\\040share rw - cifs //srv/share rw\n\
+            garbage line\n";
+        let fs_type = |path: &str| filesystem_type(mountinfo, Path::new(path));
+
+        assert_eq!(fs_type("/usr/src"), Some("ext4"));
+        assert_eq!(fs_type("/home/user/project"), Some("btrfs"));
+        assert_eq!(fs_type("/home/user/net/project"), Some("nfs4"));
+        assert_eq!(fs_type("/home/user/my share/project"), Some("cifs"));
+        // Prefixes are matched per-component, not per-character
+        assert_eq!(fs_type("/home/user/network"), Some("btrfs"));
+        assert_eq!(filesystem_type("", Path::new("/")), None);
+
+        assert!(NETWORK_FILESYSTEMS.contains(&"nfs4"));
+        assert!(!NETWORK_FILESYSTEMS.contains(&"btrfs"));
+    }
+
+    /// Assert that `--profile=` becomes an `include` of the file Firejail would load, without
+    /// mistaking part of a dotted name for an extension
+    #[test]
+    fn firejail_syntax_profile_include() {
+        let include = |profile: &str| {
+            firejail_syntax(&["firejail".into(), format!("--profile={profile}").into()])
+        };
+        assert_eq!(include("cargo"), ["include cargo.profile"]);
+        assert_eq!(include("org.app"), ["include org.app.profile"]);
+        assert_eq!(include("foo.local"), ["include foo.local.profile"]);
+        assert_eq!(include("cargo.profile"), ["include cargo.profile"]);
+        assert_eq!(include("/etc/firejail/cargo.inc"), ["include /etc/firejail/cargo.inc"]);
+    }
+
+    /// Assert that an exported profile uses `.profile` syntax, stands in a placeholder for the
+    /// root, and leaves out what only makes sense on the command line
+    #[test]
+    fn export_profile_directives() {
+        let config: Config = toml_from_str(
+            "firejail_base_flags = [\"--seccomp\"]\n\
+             root_blacklist = [\".git\"]\n\
+             [profile.cargo]\n\
+             root_marked_by = [\"Cargo.toml\"]\n\
+             firejail_profile = \"cargo\"\n\
+             umask = \"0077\"\n\
+             whitelist = [\"~/.cargo\"]",
+        )
+        .unwrap();
+        let resolution =
+            config.resolve_unrooted(&["cargo".into()], &crate::resolve::Overrides::default());
+        let env: Env = [("HOME", "/home/user")].into_iter().collect();
+        let exported =
+            export_profile(&config, resolution.unwrap(), Some(Path::new("/conf/nodo.toml")), &env)
+                .unwrap();
+
+        let directives: Vec<&str> = exported.lines().filter(|x| !x.starts_with('#')).collect();
+        assert_eq!(
+            directives,
+            [
+                "blacklist /conf/nodo.toml",
+                "include cargo.profile",
+                "seccomp",
+                "read-only /usr",
+                "read-only /etc",
+                "read-only /bin",
+                "net none",
+                "protocol unix,inet,inet6,netlink",
+                "whitelist /PROJECT_ROOT",
+                "blacklist /PROJECT_ROOT/.git",
+                "whitelist /home/user/.cargo",
+            ]
+        );
+        assert!(exported.starts_with("# Firejail profile exported by"), "{exported}");
+        assert!(exported.contains("Replace /PROJECT_ROOT with"), "{exported}");
+    }
+
+    /// Assert that whitelisting a path that's blacklisted, inside one that is, or containing one
+    /// that is, is refused once `~` and the root have been expanded
+    #[test]
+    fn contradictions_detected() {
+        let check = |profile: &str| {
+            let config: Config = toml_from_str(&format!(
+                "firejail_base_flags = []\nroot_blacklist = [\".git\"]\n\
+                 [profile.make]\nprojectless_subcommands = [\"all\"]\n\
+                 root_marked_by = [\"Makefile\"]\n{profile}"
+            ))
+            .unwrap();
+            let argv = ["make".into(), "all".into()];
+            let resolution =
+                config.resolve(&argv, Path::new("/"), &crate::resolve::Overrides::default());
+            let resolution = Resolution {
+                root: Some(PathBuf::from("/project")),
+                cwd: PathBuf::from("/project"),
+                ..resolution.unwrap()
+            };
+            let env: Env = [("HOME", "/home/user")].into_iter().collect();
+            check_contradictions(&config, &resolution, &env)
+        };
+        let contradiction = |whitelisted: &str, blacklisted: &str, path: &str| {
+            Err(Error::Contradiction(whitelisted.into(), blacklisted.into(), path.into()))
+        };
+
+        check("whitelist = [\"~/.cargo\"]\nblacklist = [\"~/.ssh\"]").unwrap();
+        assert_eq!(
+            check("whitelist = [\"~/.ssh\"]\nblacklist = [\"/home/user/.ssh\"]"),
+            contradiction("~/.ssh", "/home/user/.ssh", "/home/user/.ssh")
+        );
+        assert_eq!(
+            check("whitelist = [\"~/.ssh/config\"]\nblacklist = [\"~/.ssh\"]"),
+            contradiction("~/.ssh/config", "~/.ssh", "/home/user/.ssh/config")
+        );
+        assert_eq!(
+            check("whitelist = [\"~\"]\nblacklist = [\"~/.ssh\"]"),
+            contradiction("~", "~/.ssh", "/home/user/.ssh")
+        );
+        assert_eq!(
+            check("whitelist = [\"/project/.git/hooks\"]"),
+            contradiction("/project/.git/hooks", ".git", "/project/.git/hooks")
+        );
+
+        assert_eq!(
+            check("whitelist = [\"/project\"]"),
+            contradiction("/project", ".git", "/project/.git")
+        );
+
+        // Prefixes are compared per-component, not per-character
+        check("whitelist = [\"/project/.github\"]").unwrap();
+        let err = check("whitelist = [\"/project/.git\"]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "whitelist entry \"/project/.git\" and blacklist entry \".git\" overlap at \
+             /project/.git, which leaves what Firejail will do undefined"
+        );
+    }
+
+    /// Assert that only options `--help` doesn't mention as whole words are reported, by name and
+    /// once each, and that nothing after `--` is considered
+    #[test]
+    fn unsupported_flags_from_help() {
+        let help = "Usage: firejail [options] [program and arguments]\n\
+                    \x20   --blacklist=filename - blacklist directory or file.\n\
+                    \x20   --net=none - enable a new, unconnected network namespace.\n\
+                    \x20   --seccomp.drop=syscall - enable seccomp filter.\n";
+        let argv: Vec<OsString> = [
+            "firejail",
+            "--blacklist=/conf/nodo.toml",
+            "--net=none",
+            "--seccomp",
+            "--whitelist=/a",
+            "--whitelist=/b",
+            "--",
+            "cargo",
+            "--frobnicate",
+        ]
+        .map(Into::into)
+        .into();
+        assert_eq!(unsupported_flags(&argv, help), ["--seccomp", "--whitelist"]);
+        assert_eq!(unsupported_flags(&argv[..3], help), Vec::<String>::new());
+    }
+
+    /// Assert that a child which ignores the signal passed on is killed with `SIGKILL` once the
+    /// grace period is up, while one which doesn't exits on it without waiting for it
+    #[test]
+    fn terminate_escalates_after_grace() {
+        let spawn = |script: &str| {
+            let mut child = Command::new("/bin/sh")
+                .args(["-c", script])
+                .stdout(Stdio::piped())
+                .spawn()
+                .unwrap();
+            // Wait until the script says it's ready so `SIGTERM` can't beat the `trap`
+            let mut ready = [0u8];
+            child.stdout.take().unwrap().read_exact(&mut ready).unwrap();
+            child
+        };
+        let grace = Duration::from_millis(300);
+
+        let started = Instant::now();
+        let status =
+            terminate(&mut spawn("trap '' TERM; echo; exec sleep 30"), Signal::SIGTERM, grace)
+                .unwrap();
+        assert_eq!(status.signal(), Some(9));
+        assert!(started.elapsed() >= grace);
+
+        let started = Instant::now();
+        let status =
+            terminate(&mut spawn("echo; exec sleep 30"), Signal::SIGINT, Duration::from_secs(30))
+                .unwrap();
+        assert_eq!(status.signal(), Some(2));
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+}
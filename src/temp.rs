@@ -0,0 +1,119 @@
+//! Creation and cleanup of temporary files and directories created while preparing a sandbox
+
+use std::path::{Path, PathBuf};
+use std::{env, fs, process};
+
+use crate::report::Fatal;
+
+/// The environment variable which, if set, overrides where temporary directories are created
+/// (otherwise `TMPDIR`, falling back to `/tmp`, as per [`std::env::temp_dir`])
+pub const TEMP_ENV_VAR: &str = "NODO_TMPDIR";
+
+/// Return the directory temporary directories will be created in
+#[must_use]
+pub fn temp_base() -> PathBuf {
+    env::var_os(TEMP_ENV_VAR).filter(|x| !x.is_empty()).map_or_else(env::temp_dir, PathBuf::from)
+}
+
+/// Removes the file or directory at its path when dropped
+///
+/// Because `nodo` forbids `unsafe`, there are no signal handlers backing this up. Cleanup is
+/// best-effort and happens whenever the guard goes out of scope, including when the child exits
+/// abnormally, so anything holding one must be dropped before `std::process::exit` is called.
+#[derive(Debug)]
+pub struct TempGuard(PathBuf);
+
+impl TempGuard {
+    /// Create a new, empty directory named `name` (with the process ID appended) in
+    /// [`temp_base`] and take responsibility for removing it
+    ///
+    /// The returned path has been canonicalized, so it can be compared against paths found
+    /// inside it.
+    ///
+    /// # Errors
+    ///
+    /// Fails with a `temp_dir` report giving the attempted path if the directory couldn't be
+    /// created (eg. because the base is missing, read-only, or full). This includes it already
+    /// existing, since a directory someone else created in a shared `/tmp` can't be trusted.
+    pub fn create_dir(name: &str) -> Result<Self, Fatal> {
+        Self::create_dir_in(&temp_base(), name)
+    }
+
+    /// [`Self::create_dir`], but in `base` rather than [`temp_base`]
+    fn create_dir_in(base: &Path, name: &str) -> Result<Self, Fatal> {
+        let path = base.join(format!("{name}-{}", process::id()));
+        let fail = |err| {
+            let message =
+                format!("Could not create a temporary directory at {}: {err}", path.display());
+            Fatal::new("temp_dir", message).with_path(Some(&path))
+        };
+        fs::create_dir(&path).map_err(fail)?;
+        path.canonicalize().map(Self).map_err(|err| {
+            let _ = fs::remove_dir(&path);
+            fail(err)
+        })
+    }
+
+    /// Take responsibility for removing `path`, which the caller has already created
+    #[must_use]
+    pub fn new(path: PathBuf) -> Self {
+        Self(path)
+    }
+
+    /// The path which will be removed
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempGuard {
+    fn drop(&mut self) {
+        // Errors are ignored because there's nothing useful to do about them during cleanup
+        let _ =
+            if self.0.is_dir() { fs::remove_dir_all(&self.0) } else { fs::remove_file(&self.0) };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Assert that dropping the guard removes both files and directory trees
+    #[test]
+    fn drop_removes_path() {
+        let file = env::temp_dir().join(format!("nodo-tempguard-{}", std::process::id()));
+        fs::write(&file, "").unwrap();
+        drop(TempGuard::new(file.clone()));
+        assert!(!file.exists());
+
+        let dir = env::temp_dir().join(format!("nodo-tempguard-dir-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        fs::write(dir.join("nested").join("file"), "").unwrap();
+        let guard = TempGuard::new(dir.clone());
+        assert_eq!(guard.path(), dir);
+        drop(guard);
+        assert!(!dir.exists());
+    }
+
+    /// Assert that directories are created fresh under the base given and that failing to
+    /// create one is reported with the attempted path rather than panicking
+    #[test]
+    fn create_dir_reports_failure() {
+        let guard = TempGuard::create_dir_in(&env::temp_dir(), "nodo-tempguard-create").unwrap();
+        assert!(guard.path().is_dir());
+        assert!(TempGuard::create_dir_in(&env::temp_dir(), "nodo-tempguard-create").is_err());
+        let path = guard.path().to_owned();
+        drop(guard);
+        assert!(!path.exists());
+
+        // Read-only even for root, unlike a directory with its write permission removed
+        let err = TempGuard::create_dir_in(Path::new("/proc"), "nodo-tempguard").unwrap_err();
+        let expected = format!("/proc/nodo-tempguard-{}", process::id());
+        assert_eq!(err.kind, "temp_dir");
+        assert_eq!(err.path.as_deref(), Some(expected.as_str()));
+        assert!(err
+            .message
+            .starts_with(&format!("Could not create a temporary directory at {expected}: ")));
+    }
+}
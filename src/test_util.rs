@@ -0,0 +1,18 @@
+//! Helpers shared between the unit tests of several modules
+
+use std::{env, fs, process};
+
+use crate::temp::TempGuard;
+
+/// Create a fresh, empty directory for the test named `name` and return a guard which removes it
+/// (and everything in it) when dropped, so it's cleaned up even if an assertion fails first
+///
+/// The process ID is included in the path so concurrent test runs can't collide, and the path is
+/// canonicalized so it can be compared against paths found inside it.
+pub fn test_dir(name: &str) -> TempGuard {
+    let path = env::temp_dir().join(format!("nodo_test_{name}_{}", process::id()));
+    // Left over from a run which was killed before it could clean up
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    TempGuard::new(path.canonicalize().unwrap())
+}
@@ -1,8 +1,10 @@
 //! Data types shared between the configuration schema and the actual internal APIs
 
-use std::path;
+use std::io::Read;
+use std::{fmt, fs, path};
 
-use serde_derive::Deserialize;
+use serde::de::{self, Deserializer, MapAccess, Visitor};
+use serde_derive::{Deserialize, Serialize};
 
 pub mod caps;
 
@@ -17,11 +19,17 @@ pub mod caps;
 ///
 /// This makes it more difficult to circumvent the protections afforded by using newtypes and makes
 /// apparent the need to do things like normalizing `argv[0]` before checking it.
+///
+/// (`Display` and `Serialize` are implemented only so the values can appear in output meant for
+/// the user or for tooling.)
 macro_rules! newtype {
     ($newtype:ident, $docstring:expr) => {
+        newtype!($newtype, $docstring, is_bad_name);
+    };
+    ($newtype:ident, $docstring:expr, $validator:ident) => {
         #[doc = "Newtype for "]
         #[doc=$docstring]
-        #[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
+        #[derive(Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
         #[serde(try_from = "String")]
         pub struct $newtype(String);
 
@@ -29,16 +37,168 @@ macro_rules! newtype {
             type Error = &'static str;
 
             fn try_from(value: String) -> Result<Self, Self::Error> {
-                is_bad_name(&value)?;
+                $validator(&value)?;
                 Ok($newtype(value))
             }
         }
+
+        impl fmt::Display for $newtype {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
     };
 }
 
 newtype!(FileName, "values like `root_marked_by` (too restrictive for `argv[2]` and beyond)");
 newtype!(CommandName, "`argv[0]` as seen by wrapped commands for use as profile names");
 newtype!(SubcommandName, "`argv[1]` as seen by wrapped commands for use as subcommand names");
+newtype!(Timeout, "wall-clock time limits in the `HH:MM:SS` form Firejail expects", is_bad_timeout);
+newtype!(Umask, "file mode creation masks as 3 or 4 octal digits (eg. `0077`)", is_bad_umask);
+newtype!(Capability, "Linux capability names as spelled in `capabilities(7)`", is_bad_capability);
+newtype!(Hostname, "RFC 1123 host names for Firejail's `--hostname`", is_bad_hostname);
+newtype!(InterfaceName, "network interface names for Firejail's `--net`", is_bad_interface);
+newtype!(
+    FirejailProfile,
+    "names of (or absolute paths to) Firejail `.profile` files for `--profile`",
+    is_bad_firejail_profile
+);
+
+impl InterfaceName {
+    /// Return `true` if this is the loopback interface, which every network namespace already has
+    #[must_use]
+    pub fn is_loopback(&self) -> bool {
+        self.0 == "lo"
+    }
+}
+
+impl FileName {
+    /// Join this name onto `dir` without exposing the inner string
+    ///
+    /// (`is_bad_name` having rejected path separators is what makes this safe to do blindly)
+    #[must_use]
+    pub fn join_to(&self, dir: &path::Path) -> path::PathBuf {
+        dir.join(&self.0)
+    }
+
+    /// Return `true` if this is `.` or `..`, which name a directory relative to any other
+    #[must_use]
+    pub fn is_dot_entry(&self) -> bool {
+        self.0 == "." || self.0 == ".."
+    }
+
+    /// Return `true` if this contains a `,`, which would split it in two in a comma-separated
+    /// Firejail option like `--private-bin=`
+    #[must_use]
+    pub fn has_comma(&self) -> bool {
+        self.0.contains(',')
+    }
+}
+
+/// How much of a file a [`RootMarker::Content`] marker will read looking for its substring
+///
+/// (Markers are checked in every ancestor of the working directory, so a stray multi-gigabyte
+/// file with a marker's name shouldn't stall every invocation.)
+pub const MARKER_READ_LIMIT: u64 = 1024 * 1024;
+
+/// An entry in `root_marked_by`: either a bare file/directory name or a file which must also
+/// contain a given substring (eg. a `pyproject.toml` containing `[tool.poetry]`)
+#[derive(Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(untagged)]
+pub enum RootMarker {
+    /// A file or directory which marks the root just by existing
+    Name(FileName),
+    /// A file which marks the root only if its first [`MARKER_READ_LIMIT`] bytes contain
+    /// `contains`
+    Content {
+        /// The name of the file
+        name: FileName,
+        /// The (non-empty) substring to look for
+        contains: String,
+    },
+}
+
+impl RootMarker {
+    /// The file or directory name this marker looks for
+    #[must_use]
+    pub fn name(&self) -> &FileName {
+        match *self {
+            Self::Name(ref name) | Self::Content { ref name, .. } => name,
+        }
+    }
+
+    /// Return `true` if this marker is present in `dir`
+    ///
+    /// Unreadable files never match a [`RootMarker::Content`] marker.
+    #[must_use]
+    pub fn is_in(&self, dir: &path::Path) -> bool {
+        match *self {
+            Self::Name(ref name) => name.join_to(dir).exists(),
+            Self::Content { ref name, ref contains } => {
+                let mut head = Vec::new();
+                fs::File::open(name.join_to(dir))
+                    .and_then(|file| file.take(MARKER_READ_LIMIT).read_to_end(&mut head))
+                    .is_ok()
+                    && head.windows(contains.len()).any(|window| window == contains.as_bytes())
+            },
+        }
+    }
+}
+
+impl fmt::Display for RootMarker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Name(ref name) => name.fmt(f),
+            Self::Content { ref name, ref contains } => {
+                write!(f, "{name} (containing {contains:?})")
+            },
+        }
+    }
+}
+
+/// Deserialize a [`RootMarker`] from either a string or a `{ name = ..., contains = ... }` table
+///
+/// (Implemented as a `Visitor` rather than `#[serde(untagged)]` so that an invalid name still
+/// gets reported instead of a generic "did not match any variant".)
+impl<'de> serde::Deserialize<'de> for RootMarker {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        /// The table form, kept separate so unknown keys in it can be refused
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct ContentMarker {
+            /// See [`RootMarker::Content`]
+            name: FileName,
+            /// See [`RootMarker::Content`]
+            contains: String,
+        }
+
+        /// `Visitor` that dispatches on whether the marker is a string or a table
+        struct MarkerVisitor;
+
+        impl<'de> Visitor<'de> for MarkerVisitor {
+            type Value = RootMarker;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a file name or a table with `name` and `contains` keys")
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                FileName::try_from(value.to_owned()).map(RootMarker::Name).map_err(E::custom)
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let ContentMarker { name, contains } =
+                    serde::Deserialize::deserialize(de::value::MapAccessDeserializer::new(map))?;
+                if contains.is_empty() {
+                    return Err(de::Error::custom("`contains` must not be empty"));
+                }
+                Ok(RootMarker::Content { name, contains })
+            }
+        }
+
+        deserializer.deserialize_any(MarkerVisitor)
+    }
+}
 
 /// Check for end-user misunderstandings in a field expecting a file/command/subcommand name.
 ///
@@ -79,6 +239,170 @@ fn is_bad_name(name: &str) -> Result<(), &'static str> {
     Ok(())
 }
 
+/// Check that a timeout is in the `HH:MM:SS` form accepted by Firejail's `--timeout`
+///
+/// Each field must be exactly two digits, minutes and seconds must be below 60, and the total
+/// must be non-zero. (A zero timeout is far more likely to be a mistake than a request to kill
+/// the command immediately.)
+fn is_bad_timeout(value: &str) -> Result<(), &'static str> {
+    let fields: Vec<&str> = value.split(':').collect();
+    let [hours, minutes, seconds] = fields[..] else {
+        return Err("timeout must be in HH:MM:SS form");
+    };
+    if !fields.iter().all(|x| x.len() == 2 && x.bytes().all(|b| b.is_ascii_digit())) {
+        return Err("timeout fields must each be two digits");
+    }
+    if minutes > "59" || seconds > "59" {
+        return Err("timeout minutes and seconds must be below 60");
+    }
+    if hours == "00" && minutes == "00" && seconds == "00" {
+        return Err("timeout must be non-zero");
+    }
+    Ok(())
+}
+
+/// Check that a umask is 3 or 4 octal digits no greater than `0777`
+///
+/// Requiring the digits (rather than also accepting symbolic forms like `u=rwx,go=`) keeps the
+/// value safe to pass to a shell's `umask` builtin verbatim.
+fn is_bad_umask(value: &str) -> Result<(), &'static str> {
+    if !(value.len() == 3 || value.len() == 4) || !value.bytes().all(|b| (b'0'..=b'7').contains(&b))
+    {
+        return Err("umask must be 3 or 4 octal digits (eg. \"0077\")");
+    }
+    if value.len() == 4 && !value.starts_with('0') {
+        return Err("umask must be no greater than 0777");
+    }
+    Ok(())
+}
+
+impl Capability {
+    /// The name in the form Firejail's `--caps.keep` expects (eg. `net_bind_service` for
+    /// `CAP_NET_BIND_SERVICE`)
+    #[must_use]
+    pub fn firejail_name(&self) -> String {
+        // `is_bad_capability` has already checked that the prefix is there
+        self.0.trim_start_matches("CAP_").to_ascii_lowercase()
+    }
+}
+
+/// Every capability in `capabilities(7)` as of Linux 6.x
+const CAPABILITIES: &[&str] = &[
+    "CAP_AUDIT_CONTROL",
+    "CAP_AUDIT_READ",
+    "CAP_AUDIT_WRITE",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_KILL",
+    "CAP_LEASE",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_MAC_ADMIN",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MKNOD",
+    "CAP_NET_ADMIN",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_RAW",
+    "CAP_PERFMON",
+    "CAP_SETFCAP",
+    "CAP_SETGID",
+    "CAP_SETPCAP",
+    "CAP_SETUID",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_NICE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+];
+
+/// Check that a capability name is one of [`CAPABILITIES`]
+fn is_bad_capability(name: &str) -> Result<(), &'static str> {
+    if CAPABILITIES.contains(&name) {
+        Ok(())
+    } else {
+        Err("capabilities must be named as in capabilities(7) (eg. CAP_NET_BIND_SERVICE)")
+    }
+}
+
+/// Check that a host name follows RFC 1123
+///
+/// That means at most 253 characters, made of `.`-separated labels which are each 1 to 63 ASCII
+/// letters, digits, or `-`, and which don't start or end with `-`.
+fn is_bad_hostname(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() || name.len() > 253 {
+        return Err("host names must be 1 to 253 characters long");
+    }
+    for label in name.split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return Err("host name labels must each be 1 to 63 characters long");
+        }
+        if !label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-') {
+            return Err("host names may only contain ASCII letters, digits, '-', and '.'");
+        }
+        if label.starts_with('-') || label.ends_with('-') {
+            return Err("host name labels can't start or end with '-'");
+        }
+    }
+    Ok(())
+}
+
+/// Check that a network interface name is something Linux could have created
+///
+/// Names must be 1 to 15 characters (`IFNAMSIZ` minus the null terminator) of ASCII letters,
+/// digits, `-`, `_`, or `.`, and can't be `.` or `..`. This is stricter than the kernel, but it
+/// keeps anything resembling a second Firejail flag or a path out of `--net=`.
+fn is_bad_interface(name: &str) -> Result<(), &'static str> {
+    if name.is_empty() || name.len() > 15 {
+        return Err("interface names must be 1 to 15 characters long");
+    }
+    if !name.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_.".contains(&b)) {
+        return Err("interface names may only contain ASCII letters, digits, '-', '_', and '.'");
+    }
+    if name == "." || name == ".." {
+        return Err("interface names can't be '.' or '..'");
+    }
+    Ok(())
+}
+
+/// Check that a Firejail profile reference is either a bare name or an absolute path
+///
+/// Bare names (eg. `cargo` or `cargo.profile`) are looked up by Firejail in its usual profile
+/// directories and must pass [`is_bad_name`]. Anything containing a path separator must be an
+/// absolute path, so a relative path can't silently change meaning with the working directory,
+/// and may not contain `..` components, whitespace, or null bytes.
+fn is_bad_firejail_profile(value: &str) -> Result<(), &'static str> {
+    if !value.contains(path::is_separator) {
+        return is_bad_name(value);
+    }
+    let as_path = path::Path::new(value);
+    if !as_path.is_absolute() {
+        return Err("Firejail profile paths must be absolute (or a bare name with no separators)");
+    }
+    if as_path.components().any(|x| x == path::Component::ParentDir) {
+        return Err("Firejail profile paths must not contain '..'");
+    }
+    if value.chars().any(|x| x.is_whitespace() || x == '\0') {
+        return Err("Firejail profile paths must not contain whitespace or null bytes");
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -98,6 +422,89 @@ mod test {
         assert!(SubcommandName::try_from("foo bar".to_owned()).is_err());
     }
 
+    /// Assert that `Timeout` accepts `HH:MM:SS` and rejects anything Firejail would misinterpret
+    #[test]
+    fn timeout_validation() {
+        assert!(Timeout::try_from("01:30:00".to_owned()).is_ok());
+        assert!(Timeout::try_from("99:59:59".to_owned()).is_ok());
+
+        assert_eq!(is_bad_timeout("90:00"), Err("timeout must be in HH:MM:SS form"));
+        assert_eq!(is_bad_timeout("01:30:00:00"), Err("timeout must be in HH:MM:SS form"));
+        assert_eq!(is_bad_timeout("1:30:00"), Err("timeout fields must each be two digits"));
+        assert_eq!(is_bad_timeout("01:3a:00"), Err("timeout fields must each be two digits"));
+        assert_eq!(is_bad_timeout("+1:30:00"), Err("timeout fields must each be two digits"));
+        assert_eq!(is_bad_timeout("01:60:00"), Err("timeout minutes and seconds must be below 60"));
+        assert_eq!(is_bad_timeout("01:00:60"), Err("timeout minutes and seconds must be below 60"));
+        assert_eq!(is_bad_timeout("00:00:00"), Err("timeout must be non-zero"));
+    }
+
+    /// Assert that `InterfaceName` only accepts plausible Linux interface names
+    #[test]
+    fn interface_validation() {
+        assert!(InterfaceName::try_from("eth0".to_owned()).is_ok());
+        assert!(InterfaceName::try_from("br-lan.10".to_owned()).is_ok());
+
+        assert!(InterfaceName::try_from(String::new()).is_err());
+        assert!(InterfaceName::try_from("sixteen-chars-xx".to_owned()).is_err());
+        assert!(InterfaceName::try_from("eth0 --noroot".to_owned()).is_err());
+        assert!(InterfaceName::try_from("eth0/1".to_owned()).is_err());
+        assert!(InterfaceName::try_from("..".to_owned()).is_err());
+    }
+
+    /// Assert that only real capability names in their `CAP_*` spelling are accepted, and that
+    /// they're converted to Firejail's spelling
+    #[test]
+    fn capability_validation() {
+        let bind = Capability::try_from("CAP_NET_BIND_SERVICE".to_owned()).unwrap();
+        assert_eq!(bind.firejail_name(), "net_bind_service");
+        for bad in ["CAP_NET_BIND", "net_bind_service", "cap_net_bind_service", "CAP_", "ALL"] {
+            assert!(Capability::try_from(bad.to_owned()).is_err(), "{bad:?}");
+        }
+    }
+
+    /// Assert that `Hostname` enforces RFC 1123's length and character rules
+    #[test]
+    fn hostname_validation() {
+        assert_eq!(is_bad_hostname("builder"), Ok(()));
+        assert_eq!(is_bad_hostname("ci-1.example.com"), Ok(()));
+        assert_eq!(is_bad_hostname(&"a".repeat(63)), Ok(()));
+
+        let long = "host names must be 1 to 253 characters long";
+        assert_eq!(is_bad_hostname(""), Err(long));
+        assert_eq!(is_bad_hostname(&vec!["a".repeat(63); 4].join(".")), Err(long));
+        let label = "host name labels must each be 1 to 63 characters long";
+        assert_eq!(is_bad_hostname(&"a".repeat(64)), Err(label));
+        assert_eq!(is_bad_hostname("example..com"), Err(label));
+        assert_eq!(is_bad_hostname("example."), Err(label));
+        let chars = "host names may only contain ASCII letters, digits, '-', and '.'";
+        assert_eq!(is_bad_hostname("build_host"), Err(chars));
+        assert_eq!(is_bad_hostname("host --noroot"), Err(chars));
+        assert_eq!(is_bad_hostname("-host"), Err("host name labels can't start or end with '-'"));
+    }
+
+    /// Assert that `FirejailProfile` accepts bare names and absolute paths only
+    #[test]
+    fn firejail_profile_validation() {
+        assert_eq!(is_bad_firejail_profile("cargo"), Ok(()));
+        assert_eq!(is_bad_firejail_profile("firefox.profile"), Ok(()));
+        assert_eq!(is_bad_firejail_profile("/etc/firejail/cargo.profile"), Ok(()));
+
+        assert_eq!(is_bad_firejail_profile(""), Err("empty string"));
+        assert_eq!(is_bad_firejail_profile("my cargo"), Err("shell argument list"));
+        assert_eq!(
+            is_bad_firejail_profile("profiles/cargo.profile"),
+            Err("Firejail profile paths must be absolute (or a bare name with no separators)")
+        );
+        assert_eq!(
+            is_bad_firejail_profile("/etc/firejail/../../tmp/evil.profile"),
+            Err("Firejail profile paths must not contain '..'")
+        );
+        assert_eq!(
+            is_bad_firejail_profile("/etc/firejail/my cargo.profile"),
+            Err("Firejail profile paths must not contain whitespace or null bytes")
+        );
+    }
+
     /// Assert that is_bad_name rejects supposed filenames/commands/subcommands that are
     /// impossible because they're empty strings or contain binary nulls or path separators
     #[test]
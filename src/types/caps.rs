@@ -1,6 +1,10 @@
 //! Capabilities (in the "POSIX capabilities" sense) that a configuration file may grant
 
-use serde_derive::Deserialize;
+use std::fmt;
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::{Deserialize, Serializer};
+use serde_derive::Serialize;
 
 /// Helper for creating newtypes for boolean sandbox permissions that should not be conflated
 ///
@@ -10,26 +14,85 @@ use serde_derive::Deserialize;
 ///
 /// 1. Following the convention set by `serde(default)`, the false variant will be the default,
 ///    so make sure the false variant is the one which grants fewer permissions when adding new
-///    calls to this macro.
+///    calls to this macro, then add it to `caps_default_to_documented_safe_variant` in the tests.
 ///
 /// 2. Don't implement support for getting the value back out. Instead, convert the value to be
 ///    compared into the newtype.
 ///
 ///    This makes it more difficult to circumvent the protections afforded by using newtypes
 ///    and makes potential footguns more apparent.
+///
+/// 3. Configuration files may spell a capability as either a bool or the variant's name string
+///    (eg. `"all_networks"`), so the name strings are part of the file format. Don't rename them.
 macro_rules! make_capability {
-    ($cap_name:ident, $false_variant:ident, $true_variant:ident,
+    ($cap_name:ident, $false_variant:ident = $false_name:literal,
+     $true_variant:ident = $true_name:literal,
      $cap_desc: expr, $false_desc:expr, $true_desc:expr) => {
         #[doc=$cap_desc]
-        #[derive(Copy, Clone, Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd)]
-        #[serde(from = "bool")]
+        #[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
         pub enum $cap_name {
             #[doc=$false_desc]
+            #[serde(rename = $false_name)]
             $false_variant,
             #[doc=$true_desc]
+            #[serde(rename = $true_name)]
             $true_variant,
         }
 
+        impl<'de> Deserialize<'de> for $cap_name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                /// Accepts either a bool or one of the variant names
+                struct CapVisitor;
+
+                impl Visitor<'_> for CapVisitor {
+                    type Value = $cap_name;
+
+                    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                        write!(f, "`true`, `false`, \"{}\", or \"{}\"", $false_name, $true_name)
+                    }
+
+                    fn visit_bool<E: de::Error>(self, value: bool) -> Result<Self::Value, E> {
+                        Ok(value.into())
+                    }
+
+                    fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                        match value {
+                            $false_name => Ok($cap_name::$false_variant),
+                            $true_name => Ok($cap_name::$true_variant),
+                            _ => Err(E::unknown_variant(value, &[$false_name, $true_name])),
+                        }
+                    }
+                }
+
+                deserializer.deserialize_any(CapVisitor)
+            }
+        }
+
+        impl $cap_name {
+            /// The human-readable description of the variant, for use in diagnostic output
+            pub fn description(self) -> &'static str {
+                match self {
+                    Self::$false_variant => $false_desc,
+                    Self::$true_variant => $true_desc,
+                }
+            }
+
+            /// Serialize as the boolean used in configuration files rather than the variant name
+            ///
+            /// (For use with `#[serde(serialize_with)]` when writing configuration back out. The
+            /// derived `Serialize` is for diagnostic output, where the variant name is clearer.)
+            ///
+            /// # Errors
+            ///
+            /// Only fails if `serializer` does.
+            pub fn serialize_as_bool<S: Serializer>(
+                value: &Self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bool(*value == Self::$true_variant)
+            }
+        }
+
         impl Default for $cap_name {
             fn default() -> Self {
                 Self::$false_variant
@@ -50,30 +113,41 @@ macro_rules! make_capability {
 
 make_capability!(
     Network,
-    ChildProcsOnly,
-    AllNetworks,
+    ChildProcsOnly = "child_procs_only",
+    AllNetworks = "all_networks",
     "Scope of network access",
     "Launch the program in its own network namespace so it can only communicate with subprocesses it launches.",
     "Allow unrestricted network communication.");
 make_capability!(
     ProjectRoot,
-    Innermost,
-    Outermost,
+    Innermost = "innermost",
+    Outermost = "outermost",
     "Policy for identifying the project root directory",
     "Stop looking for the project root at the first match.",
     "Ascend to the filesystem root and then use the most permissive match found."
 );
+make_capability!(
+    AbstractSockets,
+    Isolated = "isolated",
+    Shared = "shared",
+    "Reachability of abstract Unix domain sockets, which are scoped to a network namespace rather than the filesystem",
+    "Keep the program out of the host's network namespace so it can't reach abstract sockets listening outside the sandbox.",
+    "Leave the program in the host's network namespace, with only Unix domain sockets permitted, so abstract sockets outside the sandbox stay reachable."
+);
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use serde_derive::Deserialize;
 
-    #[derive(Deserialize)]
+    #[derive(Debug, Deserialize)]
     struct TestFields {
         #[serde(default)]
         network: Network,
         #[serde(default)]
         project_root: ProjectRoot,
+        #[serde(default)]
+        abstract_sockets: AbstractSockets,
     }
 
     /// Assert that the capability enums err on the side of security when under the influence of
@@ -83,6 +157,48 @@ mod test {
         let test_values: TestFields = toml_edit::de::from_str("").unwrap();
         assert_eq!(test_values.network, Network::ChildProcsOnly);
         assert_eq!(test_values.project_root, ProjectRoot::Innermost);
+        assert_eq!(test_values.abstract_sockets, AbstractSockets::Isolated);
+    }
+
+    /// Assert that each capability's `Default` is the less-permissive variant, by name
+    ///
+    /// The names are the ones used in configuration files, so swapping the arguments to a
+    /// `make_capability!` call can't slip past this by also swapping the variant identifiers.
+    #[test]
+    fn caps_default_to_documented_safe_variant() {
+        /// The name the default variant serializes to
+        fn default_name<T: Default + serde::Serialize>() -> String {
+            serde_json::to_value(T::default()).unwrap().as_str().unwrap().to_owned()
+        }
+
+        assert_eq!(default_name::<Network>(), "child_procs_only");
+        assert_eq!(default_name::<ProjectRoot>(), "innermost");
+        assert_eq!(default_name::<AbstractSockets>(), "isolated");
+        assert_eq!(Network::default(), Network::from(false));
+        assert_eq!(ProjectRoot::default(), ProjectRoot::from(false));
+        assert_eq!(AbstractSockets::default(), AbstractSockets::from(false));
+    }
+
+    /// Assert that a bool and the equivalent variant name deserialize identically and that other
+    /// strings are rejected with a list of the valid names
+    #[test]
+    fn caps_accept_bools_and_names() {
+        let from_bool: TestFields =
+            toml_edit::de::from_str("network = true\nproject_root = false").unwrap();
+        let from_name: TestFields =
+            toml_edit::de::from_str("network = \"all_networks\"\nproject_root = \"innermost\"")
+                .unwrap();
+        assert_eq!(from_bool.network, Network::AllNetworks);
+        assert_eq!(from_bool.network, from_name.network);
+        assert_eq!(from_bool.project_root, from_name.project_root);
+
+        let err = toml_edit::de::from_str::<TestFields>("network = \"yes\"").unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("unknown variant `yes`, expected `child_procs_only` or `all_networks`"),
+            "{err}"
+        );
+        toml_edit::de::from_str::<TestFields>("network = 1").unwrap_err();
     }
 
     /// Assert that refactoring hasn't reversed the meanings of the capability enums
@@ -95,5 +211,7 @@ mod test {
         assert_eq!(Network::from(true), Network::AllNetworks);
         assert_eq!(ProjectRoot::from(false), ProjectRoot::Innermost);
         assert_eq!(ProjectRoot::from(true), ProjectRoot::Outermost);
+        assert_eq!(AbstractSockets::from(false), AbstractSockets::Isolated);
+        assert_eq!(AbstractSockets::from(true), AbstractSockets::Shared);
     }
 }
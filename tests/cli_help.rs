@@ -0,0 +1,38 @@
+//! Tests for the `--help` output
+
+use std::process::Command;
+
+/// Helper to run `nodo` with `args` and return its stdout
+fn help(args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_nodo")).args(args).output().unwrap();
+    assert_eq!(output.status.code(), Some(0), "{args:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+/// Assert that `-h`, `--help`, and no arguments all print the concise form within 80 columns
+fn concise_help() {
+    let concise = help(&["-h"]);
+    assert_eq!(help(&["--help"]), concise);
+    assert_eq!(help(&[]), concise);
+
+    assert!(concise.contains("--help --verbose"), "{concise}");
+    for line in concise.lines() {
+        assert!(line.chars().count() <= 80, "Too long: {line:?}");
+    }
+}
+
+#[test]
+/// Assert that `--help --verbose` and `--help-all` print the full reference
+fn verbose_help() {
+    let verbose = help(&["--help", "--verbose"]);
+    assert_eq!(help(&["-h", "--verbose"]), verbose);
+    assert_eq!(help(&["--help-all"]), verbose);
+
+    assert_ne!(verbose, help(&["--help"]));
+    for flag in ["--dry-run", "--json", "--write-conf", "--dump-config", "--conf-path"] {
+        assert!(verbose.contains(flag), "Missing {flag}");
+    }
+    // The help2man-compatible spacing between `USAGE` lines is kept
+    assert!(verbose.contains("[arguments]\n\n    "), "{verbose}");
+}
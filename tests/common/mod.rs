@@ -0,0 +1,119 @@
+//! Helpers shared between the integration test binaries
+
+// Not every test binary uses every helper
+#![allow(dead_code)]
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+use std::{fs, io};
+
+/// Helper to set up and tear down test directories
+///
+/// Feed `line!()` to the first argument to ensure tests don't race with each other
+/// without needing to pull in the `rand` crate or read from `/dev/urandom`.
+pub fn with_test_dir(test_id: u32, test_cb: fn(&Path)) {
+    let mut test_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    test_dir.push(format!("test_{}_{}", env!("CARGO_CRATE_NAME"), test_id));
+
+    test_dir = ensure_dir(test_dir);
+    test_cb(&test_dir);
+    fs::remove_dir_all(test_dir).unwrap();
+}
+
+/// Helper to `fs::create_dir_all(...).unwrap()` but ignore "already exists" in case a previous
+/// test run failed an assertion and left itself un-cleaned-up to help with diagnosis
+///
+/// Return the path to make the tests using this more concise
+pub fn ensure_dir(path: PathBuf) -> PathBuf {
+    fs::create_dir_all(&path)
+        .or_else(|e| if e.kind() == io::ErrorKind::AlreadyExists { Ok(()) } else { Err(e) })
+        .unwrap();
+    path
+}
+
+/// A configuration using `Innermost` so that the `Cargo.toml` of the crate under test, which
+/// `CARGO_TARGET_TMPDIR` lives inside, can't be mistaken for the project root
+pub const TEST_CONFIG: &str = r#"
+firejail_base_flags = []
+
+[profile.cargo]
+allow_network_subcommands = ["build"]
+deny_subcommands = ["install"]
+projectless_subcommands = ["new"]
+root_marked_by = ["Cargo.toml"]
+subcommand_aliases = { b = "build" }
+
+[profile.make]
+root_marked_by = ["Makefile"]
+"#;
+
+/// Helper to run `nodo` with `cwd` as the working directory and [`TEST_CONFIG`] written into
+/// `config_dir` so the configuration of the user running the tests can't interfere
+pub fn run_nodo(config_dir: &Path, cwd: &Path, args: &[&str]) -> Output {
+    run_nodo_with_config(config_dir, cwd, TEST_CONFIG, args)
+}
+
+/// Like [`run_nodo`], but with `config` in place of [`TEST_CONFIG`]
+pub fn run_nodo_with_config(config_dir: &Path, cwd: &Path, config: &str, args: &[&str]) -> Output {
+    nodo_command(config_dir, cwd, config).args(args).output().unwrap()
+}
+
+/// Write `config` into `config_dir` and return a `Command` for `nodo` with an otherwise empty
+/// environment, for tests which need to customize it further before running it
+pub fn nodo_command(config_dir: &Path, cwd: &Path, config: &str) -> Command {
+    fs::write(config_dir.join("nodo.toml"), config).unwrap();
+    let mut command = Command::new(env!("CARGO_BIN_EXE_nodo"));
+    command.current_dir(cwd).env_clear().env("XDG_CONFIG_HOME", config_dir);
+    command
+}
+
+/// Write an executable shell script named `name` into `dir`
+pub fn write_script(dir: &Path, name: &str, body: &str) {
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\n{body}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// Every option `nodo` may pass to Firejail, as listed by [`write_fake_firejail`]'s `--help`
+pub const FIREJAIL_OPTIONS: &[&str] = &[
+    "--blacklist",
+    "--caps.keep",
+    "--dns",
+    "--env",
+    "--hostname",
+    "--net",
+    "--noroot",
+    "--private-cwd",
+    "--profile",
+    "--protocol",
+    "--quiet",
+    "--read-only",
+    "--read-write",
+    "--seccomp",
+    "--timeout",
+    "--whitelist",
+];
+
+/// Write a fake `firejail` into `dir` which ignores its own flags (except for setting variables
+/// passed with `--env=`) and runs whatever follows `--` unsandboxed, for testing what `nodo` does
+/// around an actual launch
+pub fn write_fake_firejail(dir: &Path) {
+    write_fake_firejail_supporting(dir, FIREJAIL_OPTIONS);
+}
+
+/// [`write_fake_firejail`], but only listing `options` in its `--help` output
+pub fn write_fake_firejail_supporting(dir: &Path, options: &[&str]) {
+    let help: String = options.iter().map(|x| format!("    {x} - (fake)\n")).collect();
+    write_script(
+        dir,
+        "firejail",
+        &format!(
+            "if [ \"$1\" = --help ]; then printf '%s' '{help}'; exit 0; fi\n\
+             while [ \"$1\" != -- ]; do\n\
+                 case \"$1\" in --env=*) export \"${{1#--env=}}\";; esac\n\
+                 shift\n\
+             done\nshift\nexec \"$@\""
+        ),
+    );
+}
@@ -0,0 +1,123 @@
+//! Tests for how problems in the user's configuration file are reported
+
+use std::path::Path;
+
+mod common;
+use common::{nodo_command, run_nodo_with_config, with_test_dir, TEST_CONFIG};
+
+#[test]
+/// Assert that a type error is reported with its location, the offending line, and a hint
+fn type_error_points_at_span() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let config = "firejail_base_flags = []\n\
+                      \n\
+                      [profile.make]\n\
+                      root_marked_by = [\"Makefile\"]\n\
+                      allow_network = \"yes\"\n";
+        let output = run_nodo_with_config(test_dir, test_dir, config, &["make"]);
+        assert_eq!(output.status.code(), Some(1));
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let expected_location = format!(" --> {}:5:17", test_dir.join("nodo.toml").display());
+        assert!(stderr.contains(&expected_location), "{stderr}");
+        assert!(
+            stderr.contains("5 | allow_network = \"yes\"\n  |                 ^^^^^\n"),
+            "{stderr}"
+        );
+        assert!(stderr.contains("hint: "), "{stderr}");
+    });
+}
+
+#[test]
+/// Assert that a config which parses but fails validation gets a clean error and exit code
+/// rather than a panic
+fn validation_failure_is_reported() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        for config in
+            ["firejail_base_flags = []\nprofile = {}", "firejail_base_flags = []\nprofile = []"]
+        {
+            for args in [&["make"][..], &["--explain", "make"], &["--dump-config"]] {
+                let output = run_nodo_with_config(test_dir, test_dir, config, args);
+                assert_eq!(output.status.code(), Some(3), "{args:?}");
+
+                let stderr = String::from_utf8_lossy(&output.stderr);
+                assert_eq!(
+                    stderr,
+                    "CRITICAL FAILURE: Configuration file must contain at least one profile\n"
+                );
+            }
+        }
+
+        // Every problem is reported, not just the first
+        let config = "firejail_base_flags = []\n\
+                      [profile.make]\nroot_marked_by = []\n\
+                      [profile.Make]\nroot_marked_by = [\"Makefile\"]\n";
+        let output = run_nodo_with_config(test_dir, test_dir, config, &["make"]);
+        assert_eq!(output.status.code(), Some(3));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stderr.lines().count(), 2, "{stderr}");
+        assert!(stderr.lines().all(|x| x.starts_with("CRITICAL FAILURE: ")), "{stderr}");
+    });
+}
+
+#[test]
+/// Assert that `--error-format json` reports a validation failure as a single JSON object with
+/// the same exit code as the human-readable form
+fn validation_failure_as_json() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let config = "firejail_base_flags = []\n\
+                      [profile.make]\nroot_marked_by = []\n\
+                      [profile.Make]\nroot_marked_by = [\"Makefile\"]\n";
+        let output =
+            run_nodo_with_config(test_dir, test_dir, config, &["--error-format", "json", "make"]);
+        assert_eq!(output.status.code(), Some(3));
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert_eq!(stderr.lines().count(), 1, "{stderr}");
+        let report: serde_json::Value = serde_json::from_str(&stderr).unwrap();
+        assert_eq!(report["kind"], "validation");
+        assert_eq!(report["path"], test_dir.join("nodo.toml").to_str().unwrap());
+        let message = report["message"].as_str().unwrap();
+        assert!(message.contains("'root_marked_by' must contain at least one"), "{message}");
+        assert!(message.contains("must not differ only by case"), "{message}");
+        assert!(report.get("profile").is_none());
+
+        // Parse errors get their own kind
+        let output = run_nodo_with_config(
+            test_dir,
+            test_dir,
+            "firejail_base_flags = 1",
+            &["--error-format", "json", "make"],
+        );
+        assert_eq!(output.status.code(), Some(1));
+        let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+        assert_eq!(report["kind"], "parse");
+    });
+}
+
+#[test]
+/// Assert that `NODO_CONFIG_SHA256` lets only a configuration with that digest be used, whichever
+/// action reads it
+fn digest_from_environment_enforced() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let config = TEST_CONFIG.replace("[]", "[\"--seccomp\"]");
+        let digest = nodo::sha256::hex_digest(config.as_bytes());
+        let run_expecting = |expected: &str, args: &[&str]| {
+            nodo_command(test_dir, test_dir, &config)
+                .env("NODO_CONFIG_SHA256", expected)
+                .args(args)
+                .output()
+                .unwrap()
+        };
+
+        for args in [&["--explain", "cargo", "build"][..], &["--dump-config"], &["--lint"]] {
+            let output = run_expecting(&digest, args);
+            assert_eq!(output.status.code(), Some(0), "{args:?}");
+
+            let output = run_expecting(&"0".repeat(64), args);
+            assert_eq!(output.status.code(), Some(1), "{args:?}");
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("Configuration does not match NODO_CONFIG_SHA256"), "{stderr}");
+        }
+    });
+}
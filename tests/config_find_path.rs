@@ -2,33 +2,13 @@
 //! the shortcomings of POSIX environment variables discussed in
 //! [rust-lang/rust#90308](https://github.com/rust-lang/rust/issues/90308).
 
-use std::path::{Path, PathBuf};
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::Path;
 use std::process::Command;
-use std::{env, fs, io};
 
-/// Helper to set up and tear down test directories
-///
-/// Feed `line!()` to the first argument to ensure tests don't race with each other
-/// without needing to pull in the `rand` crate or read from `/dev/urandom`.
-fn with_test_dir(test_id: u32, test_cb: fn(&Path)) {
-    let mut test_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
-    test_dir.push(format!("test_config_find_path_{}", test_id));
-
-    test_dir = ensure_dir(test_dir);
-    test_cb(&test_dir);
-    fs::remove_dir_all(test_dir).unwrap();
-}
-
-/// Helper to `fs::create_dir_all(...).unwrap()` but ignore "already exists" in case a previous
-/// test run failed an assertion and left itself un-cleaned-up to help with diagnosis
-///
-/// Return the path to make the tests using this more concise
-fn ensure_dir(path: PathBuf) -> PathBuf {
-    fs::create_dir_all(&path)
-        .or_else(|e| if e.kind() == io::ErrorKind::AlreadyExists { Ok(()) } else { Err(e) })
-        .unwrap();
-    path
-}
+mod common;
+use common::{ensure_dir, with_test_dir};
 
 /// Helper to deduplicate the boilerplate of invoking `--conf-path` with a custom environment
 /// and working directory
@@ -200,6 +180,29 @@ fn fallback_on_invalid() {
     });
 }
 
-// TODO: Decide where std::fs::canonicalize fits into the intended semantics
-// (We want to canonicalize it before handing off to Firejail, but it might be surprising and/or
-// confusing if the text displayed to the user doesn't match what's in XDG_CONFIG_HOME or HOME)
+#[test]
+/// Assert that `--conf-path` shows the path as built from `XDG_CONFIG_HOME` or `HOME`, so it
+/// matches what the user set, while `--conf-path --canonical` resolves symlinks in it
+fn canonical_resolves_symlinks() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let real = ensure_dir(test_dir.join("real"));
+        let link = test_dir.join("link");
+        symlink(&real, &link).unwrap();
+        let canonical = |xdg_config_home: &Path| {
+            Command::new(env!("CARGO_BIN_EXE_nodo"))
+                .args(["--conf-path", "--canonical"])
+                .current_dir(test_dir)
+                .env_clear()
+                .env("XDG_CONFIG_HOME", xdg_config_home)
+                .output()
+                .unwrap()
+        };
+
+        assert_success!(output_for!(test_dir, XDG_CONFIG_HOME => &link), &link);
+        assert_success!(canonical(&link), real.canonicalize().unwrap());
+        // Both forms agree when there's nothing to resolve, whether or not the file exists yet
+        assert_success!(canonical(&real), real.canonicalize().unwrap());
+        fs::write(real.join(format!("{}.toml", env!("CARGO_PKG_NAME"))), "").unwrap();
+        assert_success!(canonical(&link), real.canonicalize().unwrap());
+    });
+}
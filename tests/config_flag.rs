@@ -0,0 +1,63 @@
+//! Tests for choosing the configuration file with `--config`
+
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+mod common;
+use common::{ensure_dir, nodo_command, run_nodo, with_test_dir, TEST_CONFIG};
+
+#[test]
+/// Assert that the sandbox blacklists the file `--config` named rather than the usual one
+fn config_flag_file_is_blacklisted() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let xdg_dir = ensure_dir(test_dir.join("xdg"));
+        let explicit = test_dir.join("explicit.toml");
+        fs::write(&explicit, TEST_CONFIG).unwrap();
+        let explicit = explicit.to_str().unwrap();
+
+        let output = run_nodo(&xdg_dir, test_dir, &["--config", explicit, "--dry-run", "make"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains(&format!(" --blacklist={explicit} ")), "{stdout}");
+        assert!(!stdout.contains(&xdg_dir.display().to_string()), "{stdout}");
+
+        let output = run_nodo(&xdg_dir, test_dir, &["--config", explicit, "--conf-path"]);
+        assert_eq!(output.stdout, format!("{explicit}\n").into_bytes());
+
+        // Unlike the usual location, a missing file isn't quietly replaced by the defaults
+        let missing = test_dir.join("missing.toml");
+        let output = run_nodo(&xdg_dir, test_dir, &["--config", missing.to_str().unwrap(), "make"]);
+        assert_eq!(output.status.code(), Some(1));
+    });
+}
+
+#[test]
+/// Assert that a configuration read from standard input blacklists nothing and says so
+fn config_flag_stdin_blacklists_nothing() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+
+        let mut child = nodo_command(test_dir, test_dir, TEST_CONFIG)
+            .args(["--config", "-", "--debug", "--dry-run", "make"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(TEST_CONFIG.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("firejail "), "{stdout}");
+        assert!(!stdout.contains("--blacklist="), "{stdout}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains("Config: read from standard input, so there is no file to blacklist"),
+            "{stderr}"
+        );
+    });
+}
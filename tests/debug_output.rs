@@ -0,0 +1,151 @@
+//! Tests for the diagnostics printed to stderr by `--debug`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{
+    ensure_dir, nodo_command, run_nodo, run_nodo_with_config, with_test_dir, write_fake_firejail,
+    write_script, TEST_CONFIG,
+};
+
+#[test]
+/// Assert that `--debug` explains which profile rule decided the network access
+fn network_provenance() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+
+        // Upgraded by `allow_network_subcommands` (via the `b` alias)
+        let output = run_nodo(test_dir, &project, &["--debug", "--dry-run", "cargo", "b"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.lines().any(|line| line
+                == "Network: Allow unrestricted network communication. \
+                    (from allow_network_subcommands entry \"build\")"),
+            "{stderr}"
+        );
+
+        // Left at the profile-wide default
+        let output = run_nodo(test_dir, &project, &["--debug", "--dry-run", "cargo", "check"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("(from profile-wide allow_network default)"), "{stderr}");
+
+        // ...and none of it without --debug
+        let output = run_nodo(test_dir, &project, &["--dry-run", "cargo", "b"]);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("Network:"));
+    });
+}
+
+#[test]
+/// Assert the `--debug`/`--dry-run` matrix: the diagnostics only appear with `--debug`, the
+/// command line alone goes to stdout, and nothing is run either way
+///
+/// (Firejail isn't needed for this test to pass, since it must never be executed. If it were,
+/// the missing binary or the command's own output would change the exit code or stdout.)
+fn debug_with_dry_run_never_executes() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Makefile"), "").unwrap();
+        let config = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.make]\nroot_marked_by = [\"Makefile\"]\n";
+
+        let dry_run = run_nodo_with_config(test_dir, &project, config, &["--dry-run", "make"]);
+        assert_eq!(dry_run.status.code(), Some(0));
+        assert!(dry_run.stderr.is_empty(), "{}", String::from_utf8_lossy(&dry_run.stderr));
+        let command_line = String::from_utf8(dry_run.stdout).unwrap();
+        assert_eq!(command_line.lines().count(), 1);
+
+        let both =
+            run_nodo_with_config(test_dir, &project, config, &["--debug", "--dry-run", "make"]);
+        assert_eq!(both.status.code(), Some(0));
+        assert_eq!(String::from_utf8(both.stdout).unwrap(), command_line);
+        let stderr = String::from_utf8(both.stderr).unwrap();
+        assert!(stderr.contains("Network: "), "{stderr}");
+        assert!(
+            stderr.contains(&format!("Root: {} (from innermost", project.display())),
+            "{stderr}"
+        );
+        assert!(
+            stderr
+                .lines()
+                .any(|x| x == format!("Not running (--dry-run): {}", command_line.trim())),
+            "{stderr}"
+        );
+        assert!(!stderr.contains("Running:"), "{stderr}");
+    });
+}
+
+#[test]
+/// Assert that `NODO_LOG` picks which of `nodo`'s own messages appear when no flag does, and
+/// that a flag still overrides it
+fn log_level_from_env() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Makefile"), "").unwrap();
+        let stderr_with = |level: &str, args: &[&str]| {
+            let output = nodo_command(test_dir, &project, TEST_CONFIG)
+                .env("NODO_LOG", level)
+                .args(args)
+                .output()
+                .unwrap();
+            (output.status.code(), String::from_utf8(output.stderr).unwrap())
+        };
+        let warning = "WARNING: The Firejail base flags for this profile are empty";
+
+        // At `error`, the warning about the empty base flags (and any info) is suppressed
+        let (code, stderr) = stderr_with("error", &["--dry-run", "make"]);
+        assert_eq!((code, stderr.as_str()), (Some(0), ""));
+
+        let (_, stderr) = stderr_with("warn", &["--dry-run", "make"]);
+        assert!(stderr.starts_with(warning), "{stderr}");
+        assert!(!stderr.contains("Command: make"), "{stderr}");
+
+        let (_, stderr) = stderr_with("debug", &["--dry-run", "make"]);
+        assert!(stderr.contains("Command: make (no subcommand)"), "{stderr}");
+        assert!(!stderr.contains("Root search:"), "{stderr}");
+
+        let (_, stderr) = stderr_with("trace", &["--quiet", "--dry-run", "make"]);
+        assert_eq!(stderr, "");
+
+        let (code, stderr) = stderr_with("loud", &["--dry-run", "make"]);
+        assert_eq!(code, Some(1));
+        assert!(stderr.contains("NODO_LOG must be error, warn, info, debug, or trace"), "{stderr}");
+    });
+}
+
+#[test]
+/// Assert that `--debug` ends a real run with a summary of how it went, and that nothing is
+/// added to stderr without it
+fn run_summary() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "make", "exit 3");
+        let config = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.make]\nroot_marked_by = [\"Makefile\"]\n";
+        let run = |args: &[&str]| {
+            let output = nodo_command(test_dir, &project, config)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .args(args)
+                .output()
+                .unwrap();
+            (output.status.code(), String::from_utf8(output.stderr).unwrap())
+        };
+
+        let (code, stderr) = run(&["--debug", "make"]);
+        assert_eq!(code, Some(3));
+        let summary = stderr.lines().last().unwrap_or_default();
+        let (prefix, rest) = summary.split_once(" after ").unwrap_or_default();
+        assert_eq!(prefix, "Finished: exit code 3", "{stderr}");
+        let (elapsed, rest) = rest.split_once("s (").unwrap_or_default();
+        elapsed.parse::<f64>().unwrap();
+        assert_eq!(rest, format!("profile: make, root: {})", project.display()));
+
+        let (code, stderr) = run(&["make"]);
+        assert_eq!((code, stderr.as_str()), (Some(3), ""));
+    });
+}
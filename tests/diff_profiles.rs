@@ -0,0 +1,67 @@
+//! Tests for `--diff-profiles`
+
+use std::path::Path;
+
+mod common;
+use common::{run_nodo_with_config, with_test_dir};
+
+/// A configuration where `gmake` is `make` written differently and `slow-make` adds a timeout
+const CONFIG: &str = r#"
+firejail_base_flags = ["--seccomp"]
+system_readonly = []
+
+[profile.make]
+root_marked_by = ["Makefile", "GNUmakefile"]
+whitelist = ["/usr/share/make"]
+
+[profile.gmake]
+whitelist = ["/usr/share/make"]
+root_marked_by = ["GNUmakefile", "Makefile"]
+
+[profile.slow-make]
+root_marked_by = ["Makefile"]
+timeout = "01:00:00"
+whitelist = ["/usr/share/make"]
+"#;
+
+#[test]
+/// Assert that equivalent profiles produce no differences and a changed one is reported as such,
+/// with exit statuses like `diff(1)`
+fn reports_only_real_differences() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let diff = |args: &[&str]| {
+            let mut full_args = vec!["--diff-profiles"];
+            full_args.extend_from_slice(args);
+            let output = run_nodo_with_config(test_dir, test_dir, CONFIG, &full_args);
+            (output.status.code(), String::from_utf8(output.stdout).unwrap())
+        };
+        let config_path = test_dir.join("nodo.toml");
+        let common = [
+            "  firejail".to_owned(),
+            format!("  --blacklist={}", config_path.display()),
+            "  --seccomp".to_owned(),
+            "  --net=none".to_owned(),
+            "  --protocol=unix,inet,inet6,netlink".to_owned(),
+        ];
+
+        let (status, output) = diff(&["make", "gmake", "--root", "/src/project"]);
+        assert_eq!(status, Some(0), "{output}");
+        assert_eq!(
+            output,
+            format!(
+                "--- make\n+++ gmake\n{}\n  --whitelist=/src/project\n  \
+                 --private-cwd=/src/project\n  --whitelist=/usr/share/make\n  --\n",
+                common.join("\n")
+            )
+        );
+
+        let (status, output) = diff(&["make", "slow-make"]);
+        assert_eq!(status, Some(1), "{output}");
+        assert!(output.contains(&format!("{}\n+ --timeout=01:00:00\n", common[4])), "{output}");
+        assert_eq!(output.lines().filter(|x| !x.starts_with("  ")).count(), 3, "{output}");
+        assert!(output.contains("  --whitelist=/PROJECT_ROOT\n"), "{output}");
+
+        let (status, _) = diff(&["make", "ninja"]);
+        assert_eq!(status, Some(2));
+    });
+}
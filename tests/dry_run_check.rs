@@ -0,0 +1,46 @@
+//! Tests for the path checks done by `--dry-run --check`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, run_nodo_with_config, with_test_dir};
+
+#[test]
+/// Assert that a missing whitelist entry is reported without anything being run
+fn reports_missing_whitelist_entry() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Makefile"), "").unwrap();
+        let present = ensure_dir(test_dir.join("present"));
+        let missing = test_dir.join("missing");
+        let config = format!(
+            "firejail_base_flags = [\"--seccomp\"]\n\
+             [profile.make]\n\
+             root_marked_by = [\"Makefile\"]\n\
+             whitelist = [{:?}, {:?}]\n",
+            present.display().to_string(),
+            missing.display().to_string(),
+        );
+
+        let output =
+            run_nodo_with_config(test_dir, &project, &config, &["--dry-run", "--check", "make"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("firejail "));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let warnings: Vec<&str> = stderr.lines().filter(|x| x.starts_with("WARNING: ")).collect();
+        assert_eq!(
+            warnings,
+            [format!(
+                "WARNING: whitelist entry {:?} ({}) does not exist",
+                missing.display().to_string(),
+                missing.display()
+            )],
+            "{stderr}"
+        );
+
+        // Plain --dry-run doesn't look
+        let output = run_nodo_with_config(test_dir, &project, &config, &["--dry-run", "make"]);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("does not exist"));
+    });
+}
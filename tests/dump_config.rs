@@ -0,0 +1,48 @@
+//! Tests for `--dump-config`
+
+use std::path::Path;
+
+mod common;
+use common::{run_nodo, with_test_dir};
+
+#[test]
+/// Assert that the dump shows the effective values of fields the file left to their defaults
+fn shows_defaulted_fields() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let output = run_nodo(test_dir, test_dir, &["--dump-config"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        // `[profile.make]` only sets `root_marked_by` in the test config
+        let make = stdout.split("[profile.make]").nth(1).unwrap();
+        for line in [
+            "allow_network = false",
+            "deny_subcommands = []",
+            "root_marked_by = [\"Makefile\"]",
+            "root_find_outermost = false",
+        ] {
+            assert!(make.lines().any(|x| x == line), "missing {line:?} in:\n{stdout}");
+        }
+        assert!(stdout.lines().any(|x| x == "strict = false"), "{stdout}");
+        assert!(stdout.lines().any(|x| x == "root_blacklist = []"), "{stdout}");
+    });
+}
+
+#[test]
+/// Assert that the dump is itself a valid configuration describing the same profiles
+fn dump_round_trips() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let first = run_nodo(test_dir, test_dir, &["--dump-config"]);
+        assert_eq!(first.status.code(), Some(0));
+
+        let redump_dir = common::ensure_dir(test_dir.join("redump"));
+        let second = common::run_nodo_with_config(
+            &redump_dir,
+            &redump_dir,
+            std::str::from_utf8(&first.stdout).unwrap(),
+            &["--dump-config"],
+        );
+        assert_eq!(second.status.code(), Some(0));
+        assert_eq!(first.stdout, second.stdout);
+    });
+}
@@ -0,0 +1,36 @@
+//! Tests for `--env`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_fake_firejail, write_script};
+
+#[test]
+/// Assert that variables given with `--env` reach the sandboxed command and override the
+/// profile's `env_set`
+fn reaches_child() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "echo \"$GREETING $TARGET\"");
+
+        let config = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.tool]\nroot_marked_by = [\"Makefile\"]\n\
+                      env_set = { GREETING = \"hello\", TARGET = \"profile\" }\n";
+        let run = |args: &[&str]| {
+            let output = nodo_command(test_dir, test_dir, config)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .args(args)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(0), "{args:?}");
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        assert_eq!(run(&["tool"]), "hello profile\n");
+        assert_eq!(run(&["--env", "TARGET=a=b c", "tool"]), "hello a=b c\n");
+        assert_eq!(run(&["--env", "GREETING=hi", "--env", "TARGET=cli", "tool"]), "hi cli\n");
+    });
+}
@@ -0,0 +1,36 @@
+//! Tests for how the exit status of the sandboxed command becomes that of `nodo`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_fake_firejail, write_script};
+
+/// A configuration treating exit code 1 from `tool` as success
+const CONFIG: &str = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.tool]\nroot_marked_by = [\"Makefile\"]\n\
+                      success_exit_codes = [1]\n";
+
+#[test]
+/// Assert that a listed exit code becomes 0 while others pass through unchanged
+fn success_exit_codes_remapped() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "exit \"$1\"");
+
+        let status_for = |code: &str| {
+            let output = nodo_command(test_dir, test_dir, CONFIG)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .args(["tool", code])
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+            output.status.code()
+        };
+        assert_eq!(status_for("0"), Some(0));
+        assert_eq!(status_for("1"), Some(0));
+        assert_eq!(status_for("2"), Some(2));
+    });
+}
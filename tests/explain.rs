@@ -0,0 +1,202 @@
+//! Tests for `--explain` which invoke it as a subprocess against temporary project trees
+
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::process::Output;
+
+use serde_json::{json, Value};
+
+mod common;
+use common::{ensure_dir, run_nodo, run_nodo_with_config, with_test_dir};
+
+/// Helper to run `nodo --explain --json` on the given child command line
+fn explain_json(config_dir: &Path, cwd: &Path, argv: &[&str]) -> Output {
+    run_nodo(config_dir, cwd, &[&["--explain", "--json"], argv].concat())
+}
+
+/// Helper to assert success and parse the JSON object from stdout
+///
+/// Use a macro so `assert_eq!` reports the most useful failure location
+macro_rules! parse_success {
+    ($output:expr) => {{
+        let output = $output;
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+        serde_json::from_slice::<Value>(&output.stdout).unwrap()
+    }};
+}
+
+#[test]
+/// Assert that the JSON output reports the profile, caps, root, and subcommand decisions
+fn json_reports_resolution() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        let src = ensure_dir(project.join("src"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+
+        // Network upgraded by subcommand via alias, and the nearest Cargo.toml marks the root
+        let parsed = parse_success!(explain_json(test_dir, &src, &["cargo", "b", "--release"]));
+        assert_eq!(
+            parsed,
+            json!({
+                "profile": "cargo",
+                "subcommand": "build",
+                "denied": false,
+                "projectless": false,
+                "no_root": false,
+                "network": "all_networks",
+                "network_source": "allow_network_subcommands",
+                "root_find_outermost": "innermost",
+                "root": project.to_str().unwrap(),
+            })
+        );
+
+        // No subcommand means no network upgrade
+        let parsed = parse_success!(explain_json(test_dir, &src, &["cargo", "--version"]));
+        assert_eq!(parsed["subcommand"], Value::Null);
+        assert_eq!(parsed["network"], "child_procs_only");
+        assert_eq!(parsed["network_source"], "allow_network");
+
+        // Projectless subcommands use the current directory
+        let parsed = parse_success!(explain_json(test_dir, &src, &["cargo", "new", "foo"]));
+        assert_eq!(parsed["projectless"], true);
+        assert_eq!(parsed["root"], src.to_str().unwrap());
+
+        // Denied subcommands have no root
+        let parsed = parse_success!(explain_json(test_dir, &src, &["cargo", "install", "foo"]));
+        assert_eq!(parsed["denied"], true);
+        assert_eq!(parsed["root"], Value::Null);
+    });
+}
+
+#[test]
+/// Assert that a root path which isn't valid UTF-8 is encoded losslessly as an array of bytes
+fn json_encodes_non_utf8_paths_losslessly() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let raw_name = b"proj\xff";
+        let project = ensure_dir(test_dir.join(OsStr::from_bytes(raw_name)));
+        fs::write(project.join("Makefile"), "").unwrap();
+
+        let parsed = parse_success!(explain_json(test_dir, &project, &["make"]));
+        let expected: Vec<u8> = project.as_os_str().as_bytes().to_owned();
+        assert_eq!(parsed["root"], json!(expected));
+    });
+}
+
+#[test]
+/// Assert that the human-readable form remains the default
+fn human_readable_is_default() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+
+        let output = run_nodo(test_dir, test_dir, &["--explain", "make", "all"]);
+        assert_eq!(output.status.code(), Some(0));
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(serde_json::from_str::<Value>(&stdout).is_err());
+        assert!(stdout.contains("profile:      make\n"));
+        assert!(stdout.contains(&format!("root:         {}\n", test_dir.display())));
+    });
+}
+
+#[test]
+/// Assert that every alias which resolves to the subcommand is listed, including ones which get
+/// there through another alias
+fn human_readable_alias_fan_in() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Cargo.toml"), "").unwrap();
+        let config = "firejail_base_flags = []\n\
+                      [profile.cargo]\n\
+                      root_marked_by = [\"Cargo.toml\"]\n\
+                      subcommand_aliases = { b = \"bld\", bld = \"build\", t = \"test\" }";
+
+        let output = run_nodo_with_config(test_dir, test_dir, config, &["--explain", "cargo", "b"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains("subcommand:   build\naliases:      b, bld\n"), "{stdout}");
+
+        // Subcommands nothing aliases get no line
+        let output =
+            run_nodo_with_config(test_dir, test_dir, config, &["--explain", "cargo", "check"]);
+        assert!(!String::from_utf8_lossy(&output.stdout).contains("aliases:"));
+    });
+}
+
+#[test]
+/// Assert that `--profile` changes the policy applied without changing the command that's run
+fn profile_override() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+
+        // `cargo` would fail to find a `Cargo.toml` root, but `make` finds the `Makefile`
+        let parsed =
+            parse_success!(explain_json(test_dir, test_dir, &["--profile", "make", "cargo"]));
+        assert_eq!(parsed["profile"], "make");
+        assert_eq!(parsed["root"], test_dir.to_str().unwrap());
+
+        let output =
+            run_nodo(test_dir, test_dir, &["--profile", "make", "--dry-run", "cargo", "b"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(String::from_utf8_lossy(&output.stdout).ends_with(" -- cargo b\n"));
+
+        // Nonexistent profiles are an error rather than a fallback to the `argv[0]` lookup
+        let output = run_nodo(test_dir, test_dir, &["--profile", "ninja", "--dry-run", "make"]);
+        assert_eq!(output.status.code(), Some(2));
+    });
+}
+
+#[test]
+/// Assert that `--explain-all` describes every profile in the default configuration exactly once
+fn explain_all_covers_default_config() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let defaults = concat!(env!("CARGO_MANIFEST_DIR"), "/src/defaults.toml");
+        let output = run_nodo(test_dir, test_dir, &["--config", defaults, "--explain-all"]);
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        let headers: Vec<&str> = include_str!("../src/defaults.toml")
+            .lines()
+            .filter(|x| x.starts_with("[profile."))
+            .collect();
+        assert!(!headers.is_empty());
+        for header in &headers {
+            assert_eq!(stdout.lines().filter(|x| x == header).count(), 1, "{header}\n{stdout}");
+        }
+        assert_eq!(stdout.lines().filter(|x| x.starts_with("[profile.")).count(), headers.len());
+
+        let cargo = stdout.split("\n\n").find(|x| x.starts_with("[profile.cargo]\n")).unwrap();
+        assert!(cargo.contains("\nnetwork:      Launch the program in its own network namespace"));
+        assert!(cargo.contains("\ndenied:       install, login, logout, owner, uninstall\n"));
+        assert!(cargo.contains("\nroot markers: Cargo.toml\n"));
+        assert!(cargo.contains(" --net=none "), "{cargo}");
+        assert!(cargo.contains(" --whitelist=/PROJECT_ROOT "), "{cargo}");
+    });
+}
+
+#[test]
+/// Assert that `--firejail-syntax` renders the options as Firejail profile directives, including
+/// `net none` for a profile without network access
+fn firejail_syntax() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+
+        let output =
+            run_nodo(test_dir, test_dir, &["--explain", "--firejail-syntax", "make", "all"]);
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let lines: Vec<&str> = stdout.lines().collect();
+        assert!(lines.contains(&"net none"), "{stdout}");
+        assert!(lines.contains(&format!("whitelist {}", test_dir.display()).as_str()), "{stdout}");
+        assert!(lines.iter().all(|x| !x.starts_with('-') && x.split(' ').next().is_some()));
+        assert!(!stdout.contains("make"), "{stdout}");
+
+        // Denied subcommands have no sandbox to render
+        let output = run_nodo(
+            test_dir,
+            test_dir,
+            &["--explain", "--firejail-syntax", "cargo", "install", "foo"],
+        );
+        assert!(String::from_utf8_lossy(&output.stdout).contains("denied:"));
+    });
+}
@@ -0,0 +1,59 @@
+//! Tests for refusing to launch when Firejail lacks an option the sandbox needs
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{
+    ensure_dir, nodo_command, with_test_dir, write_fake_firejail_supporting, write_script,
+    FIREJAIL_OPTIONS,
+};
+
+#[test]
+/// Assert that a Firejail without `--seccomp` is refused by default and only warned about
+/// with `fail_closed = false`
+fn refuses_missing_option() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        let options: Vec<&str> =
+            FIREJAIL_OPTIONS.iter().copied().filter(|x| *x != "--seccomp").collect();
+        write_fake_firejail_supporting(&bin, &options);
+        write_script(&bin, "make", "echo ran");
+
+        let run = |setting: &str| {
+            let config = format!(
+                "firejail_base_flags = [\"--seccomp\"]\n{setting}\n\n\
+                 [profile.make]\nroot_marked_by = [\"Makefile\"]\n"
+            );
+            let output = nodo_command(test_dir, test_dir, &config)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .arg("make")
+                .output()
+                .unwrap();
+            (
+                output.status.code(),
+                String::from_utf8(output.stdout).unwrap(),
+                String::from_utf8(output.stderr).unwrap(),
+            )
+        };
+
+        let (code, stdout, stderr) = run("");
+        assert_eq!((code, stdout.as_str()), (Some(1), ""));
+        assert!(
+            stderr.contains(
+                "Not launching the sandbox because the installed Firejail doesn't support \
+                 --seccomp (see fail_closed)"
+            ),
+            "{stderr}"
+        );
+
+        let (code, stdout, stderr) = run("fail_closed = false");
+        assert_eq!((code, stdout.as_str()), (Some(0), "ran\n"));
+        assert_eq!(
+            stderr,
+            "WARNING: Launching anyway because fail_closed is off, but the installed Firejail \
+             doesn't support --seccomp\n"
+        );
+    });
+}
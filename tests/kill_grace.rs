@@ -0,0 +1,56 @@
+//! Tests for passing signals on to the sandbox and escalating to `SIGKILL` after
+//! `kill_grace_seconds`
+
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use nix::sys::signal::{kill, Signal};
+use nix::unistd::Pid;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_fake_firejail, write_script};
+
+#[test]
+/// Assert that a `SIGTERM` sent to `nodo` reaches the sandbox, and that a sandbox which ignores it
+/// is killed once the grace period is up rather than leaving `nodo` waiting on it
+fn sigterm_forwarded_then_escalated() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "stubborn", "trap '' TERM\ntouch ready\nexec sleep 30");
+        write_script(&bin, "polite", "touch ready\nexec sleep 30");
+
+        let terminate = |tool: &str| {
+            let config = format!(
+                "firejail_base_flags = [\"--seccomp\"]\nkill_grace_seconds = 1\n\n\
+                 [profile.{tool}]\nroot_marked_by = [\"Makefile\"]\n"
+            );
+            let _ = fs::remove_file(test_dir.join("ready"));
+            let mut nodo = nodo_command(test_dir, test_dir, &config)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .arg(tool)
+                .spawn()
+                .unwrap();
+            // Wait until the sandboxed command is running, so the signal can't beat the `trap`
+            while !test_dir.join("ready").exists() {
+                thread::sleep(Duration::from_millis(20));
+            }
+
+            let started = Instant::now();
+            kill(Pid::from_raw(nodo.id().try_into().unwrap()), Signal::SIGTERM).unwrap();
+            let status = nodo.wait().unwrap();
+            (status.code(), started.elapsed())
+        };
+
+        let (code, elapsed) = terminate("stubborn");
+        assert_eq!(code, Some(128 + 9));
+        assert!(elapsed >= Duration::from_secs(1) && elapsed < Duration::from_secs(20));
+
+        let (code, elapsed) = terminate("polite");
+        assert_eq!(code, Some(128 + 15));
+        assert!(elapsed < Duration::from_secs(1), "{elapsed:?}");
+    });
+}
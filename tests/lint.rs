@@ -0,0 +1,31 @@
+//! Tests for `--lint`
+
+use std::path::Path;
+
+mod common;
+use common::{run_nodo_with_config, with_test_dir};
+
+#[test]
+/// Assert that undocumented network access is reported on stdout without failing, while
+/// an invalid configuration is still reported as one
+fn warns_without_failing() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let config = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.tool]\nroot_marked_by = [\"Makefile\"]\nallow_network = true\n";
+        let output = run_nodo_with_config(test_dir, test_dir, config, &["--lint"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "WARNING: [profile.tool] 'allow_network' grants network access without a comment \
+             explaining why\n"
+        );
+
+        let documented = config.replace("allow_network", "# Fetches updates\nallow_network");
+        let output = run_nodo_with_config(test_dir, test_dir, &documented, &["--lint"]);
+        assert_eq!((output.status.code(), output.stdout.as_slice()), (Some(0), &b""[..]));
+
+        let invalid = config.replace("[\"Makefile\"]", "[]");
+        let output = run_nodo_with_config(test_dir, test_dir, &invalid, &["--lint"]);
+        assert_eq!(output.status.code(), Some(3));
+    });
+}
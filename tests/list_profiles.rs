@@ -0,0 +1,59 @@
+//! Tests for `--list-profiles`
+
+use std::collections::BTreeSet;
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+mod common;
+use common::{run_nodo, with_test_dir};
+
+/// The default configuration shipped with `nodo`
+const DEFAULTS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/defaults.toml");
+
+#[test]
+/// Assert that the JSON listing of the default configuration has one object per profile in it
+fn json_lists_default_profiles() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let output =
+            run_nodo(test_dir, test_dir, &["--config", DEFAULTS, "--list-profiles", "--json"]);
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+        let parsed: Value = serde_json::from_slice(&output.stdout).unwrap();
+        let profiles = parsed.as_array().unwrap();
+
+        let expected: BTreeSet<&str> = include_str!("../src/defaults.toml")
+            .lines()
+            .filter_map(|x| x.strip_prefix("[profile.")?.strip_suffix(']'))
+            .collect();
+        let listed: BTreeSet<&str> =
+            profiles.iter().map(|x| x["command"].as_str().unwrap()).collect();
+        assert_eq!(listed, expected);
+        assert_eq!(profiles.len(), expected.len());
+
+        let cargo = profiles.iter().find(|x| x["command"] == "cargo").unwrap();
+        assert_eq!(cargo["root_marked_by"], json!(["Cargo.toml"]));
+        assert_eq!(cargo["allow_network"], "child_procs_only");
+        assert_eq!(
+            cargo["deny_subcommands"],
+            json!(["install", "login", "logout", "owner", "uninstall"])
+        );
+        assert_eq!(cargo["projectless_subcommands"], json!(["init", "new"]));
+        assert!(cargo["allow_network_subcommands"].as_array().unwrap().contains(&json!("build")));
+    });
+}
+
+#[test]
+/// Assert that the plain listing remains the default and aligns the markers after the names
+fn plain_listing_is_default() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let output = run_nodo(test_dir, test_dir, &["--list-profiles"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "cargo  Cargo.toml\nmake   Makefile\n"
+        );
+
+        let output = run_nodo(test_dir, test_dir, &["--list-profiles", "--yaml"]);
+        assert_eq!(output.status.code(), Some(1));
+    });
+}
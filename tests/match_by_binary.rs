@@ -0,0 +1,38 @@
+//! Tests for matching profiles by the file `argv[0]` resolves to
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_script};
+
+#[test]
+/// Assert that a name linked to a profile's binary gets that profile only with `match_by_binary`
+fn linked_name_uses_binary_profile() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_script(&bin, "multi", "exit 0");
+        symlink("multi", bin.join("alias")).unwrap();
+
+        let run_with = |setting: &str| {
+            let config = format!(
+                "firejail_base_flags = [\"--seccomp\"]\n{setting}\n\n\
+                 [profile.multi]\nroot_marked_by = [\"Makefile\"]\n"
+            );
+            nodo_command(test_dir, test_dir, &config)
+                .env("PATH", &bin)
+                .args(["--explain", "alias"])
+                .output()
+                .unwrap()
+        };
+
+        let output = run_with("match_by_binary = true");
+        assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.contains("multi"), "{stdout}");
+
+        assert_eq!(run_with("").status.code(), Some(2));
+    });
+}
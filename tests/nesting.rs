@@ -0,0 +1,60 @@
+//! Tests for `nodo` being invoked from inside a `nodo` sandbox
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{nodo_command, with_test_dir, TEST_CONFIG};
+
+#[test]
+/// Assert that a nested invocation is refused unless `allow_nesting` is set, in which case the
+/// command is checked against the policy as usual and then passed straight through instead of
+/// being wrapped in a second sandbox
+fn nested_invocation() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+
+        let output = nodo_command(test_dir, test_dir, TEST_CONFIG)
+            .env("NODO_ACTIVE", "1")
+            .args(["--dry-run", "make", "all"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("inside another nodo sandbox (NODO_ACTIVE is set)"), "{stderr}");
+        assert!(output.stdout.is_empty());
+
+        let config = format!("allow_nesting = true\n{TEST_CONFIG}");
+        let output = nodo_command(test_dir, test_dir, &config)
+            .env("NODO_ACTIVE", "1")
+            .args(["--dry-run", "make", "all"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(output.stdout, b"make all\n");
+
+        // ...but only once it has passed the same checks as any other command line
+        let restricted = format!("allowed_commands = [\"make\"]\n{config}");
+        for (config, args, expected) in [
+            (&config, &["--dry-run", "cargo", "install"][..], Some(1)),
+            (&config, &["--dry-run", "ninja"], Some(2)),
+            (&restricted, &["--dry-run", "cargo", "build"], Some(1)),
+            (&restricted, &["--dry-run", "make", "all"], Some(0)),
+        ] {
+            let output = nodo_command(test_dir, test_dir, config)
+                .env("NODO_ACTIVE", "1")
+                .args(args)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), expected, "{args:?}");
+            assert_eq!(output.stdout.is_empty(), expected != Some(0), "{args:?}");
+        }
+
+        // ...and without the marker, a sandbox is created as usual
+        let output = nodo_command(test_dir, test_dir, &config)
+            .args(["--dry-run", "make", "all"])
+            .output()
+            .unwrap();
+        assert!(String::from_utf8_lossy(&output.stdout).starts_with("firejail "));
+    });
+}
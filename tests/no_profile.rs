@@ -0,0 +1,56 @@
+//! Tests for running commands which have no profile configured
+
+use std::io::Write;
+use std::path::Path;
+use std::process::Stdio;
+
+mod common;
+use common::{nodo_command, run_nodo, with_test_dir, TEST_CONFIG};
+
+#[test]
+/// Assert that an unconfigured command gets a dedicated exit code and a pointer to the config
+fn points_at_config_file() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        for args in
+            [&["ninja", "all"][..], &["--explain", "ninja"], &["--profile", "ninja", "make"]]
+        {
+            let output = run_nodo(test_dir, test_dir, args);
+            assert_eq!(output.status.code(), Some(2), "{args:?}");
+
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            assert!(stderr.contains("No profile configured for ninja."), "{stderr}");
+            let hint = format!(
+                "add a [profile.ninja] section to {}",
+                test_dir.join("nodo.toml").display()
+            );
+            assert!(stderr.contains(&hint), "{stderr}");
+            assert!(output.stdout.is_empty());
+        }
+    });
+}
+
+#[test]
+/// Assert that with `--config -`, the hint points at standard input rather than claiming the
+/// configuration directory couldn't be found
+fn points_at_stdin_config() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let mut child = nodo_command(test_dir, test_dir, TEST_CONFIG)
+            .args(["--config", "-", "ninja"])
+            .stdin(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(TEST_CONFIG.as_bytes()).unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert_eq!(output.status.code(), Some(2));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.contains(
+                "add a [profile.ninja] section to the configuration passed on standard input"
+            ),
+            "{stderr}"
+        );
+        assert!(!stderr.contains("XDG_CONFIG_HOME"), "{stderr}");
+    });
+}
@@ -0,0 +1,53 @@
+//! Tests for the `pre_exec` check run inside the sandbox before the command
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_fake_firejail, write_script};
+
+#[test]
+/// Assert that the check runs before the command and that a failing one stops the command from
+/// running, with the check's exit status
+fn check_runs_first() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "echo \"ran $*\" >> log");
+        write_script(&bin, "check", "echo \"check $1\" >> log\nexit \"$2\"");
+
+        let run_with_check = |code: &str, args: &[&str]| {
+            let config = format!(
+                "firejail_base_flags = [\"--seccomp\"]\n\n\
+                 [profile.tool]\nroot_marked_by = [\"Makefile\"]\n\
+                 pre_exec = [\"check\", \"a b\", \"{code}\"]\n"
+            );
+            let _ = fs::remove_file(test_dir.join("log"));
+            let output = nodo_command(test_dir, test_dir, &config)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .env("SHELL", bin.join("tool"))
+                .args(args)
+                .output()
+                .unwrap();
+            let log = fs::read_to_string(test_dir.join("log")).unwrap_or_default();
+            (output.status.code(), String::from_utf8(output.stderr).unwrap(), log)
+        };
+
+        assert_eq!(
+            run_with_check("0", &["tool", "x"]),
+            (Some(0), String::new(), "check a b\nran x\n".to_owned())
+        );
+
+        let (code, stderr, log) = run_with_check("3", &["tool", "x"]);
+        assert_eq!(code, Some(3));
+        assert!(stderr.contains("pre_exec check failed with status 3"), "{stderr}");
+        assert_eq!(log, "check a b\n");
+
+        // `--shell` skips the check so a failing one can be investigated
+        assert_eq!(
+            run_with_check("3", &["--shell", "tool"]),
+            (Some(0), String::new(), "ran \n".to_owned())
+        );
+    });
+}
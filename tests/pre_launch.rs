@@ -0,0 +1,41 @@
+//! Tests for the `pre_launch` hook run on the host before each launch
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_fake_firejail, write_script};
+
+#[test]
+/// Assert that the hook runs before the command and that a failing one prevents the launch
+fn failing_hook_prevents_launch() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "echo ran >> log");
+        write_script(&bin, "hook", "echo hook >> log\nexit \"$1\"");
+
+        let run_with_hook = |code: &str| {
+            let config = format!(
+                "firejail_base_flags = [\"--seccomp\"]\npre_launch = [\"hook\", \"{code}\"]\n\n\
+                 [profile.tool]\nroot_marked_by = [\"Makefile\"]\n"
+            );
+            let _ = fs::remove_file(test_dir.join("log"));
+            let output = nodo_command(test_dir, test_dir, &config)
+                .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+                .arg("tool")
+                .output()
+                .unwrap();
+            let log = fs::read_to_string(test_dir.join("log")).unwrap_or_default();
+            (output.status.code(), String::from_utf8(output.stderr).unwrap(), log)
+        };
+
+        assert_eq!(run_with_hook("0"), (Some(0), String::new(), "hook\nran\n".to_owned()));
+
+        let (code, stderr, log) = run_with_hook("3");
+        assert_eq!(code, Some(1));
+        assert!(stderr.contains("Not launching the sandbox because pre_launch failed"), "{stderr}");
+        assert_eq!(log, "hook\n");
+    });
+}
@@ -0,0 +1,42 @@
+//! Tests for `--print-root`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, run_nodo, run_nodo_with_config, with_test_dir};
+
+#[test]
+/// Assert that the root is discovered from a nested working directory, with or without a command
+fn prints_root_from_nested_dir() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        let nested = ensure_dir(project.join("src/bin"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+
+        for args in [&["--print-root"][..], &["--print-root", "--", "cargo", "build"]] {
+            let output = run_nodo(test_dir, &nested, args);
+            assert_eq!(output.status.code(), Some(0), "{args:?}");
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                format!("{}\n", project.display()),
+                "{args:?}"
+            );
+        }
+    });
+}
+
+#[test]
+/// Assert that failing to find a root is a non-zero exit with nothing on stdout
+fn fails_without_root() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let config = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.tool]\nroot_marked_by = [\"nodo-test-no-such-marker\"]\n";
+
+        for args in [&["--print-root"][..], &["--print-root", "tool"]] {
+            let output = run_nodo_with_config(test_dir, test_dir, config, args);
+            assert_ne!(output.status.code(), Some(0), "{args:?}");
+            assert!(output.stdout.is_empty(), "{args:?}");
+        }
+    });
+}
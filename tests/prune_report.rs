@@ -0,0 +1,32 @@
+//! Tests for `--prune-report`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, run_nodo, with_test_dir};
+
+#[test]
+/// Assert that profiles are split by whether their markers appear anywhere under the directory
+fn splits_used_and_unused_profiles() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let tree = ensure_dir(test_dir.join("src"));
+        let project = ensure_dir(tree.join("rust/project"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+
+        let output = run_nodo(test_dir, test_dir, &["--prune-report", tree.to_str().unwrap()]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!(
+                "Profiles with markers present:\n    cargo (eg. {})\n\
+                 Profiles with no markers present (candidates for removal):\n    make\n",
+                project.display()
+            )
+        );
+
+        // A typo is an error rather than a report that everything is unused
+        let output = run_nodo(test_dir, test_dir, &["--prune-report", "/nonexistent/nodo-test"]);
+        assert_eq!(output.status.code(), Some(1));
+    });
+}
@@ -0,0 +1,104 @@
+//! Tests for `--stdin`, `--stdout`, and `--stderr`
+
+use std::fs;
+use std::path::Path;
+use std::process::{Output, Stdio};
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir, write_fake_firejail, write_script};
+
+/// A configuration where `tool` is rooted at a `Makefile`
+const CONFIG: &str = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.tool]\nroot_marked_by = [\"Makefile\"]\n";
+
+/// Run `nodo` with `args` in `test_dir` (which holds the fake Firejail in `bin`), with standard
+/// input connected to a pipe that's never written to or closed
+fn run(test_dir: &Path, args: &[&str]) -> Output {
+    let mut child = nodo_command(test_dir, test_dir, CONFIG)
+        .env("PATH", format!("{}:/usr/bin:/bin", test_dir.join("bin").display()))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // Hold standard input open so only `--stdin null` can give the command end-of-file
+    let _stdin = child.stdin.take();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+/// Assert that the command's output can be sent to a file in the project root and that
+/// `--stdin null` leaves it nothing to wait for
+fn redirects_inside_sandbox() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        fs::write(test_dir.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "echo out; echo err >&2; cat; echo done");
+
+        let output = run(test_dir, &["--stdin", "null", "--stdout", "out.log", "tool"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(output.stdout, b"");
+        assert_eq!(output.stderr, b"err\n");
+        assert_eq!(fs::read_to_string(test_dir.join("out.log")).unwrap(), "out\ndone\n");
+
+        // Existing contents are replaced, as with the shell's `>`
+        let output = run(test_dir, &["--stdin", "null", "--stderr", "out.log", "tool"]);
+        assert_eq!(
+            (output.status.code(), output.stdout.as_slice()),
+            (Some(0), &b"out\ndone\n"[..])
+        );
+        assert_eq!(fs::read_to_string(test_dir.join("out.log")).unwrap(), "err\n");
+    });
+}
+
+#[test]
+/// Assert that a redirect outside the sandbox's writable locations is refused before anything
+/// is run
+fn refuses_redirect_outside_sandbox() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "echo ran");
+
+        let outside = test_dir.join("outside.log");
+        let output = nodo_command(test_dir, &project, CONFIG)
+            .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+            .args(["--stdin", "null", "--stdout", outside.to_str().unwrap(), "tool"])
+            .output()
+            .unwrap();
+        assert_eq!((output.status.code(), output.stdout.as_slice()), (Some(1), &b""[..]));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Refusing to redirect to"), "{stderr}");
+        assert!(!outside.exists());
+    });
+}
+
+#[test]
+/// Assert that a symlink in the project root pointing outside the sandbox is refused rather than
+/// followed, leaving its target untouched
+fn refuses_symlink_redirect() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Makefile"), "").unwrap();
+        let bin = ensure_dir(test_dir.join("bin"));
+        write_fake_firejail(&bin);
+        write_script(&bin, "tool", "echo ran");
+
+        let outside = test_dir.join("outside.rc");
+        fs::write(&outside, "original\n").unwrap();
+        std::os::unix::fs::symlink(&outside, project.join("build.log")).unwrap();
+        let output = nodo_command(test_dir, &project, CONFIG)
+            .env("PATH", format!("{}:/usr/bin:/bin", bin.display()))
+            .args(["--stdin", "null", "--stdout", "build.log", "tool"])
+            .output()
+            .unwrap();
+        assert_eq!((output.status.code(), output.stdout.as_slice()), (Some(1), &b""[..]));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("Refusing to redirect to"), "{stderr}");
+        assert_eq!(fs::read_to_string(&outside).unwrap(), "original\n");
+    });
+}
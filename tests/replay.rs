@@ -0,0 +1,58 @@
+//! Tests for `--replay`
+
+use std::path::Path;
+
+mod common;
+use common::{nodo_command, run_nodo, run_nodo_with_config, with_test_dir, TEST_CONFIG};
+
+/// The sample cases, recorded against [`TEST_CONFIG`]
+const CASES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/replay");
+
+#[test]
+/// Assert that the sample cases pass against the configuration they were recorded with and that
+/// changing a profile they depend on is reported as a mismatch
+fn detects_changed_profile() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let output = run_nodo(test_dir, test_dir, &["--replay", CASES]);
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "ok       cargo_build.toml\nok       cargo_new.toml\nok       unknown_command.toml\n"
+        );
+
+        let changed = TEST_CONFIG.replace("allow_network_subcommands = [\"build\"]", "");
+        let output = run_nodo_with_config(test_dir, test_dir, &changed, &["--replay", CASES]);
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "MISMATCH cargo_build.toml: network: expected \"all_networks\", got \
+             \"child_procs_only\"\nok       cargo_new.toml\nok       unknown_command.toml\n"
+        );
+
+        let output = run_nodo(test_dir, test_dir, &["--replay", "/nonexistent/nodo-test"]);
+        assert_eq!(output.status.code(), Some(1));
+    });
+}
+
+#[test]
+/// Assert that being unable to create scratch directories is a clean failure naming the path
+/// tried, rather than a panic
+fn unwritable_temp_dir() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        // Read-only even for root, unlike a directory with its write permission removed
+        let output = nodo_command(test_dir, test_dir, TEST_CONFIG)
+            .env("NODO_TMPDIR", "/proc")
+            .args(["--replay", CASES])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.starts_with(
+                "CRITICAL FAILURE: Could not create a temporary directory at /proc/nodo-replay-0-"
+            ),
+            "{stderr}"
+        );
+        assert!(!stderr.contains("panicked"));
+    });
+}
@@ -0,0 +1,44 @@
+//! Tests for `--root`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, run_nodo, with_test_dir};
+
+#[test]
+/// Assert that `--root` replaces marker-based discovery but not the safety checks on the result
+fn root_overrides_discovery() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let outer = ensure_dir(test_dir.join("outer")).canonicalize().unwrap();
+        let inner = ensure_dir(outer.join("inner"));
+        let cwd = ensure_dir(inner.join("src"));
+        fs::write(inner.join("Makefile"), "").unwrap();
+        let root_flag = |dir: &Path| format!("--whitelist={}", dir.display());
+
+        let output = run_nodo(test_dir, &cwd, &["--dry-run", "make"]);
+        assert_eq!(output.status.code(), Some(0));
+        assert!(String::from_utf8_lossy(&output.stdout).contains(&root_flag(&inner)));
+
+        let outer_arg = outer.to_str().unwrap();
+        let output = run_nodo(test_dir, &cwd, &["--root", outer_arg, "--dry-run", "make"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(&format!("{} ", root_flag(&outer))), "{stdout}");
+        assert!(!stdout.contains(&root_flag(&inner)), "{stdout}");
+
+        // Relative to the working directory, like any other path argument, but normalized
+        let output = run_nodo(test_dir, &cwd, &["--root", "../..", "--dry-run", "make"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.contains(&format!("{} ", root_flag(&outer))), "{stdout}");
+
+        // A root which doesn't contain the working directory or doesn't exist is refused
+        let elsewhere = ensure_dir(test_dir.join("elsewhere"));
+        for root in [elsewhere.to_str().unwrap(), "/nonexistent/nodo-test"] {
+            let output = run_nodo(test_dir, &cwd, &["--root", root, "--dry-run", "make"]);
+            assert_eq!(output.status.code(), Some(1), "{root}");
+            assert!(output.stdout.is_empty(), "{root}");
+        }
+    });
+}
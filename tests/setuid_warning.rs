@@ -0,0 +1,38 @@
+//! Tests for the warning about sandboxing setuid binaries
+
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir};
+
+/// A configuration whose `tool` profile needs no project root
+const CONFIG: &str = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.tool]\nroot_marked_by = [\"nodo-test-no-such-marker\"]\n\
+                      projectless_subcommands = [\"run\"]\n";
+
+#[test]
+/// Assert that a setuid-marked file found via `$PATH` is warned about and an ordinary one isn't
+fn warns_about_setuid_program() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let bin = ensure_dir(test_dir.join("bin"));
+        let tool = bin.join("tool");
+        fs::write(&tool, "").unwrap();
+
+        let stderr_for_mode = |mode: u32| {
+            fs::set_permissions(&tool, fs::Permissions::from_mode(mode)).unwrap();
+            let output = nodo_command(test_dir, test_dir, CONFIG)
+                .env("PATH", &bin)
+                .args(["--dry-run", "tool", "run"])
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(0));
+            String::from_utf8(output.stderr).unwrap()
+        };
+
+        let stderr = stderr_for_mode(0o4755);
+        assert!(stderr.contains(&format!("WARNING: {} is setuid", tool.display())), "{stderr}");
+        assert_eq!(stderr_for_mode(0o755), "");
+    });
+}
@@ -0,0 +1,42 @@
+//! Tests for `--shell`
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, with_test_dir};
+
+/// [`common::TEST_CONFIG`] with base flags, so the sandbox has something to compare
+const CONFIG: &str = "firejail_base_flags = [\"--seccomp\"]\n\n\
+                      [profile.cargo]\nroot_marked_by = [\"Cargo.toml\"]\n\
+                      allow_network_subcommands = [\"build\"]\n";
+
+#[test]
+/// Assert that the shell replaces the command as the final argv while the sandbox flags
+/// (including the subcommand-dependent network access) are exactly what the command would get
+fn shell_gets_identical_sandbox() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+
+        let dry_run = |args: &[&str], shell: Option<&str>| {
+            let mut command = nodo_command(test_dir, &project, CONFIG);
+            if let Some(shell) = shell {
+                command.env("SHELL", shell);
+            }
+            let output = command.arg("--dry-run").args(args).output().unwrap();
+            assert_eq!(output.status.code(), Some(0), "{args:?}");
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        let direct = dry_run(&["cargo", "build"], None);
+        let flags = direct.strip_suffix(" -- cargo build\n").unwrap();
+        assert!(!flags.contains("--net=none"), "{flags}");
+
+        assert_eq!(
+            dry_run(&["--shell", "cargo", "build"], Some("/bin/zsh")),
+            format!("{flags} -- /bin/zsh\n")
+        );
+        assert_eq!(dry_run(&["--shell", "cargo", "build"], None), format!("{flags} -- /bin/sh\n"));
+    });
+}
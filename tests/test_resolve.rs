@@ -0,0 +1,56 @@
+//! Tests for `--test-resolve`
+
+use std::path::Path;
+
+mod common;
+use common::{run_nodo, with_test_dir};
+
+#[test]
+/// Assert that sample command lines resolve as the profiles in [`common::TEST_CONFIG`] dictate
+/// without any project existing on disk
+fn resolves_sample_command_lines() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let resolve = |args: &[&str]| {
+            let mut full_args = vec!["--test-resolve"];
+            full_args.extend_from_slice(args);
+            let output = run_nodo(test_dir, test_dir, &full_args);
+            assert_eq!(output.status.code(), Some(0), "{args:?}");
+            String::from_utf8(output.stdout).unwrap()
+        };
+
+        assert_eq!(
+            resolve(&["cargo", "b", "--release"]),
+            "profile:      cargo\n\
+             subcommand:   build\n\
+             aliases:      b\n\
+             network:      Allow unrestricted network communication. \
+             (from allow_network_subcommands entry \"build\")\n\
+             root markers: Cargo.toml\n\
+             root policy:  Stop looking for the project root at the first match.\n\
+             root:         (would search for the root markers above)\n"
+        );
+
+        let denied = resolve(&["cargo", "install", "ripgrep"]);
+        assert!(denied.contains("denied:       Must be run outside the sandbox."), "{denied}");
+        assert!(!denied.contains("root:"), "{denied}");
+
+        let projectless = resolve(&["cargo", "new", "foo"]);
+        assert!(projectless.ends_with("root:         (the current directory)\n"), "{projectless}");
+        assert!(projectless.contains("(from profile-wide allow_network default)"), "{projectless}");
+
+        // Arguments are kept as given rather than split on whitespace, as in a real invocation
+        let quoted = resolve(&["--", "cargo", "new foo"]);
+        assert!(quoted.contains("subcommand:   (none)\n"), "{quoted}");
+        assert!(!quoted.contains("(the current directory)"), "{quoted}");
+
+        // The root is taken as given, without needing to exist, and overrides projectless
+        for argv in [&["make", "all"][..], &["cargo", "new", "foo"]] {
+            let rooted = resolve(&[&["--root", "/nonexistent/nodo-test", "--"][..], argv].concat());
+            assert!(rooted.ends_with("root:         /nonexistent/nodo-test\n"), "{rooted}");
+        }
+
+        // Resolution failures are reported as they would be for a real invocation
+        let output = run_nodo(test_dir, test_dir, &["--test-resolve", "ninja"]);
+        assert_eq!(output.status.code(), Some(2));
+    });
+}
@@ -0,0 +1,64 @@
+//! Tests for the root discovery walk printed to stderr by `--trace` (or `NODO_DEBUG=2`)
+
+use std::fs;
+use std::path::Path;
+
+mod common;
+use common::{ensure_dir, nodo_command, run_nodo, with_test_dir, TEST_CONFIG};
+
+#[test]
+/// Assert that `--trace` lists each directory examined, innermost first, until the root is found
+fn lists_directories_in_order() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        let cwd = ensure_dir(project.join("src/bin"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+
+        let output = run_nodo(test_dir, &cwd, &["--trace", "--dry-run", "cargo", "check"]);
+        assert_eq!(output.status.code(), Some(0));
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let walk: Vec<&str> =
+            stderr.lines().filter_map(|line| line.strip_prefix("Root search: ")).collect();
+        assert_eq!(
+            walk,
+            [
+                format!("{}: Cargo.toml (missing)", cwd.display()),
+                format!("{}: Cargo.toml (missing)", project.join("src").display()),
+                format!("{}: Cargo.toml (found)", project.display()),
+            ],
+            "{stderr}"
+        );
+
+        // ...and none of it with plain --debug
+        let output = run_nodo(test_dir, &cwd, &["--debug", "--dry-run", "cargo", "check"]);
+        assert!(!String::from_utf8_lossy(&output.stderr).contains("Root search:"));
+    });
+}
+
+#[test]
+/// Assert that `NODO_DEBUG` sets the debug level when no flag does and that flags win over it
+fn debug_level_from_environment() {
+    with_test_dir(line!(), |test_dir: &Path| {
+        let project = ensure_dir(test_dir.join("project"));
+        fs::write(project.join("Cargo.toml"), "").unwrap();
+        let stderr = |level: &str, args: &[&str]| {
+            let output = nodo_command(test_dir, &project, TEST_CONFIG)
+                .env("NODO_DEBUG", level)
+                .args(args)
+                .output()
+                .unwrap();
+            assert_eq!(output.status.code(), Some(0));
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        };
+
+        let traced = stderr("2", &["--dry-run", "cargo", "check"]);
+        assert!(traced.contains("Root search: "), "{traced}");
+        assert!(traced.contains("Network: "), "{traced}");
+
+        // --debug overrides the environment in either direction
+        let debugged = stderr("0", &["--debug", "--dry-run", "cargo", "check"]);
+        assert!(debugged.contains("Network: "), "{debugged}");
+        let debugged = stderr("2", &["--debug", "--dry-run", "cargo", "check"]);
+        assert!(!debugged.contains("Root search: "), "{debugged}");
+    });
+}